@@ -0,0 +1,67 @@
+//! Times `toto bulk-add` on a large list with and without `--dup-check`.
+//!
+//! Run with `cargo bench --bench bulk_add`.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use toto::output::Output;
+use toto::{Cli, NewTodo};
+
+/// Open todos already in the list.
+const EXISTING: usize = 20_000;
+/// Lines in the file being added.
+const LINES: usize = 2_000;
+const RUNS: u32 = 5;
+
+fn main() -> anyhow::Result<()> {
+    let base = std::env::temp_dir().join(format!("toto-bench-bulk-add-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&base);
+    fs::create_dir_all(&base)?;
+
+    let seed = base.join("seed");
+    fs::create_dir_all(&seed)?;
+    let mut cli = Cli::new(seed.to_string_lossy().into_owned());
+    for n in 0..EXISTING {
+        cli.add(NewTodo::new(format!("Existing chore {}", n)))?;
+    }
+    cli.save()?;
+    let todos = fs::read(seed.join("todos.json"))?;
+    let lines: String = (0..LINES)
+        .map(|n| format!("New chore {} | 3 | bench\n", n))
+        .collect();
+    let file = base.join("lines.txt");
+    fs::write(&file, lines)?;
+
+    for args in [&["bulk-add"][..], &["bulk-add", "--dup-check"]] {
+        let took = time(&base, &todos, &file, args)?;
+        println!(
+            "{:<24} {:>8.1} ms per run ({} lines onto {} todos)",
+            args.join(" "),
+            took.as_secs_f64() * 1000.0,
+            LINES,
+            EXISTING
+        );
+    }
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+// The mean time `args` takes on a fresh copy of `todos`.
+fn time(base: &Path, todos: &[u8], file: &Path, args: &[&str]) -> anyhow::Result<Duration> {
+    let dir = base.join("run");
+    let mut total = Duration::ZERO;
+    for _ in 0..RUNS {
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("todos.json"), todos)?;
+        let file = file.to_string_lossy();
+        let start = Instant::now();
+        Cli::new(dir.to_string_lossy().into_owned())
+            .with_output(Output::new(std::io::sink(), std::io::sink()))
+            .run_from(["toto"].iter().chain(args).chain([&file.as_ref()]))?;
+        total += start.elapsed();
+    }
+    Ok(total / RUNS)
+}
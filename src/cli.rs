@@ -2,23 +2,44 @@
 use std::env;
 */
 
-use anyhow::{Context, Result};
-use chrono::{
-    DateTime, Utc,
-    serde::{ts_seconds, ts_seconds_option},
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use clap::{
+    Arg, ArgAction, ArgGroup, ArgMatches, Command, ValueEnum, command, parser::ValueSource,
+    value_parser,
 };
-use clap::{Arg, ArgAction, Command, command, value_parser};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::process::Command as ProcessCommand;
 use std::{
     cmp::Ordering,
-    collections::HashMap,
-    fs::OpenOptions,
-    io::{BufReader, BufWriter},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::{self, BufWriter, ErrorKind, IsTerminal, Write},
+    ops::Range,
+    path::{Path, PathBuf},
 };
 use uuid::Uuid;
 
-use crate::term;
+use crate::config::{self, Config};
+use crate::diff::{self, Change};
+use crate::doctor;
+use crate::export::{self, ExportFormat};
+use crate::gzip;
+use crate::integrity;
+use crate::journal;
+use crate::merge::{self, Side, Strategy};
+use crate::outcome::Outcome;
+use crate::output::Output;
+use crate::prompt;
+use crate::remind::{self, Reminders};
+use crate::stats::{self, BucketSize, GroupBy, PorcelainVersion};
+use crate::term::{self, Align, Borders, Color, Column, Layout, Row, Size};
+use crate::time::{self, DateStyle, Tz, ts_milliseconds, ts_milliseconds_option};
+use crate::urgency::{self, Weights};
+
+// Still reachable from here, where it lived before moving to `merge`.
+pub use crate::merge::with_archived;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Todo {
@@ -26,23 +47,322 @@ pub struct Todo {
     pub data: TodoData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Todo {
+    /// The names `field` accepts: `id` and every field of `TodoData`.
+    pub const FIELDS: [&str; 21] = [
+        "id",
+        "title",
+        "description",
+        "priority",
+        "status",
+        "created_at",
+        "in_progress_at",
+        "completed_at",
+        "deleted_at",
+        "notes",
+        "parent",
+        "tags",
+        "color",
+        "icon",
+        "due_at",
+        "blocked_by",
+        "auto_start",
+        "snoozed_until",
+        "last_touched_at",
+        "snooze_interval",
+        "resnoozes",
+    ];
+
+    /// One field as it is stored in `todos.json`, or `None` for a name
+    /// that isn't in `FIELDS`.
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let id = cli.add(NewTodo::new("Call the plumber"))?;
+    /// let todo = cli.todo(id).unwrap();
+    /// assert_eq!(todo.field("title"), Some(json!("Call the plumber")));
+    /// assert_eq!(todo.field("status"), Some(json!("Pending")));
+    /// assert_eq!(todo.field("colour"), None);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn field(&self, name: &str) -> Option<Value> {
+        if name == "id" {
+            return Some(Value::String(self.id.to_string()));
+        }
+        match serde_json::to_value(&self.data) {
+            Ok(Value::Object(mut fields)) => fields.remove(name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TodoData {
     pub title: String,
     pub description: Option<String>,
     pub priority: u8,
     pub status: TodoStatus,
-    #[serde(with = "ts_seconds")]
+    #[serde(with = "ts_milliseconds")]
     pub created_at: DateTime<Utc>,
-    #[serde(with = "ts_seconds_option")]
+    #[serde(with = "ts_milliseconds_option")]
     pub in_progress_at: Option<DateTime<Utc>>,
-    #[serde(with = "ts_seconds_option")]
+    #[serde(with = "ts_milliseconds_option")]
     pub completed_at: Option<DateTime<Utc>>,
-    #[serde(with = "ts_seconds_option")]
+    #[serde(with = "ts_milliseconds_option")]
     pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub parent: Option<Uuid>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    pub due_at: Option<DateTime<Utc>>,
+    /// Todos that have to be completed before this one can be worked on.
+    #[serde(default)]
+    pub blocked_by: Vec<Uuid>,
+    /// Start this todo automatically once everything in `blocked_by` is done.
+    #[serde(default)]
+    pub auto_start: bool,
+    /// Kept out of `list`, `next` and `top` until then.
+    #[serde(default, with = "ts_milliseconds_option")]
+    pub snoozed_until: Option<DateTime<Utc>>,
+    /// When `touch` last marked the todo as still relevant.
+    #[serde(default, with = "ts_milliseconds_option")]
+    pub last_touched_at: Option<DateTime<Utc>>,
+    /// Seconds to snooze the todo for again whenever it comes back and is
+    /// left alone, set by `snooze --every`.
+    #[serde(default)]
+    pub snooze_interval: Option<i64>,
+    /// How many times in a row it was snoozed again that way.
+    #[serde(default)]
+    pub resnoozes: u32,
+}
+
+impl TodoData {
+    /// How long the todo has been in progress, or was until it was
+    /// completed. `None` if it was never started or is pending again.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::time::format_duration;
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let now = Utc::now();
+    /// let mut cli = Cli::new(String::from("."));
+    /// let id = cli.add(NewTodo {
+    ///     in_progress: true,
+    ///     started_at: Some(now - TimeDelta::hours(1)),
+    ///     created_at: Some(now - TimeDelta::hours(2)),
+    ///     ..NewTodo::new("Draft the proposal")
+    /// })?;
+    /// let took = cli.todo(id).unwrap().data.in_progress_for(now);
+    /// assert_eq!(took.map(format_duration).as_deref(), Some("1h"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn in_progress_for(&self, now: DateTime<Utc>) -> Option<TimeDelta> {
+        let started = self.in_progress_at?;
+        match self.status {
+            TodoStatus::InProgress => Some(now - started),
+            TodoStatus::Completed => self.completed_at.map(|completed| completed - started),
+            TodoStatus::Pending | TodoStatus::Deleted => None,
+        }
+    }
+
+    /// When the todo was created or last touched, whichever is later. Age
+    /// and staleness are measured from here.
+    pub fn last_active_at(&self) -> DateTime<Utc> {
+        self.last_touched_at
+            .map_or(self.created_at, |touched| touched.max(self.created_at))
+    }
+
+    /// Whether any of the todo's blockers is still open. `status_of` looks
+    /// a blocker up; one it can't find, e.g. because it was archived, counts
+    /// as done.
+    pub fn is_blocked(&self, status_of: impl Fn(&Uuid) -> Option<TodoStatus>) -> bool {
+        self.blocked_by.iter().any(|id| {
+            status_of(id).is_some_and(|status| {
+                matches!(status, TodoStatus::Pending | TodoStatus::InProgress)
+            })
+        })
+    }
+
+    /// Whether the todo is snoozed at `now`.
+    pub fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        self.snoozed_until.is_some_and(|until| until > now)
+    }
+}
+
+/// A timestamped free-form note attached to a todo.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub text: String,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parses the contents of a todo file or archive. A byte order mark is
+/// skipped and an empty or whitespace-only file is an empty list, as
+/// editors and other tools tend to leave those behind.
+///
+/// ```
+/// use toto::cli::parse_todos;
+///
+/// assert!(parse_todos(b"").unwrap().is_empty());
+/// assert!(parse_todos(b"[]\n").unwrap().is_empty());
+/// assert!(parse_todos(b" \n\t\n").unwrap().is_empty());
+/// assert!(parse_todos(b"\xEF\xBB\xBF[]").unwrap().is_empty());
+/// assert!(parse_todos(b"{}").is_err());
+/// ```
+pub fn parse_todos(contents: &[u8]) -> serde_json::Result<Vec<Todo>> {
+    let contents = contents.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(contents);
+    if contents.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Vec::new());
+    }
+    serde_json::from_slice(contents)
+}
+
+/// Reads the todo file, archive or import at `path` with `parse_todos`,
+/// decompressing it first if it is gzip. A missing file is an empty list.
+///
+/// ```
+/// let path = std::env::temp_dir().join("toto-read-todos-doctest.json");
+/// std::fs::write(&path, "  \n")?;
+/// assert!(toto::cli::read_todos(&path)?.is_empty());
+/// std::fs::remove_file(&path)?;
+/// assert!(toto::cli::read_todos(&path)?.is_empty());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn read_todos(path: &Path) -> Result<Vec<Todo>> {
+    match read_todo_bytes(path)? {
+        Some(contents) => decode_todos(path, &contents),
+        None => Ok(Vec::new()),
+    }
+}
+
+// The undecoded contents of `path` for `read_todos`, `None` if it is missing.
+fn read_todo_bytes(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+// The todos in `contents`, as read from `path` by `read_todo_bytes`.
+fn decode_todos(path: &Path, contents: &[u8]) -> Result<Vec<Todo>> {
+    let contents = gzip::decode(path, contents)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_todos(&contents).with_context(|| format!("Failed to deserialize {}", path.display()))
+}
+
+/// Trims and lowercases tags, dropping empty and repeated ones.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// Reads a priority given as a number or as one of the names `high`, `medium`,
+/// `low` and `none`. The names start the thirds that org export maps onto
+/// `A`, `B` and `C`, with `none` the unprioritised 255.
+///
+/// ```
+/// use toto::cli::parse_priority;
+///
+/// assert_eq!(parse_priority("High"), Ok(0));
+/// assert_eq!(parse_priority("low"), Ok(170));
+/// assert_eq!(parse_priority("42"), Ok(42));
+/// assert!(parse_priority("256").is_err());
+/// ```
+pub fn parse_priority(value: &str) -> Result<u8, String> {
+    match value.trim().to_lowercase().as_str() {
+        "high" => Ok(0),
+        "medium" => Ok(85),
+        "low" => Ok(170),
+        "none" => Ok(u8::MAX),
+        number => number.parse().map_err(|_| {
+            format!(
+                "expected 0-255 or high, medium, low or none, got '{}'",
+                value
+            )
+        }),
+    }
+}
+
+/// Reads the `tag_priorities` setting, comma separated `TAG=PRIORITY` pairs
+/// such as `urgent=high, someday=200`, with priorities as `parse_priority`
+/// takes them.
+pub fn parse_tag_priorities(value: &str) -> Result<BTreeMap<String, u8>, String> {
+    let mut priorities = BTreeMap::new();
+    for pair in value.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (tag, priority) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("expected TAG=PRIORITY, got '{}'", pair.trim()))?;
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() {
+            return Err(format!("missing tag in '{}'", pair.trim()));
+        }
+        priorities.insert(tag, parse_priority(priority)?);
+    }
+    Ok(priorities)
+}
+
+/// Reads a `--field-separator`: a single character or one of the escapes
+/// `\t`, `\0`, `\n` and `\\`.
+///
+/// ```
+/// use toto::cli::parse_separator;
+///
+/// assert_eq!(parse_separator(","), Ok(String::from(",")));
+/// assert_eq!(parse_separator("\\0"), Ok(String::from("\0")));
+/// assert!(parse_separator("::").is_err());
+/// ```
+pub fn parse_separator(value: &str) -> Result<String, String> {
+    let separator = match value {
+        "\\t" => "\t",
+        "\\0" => "\0",
+        "\\n" => "\n",
+        "\\\\" => "\\",
+        other if other.chars().count() == 1 => other,
+        _ => {
+            return Err(format!(
+                "expected a single character or one of \\t, \\0, \\n and \\\\, got '{}'",
+                value
+            ));
+        }
+    };
+    Ok(separator.to_string())
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+impl TodoData {
+    /// The status implied by the lifecycle timestamps, latest stage first.
+    pub fn status_from_timestamps(&self) -> TodoStatus {
+        if self.deleted_at.is_some() {
+            TodoStatus::Deleted
+        } else if self.completed_at.is_some() {
+            TodoStatus::Completed
+        } else if self.in_progress_at.is_some() {
+            TodoStatus::InProgress
+        } else {
+            TodoStatus::Pending
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ValueEnum)]
 pub enum TodoStatus {
     Pending,
     InProgress,
@@ -50,9 +370,1032 @@ pub enum TodoStatus {
     Deleted,
 }
 
+impl TodoStatus {
+    /// The status as the command line spells it.
+    ///
+    /// ```
+    /// use toto::TodoStatus;
+    ///
+    /// let names: Vec<_> = [
+    ///     TodoStatus::Pending,
+    ///     TodoStatus::InProgress,
+    ///     TodoStatus::Completed,
+    ///     TodoStatus::Deleted,
+    /// ]
+    /// .iter()
+    /// .map(|status| (status.name(), status.letter()))
+    /// .collect();
+    /// assert_eq!(
+    ///     names,
+    ///     [("pending", "P"), ("in-progress", "I"), ("completed", "C"), ("deleted", "D")]
+    /// );
+    /// ```
+    ///
+    /// `toto status <id>` prints it, or the letter with `--short`:
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-status-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let out = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok(out.contents())
+    /// };
+    /// for title in ["Pending", "Started", "Done", "Dropped"] {
+    ///     run(&["add", "--title", title, "--priority", "1"])?;
+    /// }
+    /// run(&["start", "--id", "1"])?;
+    /// run(&["complete", "--id", "2"])?;
+    /// run(&["delete", "--id", "3"])?;
+    ///
+    /// let statuses = |short: bool| -> anyhow::Result<String> {
+    ///     let mut all = String::new();
+    ///     for id in ["0", "1", "2", "3"] {
+    ///         all += &run(&[&["status", id][..], if short { &["--short"] } else { &[] }].concat())?;
+    ///     }
+    ///     Ok(all)
+    /// };
+    /// assert_eq!(statuses(false)?, "pending\nin-progress\ncompleted\ndeleted\n");
+    /// assert_eq!(statuses(true)?, "P\nI\nC\nD\n");
+    ///
+    /// let missing = uuid::Uuid::from_u128(7).to_string();
+    /// assert_eq!(run(&["status", &missing]).unwrap_err().to_string(), "Todo not found");
+    /// assert!(run(&["status", "99"]).is_err());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn name(&self) -> &'static str {
+        match self {
+            TodoStatus::Pending => "pending",
+            TodoStatus::InProgress => "in-progress",
+            TodoStatus::Completed => "completed",
+            TodoStatus::Deleted => "deleted",
+        }
+    }
+
+    /// The one-letter form the compact list layouts show.
+    pub fn letter(&self) -> &'static str {
+        match self {
+            TodoStatus::Pending => "P",
+            TodoStatus::InProgress => "I",
+            TodoStatus::Completed => "C",
+            TodoStatus::Deleted => "D",
+        }
+    }
+}
+
+/// Criteria a todo must meet to be shown by `list`. Every criterion that is
+/// set must match.
+#[derive(Clone, Debug, Default)]
+pub struct ListFilter {
+    pub title_contains: Option<String>,
+    pub desc_contains: Option<String>,
+    pub case_sensitive: bool,
+    /// Completed todos older than this are hidden without being archived.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::cli::{ListFilter, TodoData};
+    ///
+    /// let ago = |days| (Utc::now() - TimeDelta::days(days)).timestamp_millis();
+    /// let todo = |status: &str, completed_at: Option<i64>| -> TodoData {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "title": "Done",
+    ///         "description": null,
+    ///         "priority": 1,
+    ///         "status": status,
+    ///         "created_at": ago(30),
+    ///         "in_progress_at": null,
+    ///         "completed_at": completed_at,
+    ///         "deleted_at": null,
+    ///     }))
+    ///     .unwrap()
+    /// };
+    /// let old = todo("Completed", Some(ago(10)));
+    /// let recent = todo("Completed", Some(ago(1)));
+    /// let open = todo("Pending", None);
+    ///
+    /// let filter = ListFilter {
+    ///     hide_completed_after: Some(TimeDelta::days(7)),
+    ///     ..ListFilter::default()
+    /// };
+    /// let shown = |todo| filter.matches(todo);
+    /// assert_eq!((shown(&old), shown(&recent), shown(&open)), (false, true, true));
+    /// ```
+    pub hide_completed_after: Option<TimeDelta>,
+    /// Only open todos neither created nor touched within this long.
+    pub stale_after: Option<TimeDelta>,
+    /// Only todos created, started, completed, deleted or touched within
+    /// this long.
+    pub changed_within: Option<TimeDelta>,
+    /// Only open todos due on the current date in `tz`.
+    ///
+    /// ```
+    /// use chrono::{FixedOffset, TimeDelta, Utc};
+    /// use toto::cli::{ListFilter, TodoData};
+    /// use toto::time::Tz;
+    ///
+    /// let offset = FixedOffset::east_opt(5 * 3600).unwrap();
+    /// let midnight = Utc::now()
+    ///     .with_timezone(&offset)
+    ///     .date_naive()
+    ///     .and_hms_opt(0, 0, 0)
+    ///     .unwrap()
+    ///     .and_local_timezone(offset)
+    ///     .unwrap()
+    ///     .to_utc();
+    /// let tomorrow = midnight + TimeDelta::days(1);
+    /// let second = TimeDelta::seconds(1);
+    ///
+    /// let due = |at: chrono::DateTime<Utc>| -> TodoData {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "title": "Due",
+    ///         "description": null,
+    ///         "priority": 1,
+    ///         "status": "Pending",
+    ///         "created_at": 0,
+    ///         "in_progress_at": null,
+    ///         "completed_at": null,
+    ///         "deleted_at": null,
+    ///         "due_at": at.timestamp_millis(),
+    ///     }))
+    ///     .unwrap()
+    /// };
+    /// let todos = [
+    ///     due(midnight - second),
+    ///     due(midnight),
+    ///     due(tomorrow - second),
+    ///     due(tomorrow),
+    /// ];
+    /// let filter = ListFilter {
+    ///     due_today: true,
+    ///     tz: Tz::Fixed(offset),
+    ///     ..ListFilter::default()
+    /// };
+    /// let today: Vec<bool> = todos.iter().map(|todo| filter.matches(todo)).collect();
+    /// assert_eq!(today, [false, true, true, false]);
+    /// ```
+    pub due_today: bool,
+    /// Only open todos whose due time has passed.
+    pub overdue: bool,
+    pub tz: Tz,
+    /// Only todos in one of these states, unless empty.
+    pub statuses: Vec<TodoStatus>,
+    /// Only todos carrying at least one of these tags, unless empty.
+    pub any_tags: Vec<String>,
+    /// Only todos carrying every one of these tags. Combines with
+    /// `any_tags`, so a todo must pass both.
+    pub all_tags: Vec<String>,
+    /// Leave out todos that are snoozed.
+    pub hide_snoozed: bool,
+    /// Only todos that can be worked on now: pending or in progress, not
+    /// snoozed and not blocked. Blockers are looked up by
+    /// `Selection::apply`, as `matches` only sees one todo.
+    pub actionable: bool,
+}
+
+impl ListFilter {
+    /// Whether `todo` passes every filter that is set.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::{Cli, ListFilter, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let old = || NewTodo {
+    ///     created_at: Some(Utc::now() - TimeDelta::days(10)),
+    ///     ..NewTodo::new("Return the books")
+    /// };
+    /// let done = cli.add(old())?;
+    /// cli.complete(done, None)?;
+    /// let untouched = cli.add(old())?;
+    ///
+    /// let recent = ListFilter {
+    ///     changed_within: Some(TimeDelta::days(2)),
+    ///     ..ListFilter::default()
+    /// };
+    /// assert!(recent.matches(&cli.todo(done).unwrap().data));
+    /// assert!(!recent.matches(&cli.todo(untouched).unwrap().data));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn matches(&self, todo: &TodoData) -> bool {
+        if let Some(needle) = &self.title_contains
+            && !self.contains(&todo.title, needle)
+        {
+            return false;
+        }
+        if let Some(needle) = &self.desc_contains {
+            // A todo without a description never matches a description filter.
+            match &todo.description {
+                Some(description) if self.contains(description, needle) => {}
+                _ => return false,
+            }
+        }
+        if let Some(threshold) = self.hide_completed_after
+            && todo.status == TodoStatus::Completed
+            && todo
+                .completed_at
+                .is_some_and(|completed| Utc::now() - completed > threshold)
+        {
+            return false;
+        }
+        if let Some(threshold) = self.stale_after {
+            let open = matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress);
+            if !open || Utc::now() - todo.last_active_at() <= threshold {
+                return false;
+            }
+        }
+        if let Some(window) = self.changed_within
+            && Utc::now() - doctor::latest_timestamp(todo) > window
+        {
+            return false;
+        }
+        if !self.statuses.is_empty() && !self.statuses.contains(&todo.status) {
+            return false;
+        }
+        if !self.any_tags.is_empty() && !self.any_tags.iter().any(|tag| todo.tags.contains(tag)) {
+            return false;
+        }
+        if !self.all_tags.iter().all(|tag| todo.tags.contains(tag)) {
+            return false;
+        }
+        if self.hide_snoozed && todo.is_snoozed(Utc::now()) {
+            return false;
+        }
+        if self.actionable
+            && (!matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress)
+                || todo.is_snoozed(Utc::now()))
+        {
+            return false;
+        }
+        if self.due_today || self.overdue {
+            // Deadline views are about what is still to be done.
+            let open = matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress);
+            let Some(due_at) = todo.due_at.filter(|_| open) else {
+                return false;
+            };
+            let now = Utc::now();
+            if self.due_today && self.tz.date(due_at) != self.tz.date(now) {
+                return false;
+            }
+            if self.overdue && due_at >= now {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether `needle` is in `haystack`, ignoring case unless
+    /// `case_sensitive` is set. Backs `--title-contains` and
+    /// `--desc-contains`.
+    ///
+    /// ```
+    /// use toto::cli::{ListFilter, TodoData};
+    ///
+    /// let todo = |title: &str, description: Option<&str>| -> TodoData {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "title": title,
+    ///         "description": description,
+    ///         "priority": 1,
+    ///         "status": "Pending",
+    ///         "created_at": 0,
+    ///         "in_progress_at": null,
+    ///         "completed_at": null,
+    ///         "deleted_at": null,
+    ///     }))
+    ///     .unwrap()
+    /// };
+    /// let described = todo("Call the Bank", Some("Ask about the Invoice"));
+    /// let bare = todo("Bank holiday plans", None);
+    ///
+    /// let title = ListFilter { title_contains: Some(String::from("bank")), ..ListFilter::default() };
+    /// assert!(title.matches(&described) && title.matches(&bare));
+    /// let exact = ListFilter { case_sensitive: true, ..title.clone() };
+    /// assert!(!exact.matches(&described));
+    ///
+    /// // No description never matches a description filter.
+    /// let desc = ListFilter { desc_contains: Some(String::from("invoice")), ..ListFilter::default() };
+    /// assert!(desc.matches(&described) && !desc.matches(&bare));
+    /// ```
+    fn contains(&self, haystack: &str, needle: &str) -> bool {
+        if self.case_sensitive {
+            haystack.contains(needle)
+        } else {
+            haystack.to_lowercase().contains(&needle.to_lowercase())
+        }
+    }
+}
+
+/// Which halves of `sync` to run.
+///
+/// ```
+/// use std::path::Path;
+/// use std::process::Command;
+/// use toto::cli::{Cli, Todo};
+///
+/// # let base = std::env::temp_dir().join(format!("toto-doc-sync-mode-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&base);
+/// # std::fs::create_dir_all(&base)?;
+/// let git = |dir: &Path, args: &[&str]| -> anyhow::Result<String> {
+///     let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+///     anyhow::ensure!(output.status.success(), "git {:?} failed", args);
+///     Ok(String::from_utf8(output.stdout)?.trim().to_string())
+/// };
+/// git(&base, &["init", "-q", "--bare", "remote.git"])?;
+/// git(&base, &["clone", "-q", "remote.git", "home"])?;
+/// let home = base.join("home");
+/// git(&home, &["config", "user.name", "Toto"])?;
+/// git(&home, &["config", "user.email", "toto@example.com"])?;
+/// let pushed = || git(&base.join("remote.git"), &["rev-list", "--all", "--count"]);
+/// let archived = || -> anyhow::Result<usize> {
+///     Ok(std::fs::read_dir(&home)?
+///         .filter(|entry| entry.as_ref().is_ok_and(|e| e.file_name().to_string_lossy().starts_with("completed_")))
+///         .count())
+/// };
+/// let run = |args: &[&str]| Cli::new(home.to_string_lossy().into_owned()).run_from(["toto"].iter().chain(args));
+/// let active = || -> anyhow::Result<usize> {
+///     let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(home.join("todos.json"))?)?;
+///     Ok(todos.len())
+/// };
+///
+/// run(&["add", "--title", "File the taxes", "--priority", "1", "--done"])?;
+/// run(&["add", "--title", "Renew the lease", "--priority", "1"])?;
+/// run(&["sync", "--archive-only"])?;
+/// assert_eq!((active()?, archived()?, pushed()?.as_str()), (1, 1, "0"));
+///
+/// run(&["add", "--title", "Email the landlord", "--priority", "1", "--done"])?;
+/// run(&["sync", "--git-only"])?;
+/// assert_eq!((active()?, pushed()?.as_str()), (2, "1"));
+///
+/// run(&["sync"])?;
+/// assert_eq!((active()?, pushed()?.as_str()), (1, "2"));
+/// # std::fs::remove_dir_all(&base)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+    Both,
+    ArchiveOnly,
+    GitOnly,
+}
+
+/// A change `retag` makes to the tags of every todo it selects. Tag names
+/// are normalized like those given to `add`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagOp {
+    Add(String),
+    Remove(String),
+    Rename { from: String, to: String },
+}
+
+impl TagOp {
+    /// The tags `tags` become, or `None` if they stay the same.
+    pub fn changes(&self, tags: &[String]) -> Option<Vec<String>> {
+        let name = |tag: &str| tag.trim().to_lowercase();
+        let changed = match self {
+            TagOp::Add(tag) => {
+                let mut changed = tags.to_vec();
+                changed.push(name(tag));
+                normalize_tags(&changed)
+            }
+            TagOp::Remove(tag) => tags
+                .iter()
+                .filter(|existing| **existing != name(tag))
+                .cloned()
+                .collect(),
+            TagOp::Rename { from, to } => {
+                let from = name(from);
+                let renamed: Vec<String> = tags
+                    .iter()
+                    .map(|tag| if *tag == from { name(to) } else { tag.clone() })
+                    .collect();
+                normalize_tags(&renamed)
+            }
+        };
+        (changed != tags).then_some(changed)
+    }
+}
+
+/// How many todos `tag` changes before it wants `--yes`.
+///
+/// ```
+/// use toto::cli::{Cli, Todo, TAG_BATCH_CONFIRM};
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-tag-confirm-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+/// for n in 0..=TAG_BATCH_CONFIRM {
+///     run(&["add", "--title", &format!("Chore {}", n), "--priority", "1"])?;
+/// }
+/// let tagged = || -> anyhow::Result<usize> {
+///     let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+///     Ok(todos.iter().filter(|todo| todo.data.tags == ["home"]).count())
+/// };
+///
+/// let err = run(&["tag", "add", "home"]).unwrap_err();
+/// assert_eq!(err.to_string(), format!("This changes the tags of {} todos, pass --yes to go ahead", TAG_BATCH_CONFIRM + 1));
+/// assert_eq!(tagged()?, 0);
+/// run(&["tag", "add", "home", "--yes"])?;
+/// assert_eq!(tagged()?, TAG_BATCH_CONFIRM + 1);
+///
+/// let help = run(&["tag", "add", "--help"]).unwrap_err().to_string();
+/// assert!(help.contains(&format!("Confirm changing more than {} todos at once", TAG_BATCH_CONFIRM)));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub const TAG_BATCH_CONFIRM: usize = 10;
+
+/// Which todos `list` and `export` include, and in what order.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    pub filter: ListFilter,
+    pub sort: SortKey,
+    /// Where todos without the field sorted by go.
+    pub nulls: Nulls,
+    /// Used when sorting by urgency.
+    pub urgency: Weights,
+}
+
+impl Selection {
+    /// The todos that match the filter, in the chosen order, each with its
+    /// position in `todos`. `todos` must be in list order (see
+    /// `ordered_todos`) so the positions give the human ids `parse_todo_id`
+    /// accepts: filtering and sorting happen after numbering.
+    ///
+    /// ```
+    /// use toto::cli::{ListFilter, NewTodo, Selection};
+    /// use toto::Cli;
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let open = cli.add(NewTodo::new("Open"))?;
+    /// cli.add(NewTodo {
+    ///     blocked_by: vec![open],
+    ///     ..NewTodo::new("Blocked")
+    /// })?;
+    /// let done = cli.add(NewTodo::new("Done"))?;
+    /// cli.complete(done, None)?;
+    /// let deleted = cli.add(NewTodo::new("Deleted"))?;
+    /// cli.delete(deleted, None)?;
+    /// let snoozed = cli.add(NewTodo::new("Snoozed"))?;
+    /// cli.snooze(snoozed, chrono::Utc::now() + chrono::TimeDelta::days(1), false)?;
+    ///
+    /// let selection = Selection {
+    ///     filter: ListFilter { actionable: true, ..ListFilter::default() },
+    ///     ..Selection::default()
+    /// };
+    /// let titles: Vec<String> = cli
+    ///     .list_filtered(&selection)
+    ///     .into_iter()
+    ///     .map(|todo| todo.data.title)
+    ///     .collect();
+    /// assert_eq!(titles, ["Open"]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn apply<'a>(&self, todos: &'a [Todo]) -> Vec<(usize, &'a Todo)> {
+        let statuses: HashMap<Uuid, TodoStatus> = if self.filter.actionable {
+            todos
+                .iter()
+                .map(|todo| (todo.id, todo.data.status.clone()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        let mut rows: Vec<(usize, &Todo)> = todos
+            .iter()
+            .enumerate()
+            .filter(|(_, todo)| self.filter.matches(&todo.data))
+            .filter(|(_, todo)| {
+                !self.filter.actionable || !todo.data.is_blocked(|id| statuses.get(id).cloned())
+            })
+            .collect();
+        match self.sort {
+            SortKey::Priority => {}
+            SortKey::Created => rows.sort_by_key(|(_, todo)| todo.data.created_at),
+            SortKey::Active => rows.sort_by_key(|(_, todo)| todo.data.last_active_at()),
+            SortKey::Title => rows.sort_by_key(|(_, todo)| todo.data.title.to_lowercase()),
+            SortKey::Due => {
+                rows.sort_by(|(_, a), (_, b)| self.nulls.compare(a.data.due_at, b.data.due_at))
+            }
+            SortKey::Completed => rows.sort_by(|(_, a), (_, b)| {
+                self.nulls.compare(a.data.completed_at, b.data.completed_at)
+            }),
+            // Stable, so equally urgent todos keep their list order.
+            SortKey::Urgency => {
+                let now = Utc::now();
+                rows.sort_by(|(_, a), (_, b)| {
+                    let a = urgency::score(&a.data, &self.urgency, now);
+                    let b = urgency::score(&b.data, &self.urgency, now);
+                    b.total_cmp(&a)
+                });
+            }
+        }
+        rows
+    }
+}
+
+/// Which of the rows a selection matched `list` prints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Window {
+    #[default]
+    All,
+    /// Skip `offset` rows, then show at most `limit`.
+    Range { offset: usize, limit: Option<usize> },
+    /// Rows in pages of `size`, counting pages from 1.
+    Page { page: usize, size: usize },
+}
+
+impl Window {
+    /// The rows to show out of `total`, and for pages the page shown and
+    /// the number of pages. A page past the end shows the last page, which
+    /// may be partial.
+    ///
+    /// ```
+    /// use toto::cli::Window;
+    ///
+    /// let page = |page| Window::Page { page, size: 10 }.bounds(25);
+    /// assert_eq!(page(3), (20..25, Some((3, 3))));
+    /// assert_eq!(page(7), (20..25, Some((3, 3))));
+    /// assert_eq!(Window::Page { page: 1, size: 10 }.bounds(0), (0..0, Some((1, 1))));
+    /// ```
+    pub fn bounds(self, total: usize) -> (Range<usize>, Option<(usize, usize)>) {
+        match self {
+            Window::All => (0..total, None),
+            Window::Range { offset, limit } => {
+                let start = offset.min(total);
+                let end = limit.map_or(total, |limit| start.saturating_add(limit).min(total));
+                (start..end, None)
+            }
+            Window::Page { page, size } => {
+                let size = size.max(1);
+                let pages = total.div_ceil(size).max(1);
+                let page = page.clamp(1, pages);
+                let range = Window::Range {
+                    offset: (page - 1) * size,
+                    limit: Some(size),
+                };
+                (range.bounds(total).0, Some((page, pages)))
+            }
+        }
+    }
+}
+
+/// A todo to be added with `Cli::add`. Start from `NewTodo::new` and set
+/// only the fields that differ from a plain pending todo.
+///
+/// Backdated timestamps have to make a possible history:
+///
+/// ```
+/// use chrono::{TimeDelta, Utc};
+/// use toto::{Cli, NewTodo, TodoStatus};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let now = Utc::now();
+/// let ago = |days| Some(now - TimeDelta::days(days));
+/// let id = cli.add(NewTodo {
+///     created_at: ago(3),
+///     in_progress: true,
+///     started_at: ago(2),
+///     done: true,
+///     completed_at: ago(1),
+///     ..NewTodo::new("Move the servers")
+/// })?;
+/// let todo = cli.todo(id).unwrap().data;
+/// assert_eq!(todo.status, TodoStatus::Completed);
+/// assert_eq!(
+///     (Some(todo.created_at), todo.in_progress_at, todo.completed_at),
+///     (ago(3), ago(2), ago(1))
+/// );
+///
+/// let early = NewTodo { created_at: ago(1), in_progress: true, started_at: ago(2), ..NewTodo::new("Paint") };
+/// assert!(cli.add(early).is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct NewTodo {
+    /// Use this id instead of a generated one. It must not be taken.
+    pub id: Option<Uuid>,
+    pub title: String,
+    pub description: Option<String>,
+    /// Lower is more important; 255 means unprioritised.
+    pub priority: u8,
+    /// Whether an unprioritised todo takes its priority from the
+    /// `tag_priorities` its tags map to. On unless 255 was asked for.
+    pub priority_from_tags: bool,
+    pub in_progress: bool,
+    /// Defaults to now.
+    pub created_at: Option<DateTime<Utc>>,
+    /// Used when `in_progress` is set, defaults to now.
+    pub started_at: Option<DateTime<Utc>>,
+    pub done: bool,
+    /// Used when `done` is set, defaults to now.
+    pub completed_at: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub parent: Option<Uuid>,
+    pub blocked_by: Vec<Uuid>,
+    pub auto_start: bool,
+    pub tags: Vec<String>,
+    pub color: Option<Color>,
+    pub icon: Option<String>,
+}
+
+impl NewTodo {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            title: title.into(),
+            description: None,
+            priority: u8::MAX,
+            priority_from_tags: true,
+            in_progress: false,
+            created_at: None,
+            started_at: None,
+            done: false,
+            completed_at: None,
+            due_at: None,
+            parent: None,
+            blocked_by: Vec::new(),
+            auto_start: false,
+            tags: Vec::new(),
+            color: None,
+            icon: None,
+        }
+    }
+}
+
+/// Produces the id for each newly added todo. Random v4 ids by default, or
+/// time-ordered v7 ones with `add --uuid-v7`, which sort by creation.
+///
+/// ```
+/// use toto::cli::{Cli, Todo};
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-v7-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// for title in ["First", "Second"] {
+///     Cli::new(path.clone()).run_from(["toto", "add", "--uuid-v7", "--title", title, "--priority", "1"])?;
+/// }
+/// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+/// let id = |title: &str| todos.iter().find(|t| t.data.title == title).unwrap().id;
+/// let (first, second) = (id("First"), id("Second"));
+/// assert_eq!((first.get_version_num(), second.get_version_num()), (7, 7));
+/// assert!(first < second);
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub type IdGenerator = Box<dyn FnMut() -> Uuid>;
+
+/// How a purely numeric id argument is interpreted.
+///
+/// ```
+/// use toto::cli::{Cli, Todo};
+/// use uuid::Uuid;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-id-mode-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let mut ids = ["a0a1b2c3-0000-4000-8000-000000000000", "42a1b2c3-0000-4000-8000-000000000000"]
+///     .map(|id| Uuid::parse_str(id).unwrap())
+///     .into_iter();
+/// let mut cli = Cli::new(path.clone()).with_id_generator(move || ids.next().unwrap());
+/// cli.run_from(["toto", "add", "--title", "Row zero", "--priority", "0"])?;
+/// cli.run_from(["toto", "add", "--title", "Prefix 42", "--priority", "1"])?;
+/// let rename = |args: &[&str]| -> anyhow::Result<Vec<String>> {
+///     Cli::new(path.clone()).run_from(["toto"].iter().chain(args))?;
+///     let mut todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+///     todos.sort_by_key(|todo| todo.data.priority);
+///     Ok(todos.into_iter().map(|todo| todo.data.title).collect())
+/// };
+///
+/// // Relative: 1 is the second row.
+/// assert_eq!(rename(&["update", "--id", "1", "--title", "Row one"])?, ["Row zero", "Row one"]);
+///
+/// // Stable: numbers are UUID prefixes, so 1 means nothing here.
+/// assert_eq!(rename(&["--id-mode", "stable", "update", "--id", "42", "--title", "Prefix"])?, ["Row zero", "Prefix"]);
+/// assert!(rename(&["--id-mode", "stable", "update", "--id", "1", "--title", "Lost"]).is_err());
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum IdMode {
+    /// The number is the row shown by `list`. Row numbers shift as todos are
+    /// added or removed, which makes them quick to type but not durable.
+    #[default]
+    Relative,
+    /// The number is the start of the todo's UUID, which never changes.
+    Stable,
+}
+
+/// Field that `list` orders its rows by.
+///
+/// `created` goes by creation alone, as its name says. `active` is the age
+/// sort that counts `touch` too, the same age `stale` and the age bars go
+/// by:
+///
+/// ```
+/// use chrono::{TimeDelta, Utc};
+/// use toto::cli::SortKey;
+/// use toto::{Cli, NewTodo, Selection};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let ago = |title, days| NewTodo { created_at: Some(Utc::now() - TimeDelta::days(days)), ..NewTodo::new(title) };
+/// let garage = cli.add(ago("Sort out the garage", 40))?;
+/// cli.add(ago("Fix the gate", 20))?;
+/// cli.add(ago("Call the bank", 10))?;
+/// cli.touch(garage)?;
+///
+/// let titles = |sort| -> Vec<String> {
+///     let selection = Selection { sort, ..Selection::default() };
+///     cli.list_filtered(&selection).into_iter().map(|todo| todo.data.title).collect()
+/// };
+/// assert_eq!(titles(SortKey::Created), ["Sort out the garage", "Fix the gate", "Call the bank"]);
+/// assert_eq!(titles(SortKey::Active), ["Fix the gate", "Call the bank", "Sort out the garage"]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Most important first, the order human ids are numbered in.
+    #[default]
+    Priority,
+    /// Oldest first.
+    Created,
+    /// Longest untouched first, by creation or the last `touch`.
+    Active,
+    /// Alphabetical.
+    Title,
+    /// Soonest due first.
+    Due,
+    /// Earliest completed first.
+    Completed,
+    /// Most urgent first, see `toto top --help`.
+    Urgency,
+}
+
+/// Where sorting by an optional field, such as `--sort due`, puts the todos
+/// without it.
+///
+/// ```
+/// use chrono::{TimeDelta, Utc};
+/// use toto::cli::{Nulls, SortKey};
+/// use toto::{Cli, NewTodo, Selection};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let due = |title, days| NewTodo {
+///     due_at: Some(Utc::now() + TimeDelta::days(days)),
+///     ..NewTodo::new(title)
+/// };
+/// cli.add(due("Pay rent", 5))?;
+/// cli.add(NewTodo::new("Someday"))?;
+/// cli.add(due("Call mum", 1))?;
+///
+/// let titles = |nulls| -> Vec<String> {
+///     let selection = Selection { sort: SortKey::Due, nulls, ..Selection::default() };
+///     cli.list_filtered(&selection).into_iter().map(|todo| todo.data.title).collect()
+/// };
+/// assert_eq!(titles(Nulls::Last), ["Call mum", "Pay rent", "Someday"]);
+/// assert_eq!(titles(Nulls::First), ["Someday", "Call mum", "Pay rent"]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Nulls {
+    First,
+    #[default]
+    Last,
+}
+
+impl Nulls {
+    /// Orders `a` and `b`, with `None` before or after every value.
+    pub fn compare<T: Ord>(self, a: Option<T>, b: Option<T>) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) if self == Nulls::First => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) if self == Nulls::First => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+        }
+    }
+}
+
+/// The sort and filters `list` remembers between runs, stored in `view.json`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct ListView {
+    #[serde(default)]
+    sort: Option<SortKey>,
+    #[serde(default)]
+    sort_nulls: Option<Nulls>,
+    #[serde(default)]
+    title_contains: Option<String>,
+    #[serde(default)]
+    desc_contains: Option<String>,
+    #[serde(default)]
+    case_sensitive: bool,
+    #[serde(default)]
+    hide_completed_after_secs: Option<i64>,
+    #[serde(default)]
+    stale_after_secs: Option<i64>,
+    #[serde(default)]
+    changed_within_secs: Option<i64>,
+    #[serde(default)]
+    due_today: bool,
+    #[serde(default)]
+    overdue: bool,
+    #[serde(default)]
+    actionable: bool,
+    #[serde(default)]
+    statuses: Vec<TodoStatus>,
+    #[serde(default)]
+    any_tags: Vec<String>,
+    // Views saved before --tag-any existed stored required tags as `tags`.
+    #[serde(default, alias = "tags")]
+    all_tags: Vec<String>,
+}
+
+impl ListView {
+    /// Overrides the view with the filter and sort options given on the
+    /// command line, see `filter_args`. Returns whether any were given.
+    fn apply_matches(&mut self, matches: &ArgMatches) -> bool {
+        let given = |name: &str| matches.value_source(name) == Some(ValueSource::CommandLine);
+        let explicit = [
+            "sort",
+            "sort-nulls",
+            "title-contains",
+            "desc-contains",
+            "case-sensitive",
+            "hide-completed-after",
+            "stale",
+            "changed-since",
+            "due-today",
+            "overdue",
+            "actionable",
+            "status",
+            "tag",
+            "tag-any",
+            "tag-all",
+        ]
+        .into_iter()
+        .any(given);
+
+        if given("sort") {
+            self.sort = matches.get_one::<SortKey>("sort").copied();
+        }
+        if given("sort-nulls") {
+            self.sort_nulls = matches.get_one::<Nulls>("sort-nulls").copied();
+        }
+        if given("title-contains") {
+            self.title_contains = matches.get_one::<String>("title-contains").cloned();
+        }
+        if given("desc-contains") {
+            self.desc_contains = matches.get_one::<String>("desc-contains").cloned();
+        }
+        if given("case-sensitive") {
+            self.case_sensitive = true;
+        }
+        if given("hide-completed-after") {
+            self.hide_completed_after_secs = matches
+                .get_one::<TimeDelta>("hide-completed-after")
+                .map(|delta| delta.num_seconds());
+        }
+        if given("stale") {
+            self.stale_after_secs = matches
+                .get_one::<TimeDelta>("stale")
+                .map(|delta| delta.num_seconds());
+        }
+        if given("changed-since") {
+            self.changed_within_secs = matches
+                .get_one::<TimeDelta>("changed-since")
+                .map(|delta| delta.num_seconds());
+        }
+        if given("due-today") {
+            self.due_today = true;
+        }
+        if given("overdue") {
+            self.overdue = true;
+        }
+        if given("actionable") {
+            self.actionable = true;
+        }
+        if given("status") {
+            self.statuses = matches
+                .get_many::<TodoStatus>("status")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+        }
+        if given("tag") || given("tag-any") {
+            let tags: Vec<String> = ["tag", "tag-any"]
+                .into_iter()
+                .flat_map(|name| matches.get_many::<String>(name).unwrap_or_default())
+                .cloned()
+                .collect();
+            self.any_tags = normalize_tags(&tags);
+        }
+        if given("tag-all") {
+            let tags: Vec<String> = matches
+                .get_many::<String>("tag-all")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            self.all_tags = normalize_tags(&tags);
+        }
+        explicit
+    }
+
+    fn filter(&self) -> ListFilter {
+        ListFilter {
+            title_contains: self.title_contains.clone(),
+            desc_contains: self.desc_contains.clone(),
+            case_sensitive: self.case_sensitive,
+            hide_completed_after: self
+                .hide_completed_after_secs
+                .and_then(TimeDelta::try_seconds),
+            stale_after: self.stale_after_secs.and_then(TimeDelta::try_seconds),
+            changed_within: self.changed_within_secs.and_then(TimeDelta::try_seconds),
+            due_today: self.due_today,
+            overdue: self.overdue,
+            tz: Tz::default(),
+            statuses: self.statuses.clone(),
+            any_tags: self.any_tags.clone(),
+            all_tags: self.all_tags.clone(),
+            hide_snoozed: false,
+            actionable: self.actionable,
+        }
+    }
+}
+
+const URGENCY_HELP: &str = "Todos are ranked by an urgency score: \
+    urgency_priority_weight * P + urgency_due_weight * D. P runs from 1 for \
+    priority 0 to 0 for the default priority of 255. D is 1 once a todo is \
+    due and falls to 0 at urgency_horizon_days before its due date; todos \
+    without a due date get 0. Set the weights in config.json or with \
+    TOTO_URGENCY_* variables.";
+
+/// Longest title accepted by `add` and `update` unless `--max-title-bytes`
+/// says otherwise. Keeps a pasted blob from ending up as a single huge line
+/// in every list and export.
+///
+/// ```
+/// use toto::cli::{Cli, Todo};
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-title-bytes-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+/// let add = |global: &[&str], title: &str| {
+///     let args = [global, &["add", "--title", title, "--priority", "1"]].concat();
+///     run(&args)
+/// };
+/// let longest = "x".repeat(toto::cli::DEFAULT_MAX_TITLE_BYTES);
+/// add(&[], &longest)?;
+/// let err = add(&[], &format!("{longest}y")).unwrap_err();
+/// assert_eq!(err.to_string(), "Title is 257 bytes, over the limit of 256 (see --max-title-bytes)");
+///
+/// add(&["--max-title-bytes", "5"], "Bread")?;
+/// assert!(add(&["--max-title-bytes", "5"], "Butter").is_err());
+/// let err = run(&["--max-title-bytes", "5", "update", "--id", "1", "--title", "Butter"]).unwrap_err();
+/// assert!(err.to_string().starts_with("Title is 6 bytes"));
+///
+/// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+/// assert_eq!(todos.len(), 2);
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub const DEFAULT_MAX_TITLE_BYTES: usize = 256;
+
+/// How many times in a row `snooze --every` snoozes a todo again before
+/// leaving it in the list.
+pub const MAX_RESNOOZES: u32 = 5;
+
+/// Marker file in the data directory recording that the `id_base` notice
+/// was shown.
+const ID_BASE_NOTICE_FILE: &str = ".id_base_notice";
+
 pub struct Cli {
     file_path: String,
     todo_map: HashMap<Uuid, TodoData>,
+    id_generator: IdGenerator,
+    config: Config,
+    output: Output,
+    /// Load without checking `todos.json` against its recorded checksum.
+    skip_integrity: bool,
+    /// The loaded `todos.json` didn't match its checksum. Saving leaves the
+    /// checksum as it is then, so the warning stays until `doctor --fix`.
+    integrity_mismatch: bool,
+    /// Whether `add` may prompt for what the command line left out.
+    interactive: bool,
 }
 
 impl Default for Cli {
@@ -60,6 +1403,12 @@ impl Default for Cli {
         Self {
             file_path: String::from("."),
             todo_map: HashMap::new(),
+            id_generator: Box::new(Uuid::new_v4),
+            config: Config::default(),
+            output: Output::stdio(),
+            skip_integrity: false,
+            integrity_mismatch: false,
+            interactive: io::stdin().is_terminal(),
         }
     }
 }
@@ -68,13 +1417,324 @@ impl Cli {
     pub fn new(file_path: String) -> Self {
         Self {
             file_path,
-            todo_map: HashMap::new(),
+            ..Self::default()
+        }
+    }
+
+    /// Replaces the random v4 id generator, e.g. with a deterministic
+    /// sequence so tests and snapshots get stable ids.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    /// use uuid::Uuid;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-ids-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let mut next = 0;
+    /// let mut cli = Cli::new(path).with_id_generator(move || {
+    ///     next += 1;
+    ///     Uuid::from_u128(next)
+    /// });
+    /// cli.run_from(["toto", "add", "--title", "First", "--priority", "1"])?;
+    /// cli.run_from(["toto", "add", "--title", "Second", "--priority", "2"])?;
+    ///
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// let mut ids: Vec<Uuid> = todos.iter().map(|todo| todo.id).collect();
+    /// ids.sort();
+    /// assert_eq!(ids, [Uuid::from_u128(1), Uuid::from_u128(2)]);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_id_generator(mut self, id_generator: impl FnMut() -> Uuid + 'static) -> Self {
+        self.id_generator = Box::new(id_generator);
+        self
+    }
+
+    /// Sends what handlers print somewhere other than stdout and stderr.
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets whether `add` may prompt for a title or priority left out on the
+    /// command line. By default it does when stdin is a terminal.
+    ///
+    /// ```
+    /// use toto::Cli;
+    /// use toto::output::Output;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("toto-doc-picker-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir)?;
+    /// let path = dir.to_string_lossy().into_owned();
+    ///
+    /// // Without a terminal there is no picker, just the usual default.
+    /// Cli::new(path.clone())
+    ///     .with_output(Output::new(std::io::sink(), std::io::sink()))
+    ///     .with_interactive(false)
+    ///     .run_from(["toto", "add", "--interactive-priority", "Water the plants"])?;
+    ///
+    /// let mut cli = Cli::new(path);
+    /// cli.load()?;
+    /// assert_eq!(cli.todos()[0].data.priority, 255);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    /// Replaces the default configuration. `run` loads its own from the data
+    /// directory, so this is for library use.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Reads the todo file, starting any auto-start todos whose blockers
+    /// are done. A missing file leaves the list empty.
+    ///
+    /// ```
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("toto-doc-load-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir)?;
+    /// let path = dir.to_string_lossy().into_owned();
+    ///
+    /// let mut cli = Cli::new(path.clone());
+    /// cli.load()?;
+    /// let id = cli.add(NewTodo::new("Renew passport"))?;
+    /// cli.save()?;
+    ///
+    /// let mut reloaded = Cli::new(path);
+    /// reloaded.load()?;
+    /// assert!(reloaded.todo(id).is_some());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn load(&mut self) -> Result<()> {
+        self.load_todos()?;
+        self.catch_up()
+    }
+
+    // What `load` does once the file is read: snoozing todos again and
+    // starting unblocked ones, which changes the list until it is saved.
+    fn catch_up(&mut self) -> Result<()> {
+        for id in self.resnooze(Utc::now()) {
+            writeln!(
+                self.output.err(),
+                "Warning: \"{}\" was snoozed again {} times without being acted on, it stays in the list now",
+                self.todo_map[&id].title,
+                MAX_RESNOOZES
+            )?;
         }
+        self.start_unblocked()
+    }
+
+    /// Writes the todo file.
+    pub fn save(&self) -> Result<()> {
+        self.save_todos()
+    }
+
+    /// Every todo, in list order: by priority, then creation time, then id
+    /// for todos created at the same moment.
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use toto::{Cli, NewTodo};
+    /// use uuid::Uuid;
+    ///
+    /// let mut ids = [3, 1, 2].into_iter();
+    /// let mut cli = Cli::new(String::from(".")).with_id_generator(move || Uuid::from_u128(ids.next().unwrap()));
+    /// let at = Some(Utc::now());
+    /// for _ in 0..3 {
+    ///     cli.add(NewTodo { created_at: at, ..NewTodo::new("Todo") })?;
+    /// }
+    /// let ids: Vec<u128> = cli.todos().iter().map(|todo| todo.id.as_u128()).collect();
+    /// assert_eq!(ids, [1, 2, 3]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn todos(&self) -> Vec<Todo> {
+        self.ordered_todos()
+    }
+
+    /// The pending and in-progress todos, in list order, each with its
+    /// position among all of them as `todos` returns them, which is what
+    /// human ids count. Only these are copied and sorted, so this is cheaper
+    /// than `todos` when many finished todos are waiting to be archived.
+    ///
+    /// ```
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// for n in 0..500 {
+    ///     cli.add(NewTodo { priority: 0, done: true, ..NewTodo::new(format!("Done {}", n)) })?;
+    /// }
+    /// let open = cli.add(NewTodo::new("Still to do"))?;
+    ///
+    /// let pending = cli.open_todos();
+    /// assert_eq!(pending.len(), 1);
+    /// let (position, todo) = &pending[0];
+    /// assert_eq!(todo.id, open);
+    /// assert_eq!(cli.todos()[*position].id, open);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn open_todos(&self) -> Vec<(usize, Todo)> {
+        select_sorted(
+            &self.todo_map,
+            |(id, data)| list_order(**id, data),
+            |(_, data)| matches!(data.status, TodoStatus::Pending | TodoStatus::InProgress),
+        )
+        .into_iter()
+        .map(|(position, (&id, data))| {
+            let todo = Todo {
+                id,
+                data: data.clone(),
+            };
+            (position, todo)
+        })
+        .collect()
+    }
+
+    pub fn todo(&self, id: Uuid) -> Option<Todo> {
+        self.todo_map.get(&id).map(|data| Todo {
+            id,
+            data: data.clone(),
+        })
+    }
+
+    /// The todos `list` would show for `selection`, in its order.
+    ///
+    /// ```
+    /// use toto::{Cli, ListFilter, NewTodo, Selection, TodoStatus};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let milk = cli.add(NewTodo::new("Buy milk"))?;
+    /// cli.add(NewTodo::new("Call the bank"))?;
+    /// cli.complete(milk, Some("Oat, not dairy"))?;
+    ///
+    /// let open = Selection {
+    ///     filter: ListFilter {
+    ///         statuses: vec![TodoStatus::Pending],
+    ///         ..ListFilter::default()
+    ///     },
+    ///     ..Selection::default()
+    /// };
+    /// let titles: Vec<String> = cli
+    ///     .list_filtered(&open)
+    ///     .into_iter()
+    ///     .map(|todo| todo.data.title)
+    ///     .collect();
+    /// assert_eq!(titles, ["Call the bank"]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn list_filtered(&self, selection: &Selection) -> Vec<Todo> {
+        selection
+            .apply(&self.ordered_todos())
+            .into_iter()
+            .map(|(_, todo)| todo.clone())
+            .collect()
     }
 
+    /// Runs the command given on the command line.
     pub fn run(&mut self) -> Result<()> {
+        self.run_from(std::env::args_os())
+    }
+
+    /// Runs the command in `args`, which start with the program name like
+    /// `std::env::args` does. Arguments clap rejects, and `--help` and
+    /// `--version`, come back as a `clap::Error` rather than exiting, for
+    /// the caller to print or `exit` with.
+    ///
+    /// ```
+    /// use toto::Cli;
+    /// use toto::output::Output;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("toto-doc-words-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir)?;
+    /// let path = dir.to_string_lossy().into_owned();
+    ///
+    /// let quiet = || Output::new(std::io::sink(), std::io::sink());
+    /// Cli::new(path.clone()).with_output(quiet()).run_from(["toto", "add", "buy", "milk"])?;
+    ///
+    /// let mut cli = Cli::new(path.clone());
+    /// cli.load()?;
+    /// assert_eq!(cli.todos()[0].data.title, "buy milk");
+    ///
+    /// let kind = |args: &[&str]| {
+    ///     let err = Cli::new(path.clone()).with_output(quiet()).run_from(args.iter().copied()).unwrap_err();
+    ///     err.downcast_ref::<clap::Error>().map(|err| err.kind())
+    /// };
+    /// assert_eq!(kind(&["toto", "add", "--no-such-flag"]), Some(clap::error::ErrorKind::UnknownArgument));
+    /// assert_eq!(kind(&["toto", "--help"]), Some(clap::error::ErrorKind::DisplayHelp));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn run_from<I, T>(&mut self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
         let matches = command!()
             .subcommand_required(true)
+            .arg(
+                Arg::new("id-mode")
+                    .required(false)
+                    .long("id-mode")
+                    .global(true)
+                    .help("Whether numeric ids are list rows (relative) or UUID prefixes (stable) [default: relative]")
+                    .value_parser(value_parser!(IdMode))
+            )
+            .arg(
+                Arg::new("raw-id")
+                    .required(false)
+                    .long("raw-id")
+                    .global(true)
+                    .help("Only accept full UUIDs as ids, never row numbers or prefixes")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("max-title-bytes")
+                    .required(false)
+                    .long("max-title-bytes")
+                    .global(true)
+                    .help("Reject titles longer than this many bytes on add and update [default: 256]")
+                    .value_parser(value_parser!(usize))
+            )
+            .arg(
+                Arg::new("strict-ids")
+                    .required(false)
+                    .long("strict-ids")
+                    .global(true)
+                    .help("Fail instead of warning when the todo file has duplicate ids")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("skip-integrity")
+                    .required(false)
+                    .long("skip-integrity")
+                    .global(true)
+                    .help("Load the todo file without checking it against its recorded checksum")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("tz")
+                    .required(false)
+                    .long("tz")
+                    .global(true)
+                    .help("Timezone for calendar days: local, utc or an offset like +02:00 [default: local]")
+                    .value_parser(time::parse_tz)
+            )
+            .arg(
+                Arg::new("date-format")
+                    .required(false)
+                    .long("date-format")
+                    .global(true)
+                    .help("strftime-style format to show dates in, e.g. %d/%m/%Y [default: %Y-%m-%d]")
+                    .value_parser(time::parse_date_format)
+            )
             .subcommand(
                 Command::new("add")
                     .long_flag("add")
@@ -82,10 +1742,18 @@ impl Cli {
                     .about("Add a todo")
                     .arg(
                         Arg::new("title")
-                            .required(true)
+                            .required(false)
                             .long("title")
                             .short('t')
-                            .help("Title of the todo")
+                            .help("Title of the todo, asked for interactively if left out")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("words")
+                            .required(false)
+                            .num_args(1..)
+                            .conflicts_with("title")
+                            .help("Title of the todo, instead of -t; unquoted words are joined with spaces")
                             .value_parser(value_parser!(String)),
                     )
                     .arg(
@@ -98,64 +1766,438 @@ impl Cli {
                     )
                     .arg(
                         Arg::new("priority")
-                            .required(true)
+                            .required(false)
                             .long("priority")
                             .short('p')
-                            .help("Priority of the todo")
+                            .help("Priority of the todo, otherwise taken from its tags if tag_priorities maps them")
                             .value_parser(value_parser!(u8))
                             .default_value("255"),
                     )
                     .arg(
-                        Arg::new("in-progress")
-                            .required(false)
-                            .long("in-progress")
-                            .short('i')
-                            .help("Mark the todo as in progress")
-                            .action(ArgAction::SetTrue),
-                    ),
-            )
-            .subcommand(
-                Command::new("list")
-                    .long_flag("list")
-                    .short_flag('l')
-                    .about("List all todos")
-                    .arg(
-                        Arg::new("verbose")
+                        Arg::new("interactive-priority")
                             .required(false)
-                            .long("verbose")
-                            .short('v')
-                            .help("Verbose output")
+                            .long("interactive-priority")
+                            .conflicts_with("priority")
+                            .help("Pick the priority from high, medium, low and none when run in a terminal")
                             .action(ArgAction::SetTrue),
-                    ),
-            )
-            .subcommand(
-                Command::new("update")
-                    .long_flag("update")
-                    .short_flag('u')
-                    .about("Update a todo")
+                    )
                     .arg(
                         Arg::new("id")
-                            .required(true)
+                            .required(false)
                             .long("id")
-                            .short('i')
-                            .help("ID of the todo")
-                            .value_parser(value_parser!(String)),
+                            .conflicts_with("uuid-v7")
+                            .help("Full UUID to give the todo instead of a generated one; fails if it is taken")
+                            .value_parser(Uuid::parse_str),
                     )
                     .arg(
-                        Arg::new("title")
+                        Arg::new("template-from")
                             .required(false)
-                            .long("title")
-                            .short('t')
-                            .help("Title of the todo")
+                            .long("template-from")
+                            .help("ID of a todo to copy the title, description, priority and tags of; other flags override them")
                             .value_parser(value_parser!(String)),
                     )
                     .arg(
-                        Arg::new("description")
+                        Arg::new("in-progress")
                             .required(false)
-                            .long("description")
-                            .short('d')
-                            .help("Description of the todo")
-                            .value_parser(value_parser!(String)),
+                            .long("in-progress")
+                            .short('i')
+                            .help("Mark the todo as in progress")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("at")
+                            .required(false)
+                            .long("at")
+                            .help("Backdate the creation time of the todo")
+                            .value_parser(time::parse_timestamp),
+                    )
+                    .arg(
+                        Arg::new("started-at")
+                            .required(false)
+                            .long("started-at")
+                            .requires("in-progress")
+                            .help("When the todo was started, used with --in-progress")
+                            .value_parser(time::parse_timestamp),
+                    )
+                    .arg(
+                        Arg::new("done")
+                            .required(false)
+                            .long("done")
+                            .help("Add the todo as already completed")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("completed-at")
+                            .required(false)
+                            .long("completed-at")
+                            .requires("done")
+                            .help("When the todo was completed, used with --done")
+                            .value_parser(time::parse_timestamp),
+                    )
+                    .arg(
+                        Arg::new("due")
+                            .required(false)
+                            .long("due")
+                            .help("When the todo is due, e.g. 2024-03-01, 2024-03-01 17:00, friday or 3d")
+                            .value_parser(time::parse_due),
+                    )
+                    .arg(
+                        Arg::new("parent")
+                            .required(false)
+                            .long("parent")
+                            .help("ID of the todo this is a subtask of")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("blocked-by")
+                            .required(false)
+                            .long("blocked-by")
+                            .visible_alias("depends-on-completion-of")
+                            .help("ID of a todo that must be completed first, may be repeated")
+                            .value_parser(value_parser!(String))
+                            .action(ArgAction::Append),
+                    )
+                    .arg(
+                        Arg::new("auto-start")
+                            .required(false)
+                            .long("auto-start")
+                            .requires("blocked-by")
+                            .help("Start the todo by itself once its blockers are completed")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("tag")
+                            .required(false)
+                            .long("tag")
+                            .help("Tag the todo, may be repeated")
+                            .value_parser(value_parser!(String))
+                            .action(ArgAction::Append),
+                    )
+                    .arg(
+                        Arg::new("color")
+                            .required(false)
+                            .long("color")
+                            .help("Color to show the todo in")
+                            .value_parser(value_parser!(Color)),
+                    )
+                    .arg(
+                        Arg::new("icon")
+                            .required(false)
+                            .long("icon")
+                            .help("Short label or emoji shown before the title")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("uuid-v7")
+                            .required(false)
+                            .long("uuid-v7")
+                            .help("Use a time-ordered UUIDv7 instead of a random v4 id")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("allow-duplicate")
+                            .required(false)
+                            .long("allow-duplicate")
+                            .visible_alias("no-dup-check")
+                            .conflicts_with("dedupe")
+                            .help("Don't look for an open todo with this title, or warn about one")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("dedupe")
+                            .required(false)
+                            .long("dedupe")
+                            .help(
+                                "Merge into an open todo with exactly this title instead of adding",
+                            )
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("list")
+                    .long_flag("list")
+                    .short_flag('l')
+                    .about("List all todos")
+                    // The line formats for scripts, which --field-separator applies to.
+                    .group(ArgGroup::new("records").args(["plain", "ids-only"]))
+                    .arg(
+                        Arg::new("verbose")
+                            .required(false)
+                            .long("verbose")
+                            .short('v')
+                            .help("Verbose output")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("flat-archived")
+                            .required(false)
+                            .long("flat-archived")
+                            .visible_alias("include-archived")
+                            .help("Include archived todos, ordered by creation date")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("pending-only")
+                            .required(false)
+                            .long("pending-only")
+                            .conflicts_with_all(["flat-archived", "status"])
+                            .help("Only list pending and in-progress todos, skipping finished ones before anything else is done with them")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("offset")
+                            .required(false)
+                            .long("offset")
+                            .conflicts_with_all(["flat-archived", "page"])
+                            .help("Skip this many matching todos")
+                            .value_parser(value_parser!(usize)),
+                    )
+                    .arg(
+                        Arg::new("limit")
+                            .required(false)
+                            .long("limit")
+                            .conflicts_with_all(["flat-archived", "page"])
+                            .help("Show at most this many matching todos")
+                            .value_parser(value_parser!(usize)),
+                    )
+                    .arg(
+                        Arg::new("page")
+                            .required(false)
+                            .long("page")
+                            .conflicts_with("flat-archived")
+                            .help("Show this page of matching todos, counting from 1; see --page-size")
+                            .value_parser(value_parser!(u64).range(1..)),
+                    )
+                    .arg(
+                        Arg::new("page-size")
+                            .required(false)
+                            .long("page-size")
+                            .requires("page")
+                            .help("Todos per page")
+                            .value_parser(value_parser!(u64).range(1..))
+                            .default_value("20"),
+                    )
+                    .arg(
+                        Arg::new("include-snoozed")
+                            .required(false)
+                            .long("include-snoozed")
+                            .help("Also show todos that are snoozed")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("since-commit")
+                            .required(false)
+                            .long("since-commit")
+                            .conflicts_with("flat-archived")
+                            .help("Only show todos added or changed since the last git commit")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("ellipsis")
+                            .required(false)
+                            .long("ellipsis")
+                            .help("Marker appended to truncated values [default: ...]")
+                            .value_parser(value_parser!(String))
+                    )
+                    .arg(
+                        Arg::new("no-truncate")
+                            .required(false)
+                            .long("no-truncate")
+                            .conflicts_with("columns-auto")
+                            .help("Print every field in full on one line, e.g. to grep")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("borders")
+                            .required(false)
+                            .long("borders")
+                            .visible_alias("as-table")
+                            .conflicts_with_all(["columns-auto", "wide", "narrow"])
+                            .help("Draw a table with box-drawing borders, columns as wide as their values")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("limit-width")
+                            .required(false)
+                            .long("limit-width")
+                            .value_name("N")
+                            .conflicts_with_all(["no-truncate", "borders"])
+                            .help("Lay rows out for at most N columns, however wide the terminal, and cut longer ones off")
+                            .value_parser(value_parser!(u64).range(1..)),
+                    )
+                    .arg(
+                        Arg::new("priority-symbols")
+                            .required(false)
+                            .long("priority-symbols")
+                            .help("Show priorities as priority_symbols [default: high=!!!, medium=!!, low=!], --plain keeps the numbers")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("ascii")
+                            .required(false)
+                            .long("ascii")
+                            .requires("borders")
+                            .help("Draw the borders with +, - and |, the default outside UTF-8 locales")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("highlight")
+                            .required(false)
+                            .long("highlight")
+                            .value_name("term")
+                            .help("Show this text in bold wherever it appears in titles and descriptions")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("json")
+                            .required(false)
+                            .long("json")
+                            .conflicts_with_all(["flat-archived", "since-commit"])
+                            .help("Print the matching todos as JSON, in the format of todos.json")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("plain")
+                            .required(false)
+                            .long("plain")
+                            .conflicts_with_all(["json", "flat-archived", "since-commit"])
+                            .help("Print one ID, UUID, status, priority and title record per todo, for scripts")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("ids-only")
+                            .required(false)
+                            .long("ids-only")
+                            .conflicts_with_all(["json", "plain", "flat-archived", "since-commit"])
+                            .help("Print just the UUIDs of the matching todos, for scripts")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("field-separator")
+                            .required(false)
+                            .long("field-separator")
+                            .requires("records")
+                            .help("Separator for --plain fields and --ids-only ids: one character, \\t, \\0, \\n or \\\\ [default: \\t]")
+                            .value_parser(parse_separator),
+                    )
+                    .arg(
+                        Arg::new("compact")
+                            .required(false)
+                            .long("compact")
+                            .requires("json")
+                            .help("Print the JSON on one line, for piping")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("pretty")
+                            .required(false)
+                            .long("pretty")
+                            .requires("json")
+                            .conflicts_with("compact")
+                            .help("Indent the JSON for reading, the default")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("no-header")
+                            .required(false)
+                            .long("no-header")
+                            .help("Leave out the header line, e.g. to concatenate several lists")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("bars")
+                            .required(false)
+                            .long("bars")
+                            .help("On wide terminals, add a bar of each todo's subtask progress, or of its age up to 30 days")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("compact-dates")
+                            .required(false)
+                            .long("compact-dates")
+                            .help("Show dates within compact_dates_within of now as e.g. 2d ago [default: 7d]")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("no-color")
+                            .required(false)
+                            .long("no-color")
+                            .help("Never color or highlight the output, as with NO_COLOR set")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("wide")
+                            .required(false)
+                            .long("wide")
+                            .conflicts_with_all(["narrow", "columns-auto", "no-truncate"])
+                            .help("Use the wide layout whatever the terminal width")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("narrow")
+                            .required(false)
+                            .long("narrow")
+                            .conflicts_with_all(["columns-auto", "no-truncate"])
+                            .help("Use the narrow layout whatever the terminal width")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("columns-auto")
+                            .required(false)
+                            .long("columns-auto")
+                            .help("Drop columns progressively to fit narrow terminals")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("align")
+                            .required(false)
+                            .long("align")
+                            .help("Align a column, e.g. --align title=right (left/center/right)")
+                            .value_parser(term::parse_column_align)
+                            .action(ArgAction::Append),
+                    )
+                    .args(filter_args())
+                    .args(gate_args())
+                    .arg(
+                        Arg::new("reset-view")
+                            .required(false)
+                            .long("reset-view")
+                            .help("Forget the sort and filters remembered from earlier runs")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .after_help(
+                        "Sort and filter options are remembered and reused by later \
+                         `list` runs until changed or cleared with --reset-view.",
+                    ),
+            )
+            .subcommand(
+                Command::new("update")
+                    .long_flag("update")
+                    .short_flag('u')
+                    .about("Update a todo")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .long("id")
+                            .short('i')
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("title")
+                            .required(false)
+                            .long("title")
+                            .short('t')
+                            .help("Title of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("description")
+                            .required(false)
+                            .long("description")
+                            .short('d')
+                            .help("Description of the todo")
+                            .value_parser(value_parser!(String)),
                     )
                     .arg(
                         Arg::new("priority")
@@ -169,8 +2211,9 @@ impl Cli {
                         Arg::new("in-progress")
                             .required(false)
                             .long("in-progress")
-                            .short('i')
-                            .help("Mark the todo as in progress")
+                            .help(
+                                "Mark the todo as in progress, or false to move it back to pending",
+                            )
                             .value_parser(value_parser!(bool)),
                     )
                     .arg(
@@ -178,16 +2221,44 @@ impl Cli {
                             .required(false)
                             .long("completed")
                             .short('c')
-                            .help("Mark the todo as completed")
+                            .help("Mark the todo as completed, or false to reopen it")
                             .value_parser(value_parser!(bool)),
                     )
                     .arg(
                         Arg::new("deleted")
                             .required(false)
                             .long("deleted")
-                            .short('d')
-                            .help("Mark the todo as deleted")
+                            .help("Mark the todo as deleted, or false to restore it")
                             .value_parser(value_parser!(bool)),
+                    )
+                    .arg(
+                        Arg::new("due")
+                            .required(false)
+                            .long("due")
+                            .help("When the todo is due, e.g. 2024-03-01, friday or 3d")
+                            .value_parser(time::parse_due),
+                    )
+                    .arg(
+                        Arg::new("color")
+                            .required(false)
+                            .long("color")
+                            .help("Color to show the todo in")
+                            .value_parser(value_parser!(Color)),
+                    )
+                    .arg(
+                        Arg::new("clear-color")
+                            .required(false)
+                            .long("clear-color")
+                            .conflicts_with("color")
+                            .help("Remove the todo's color")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("icon")
+                            .required(false)
+                            .long("icon")
+                            .help("Short label or emoji shown before the title")
+                            .value_parser(value_parser!(String)),
                     ),
             )
             .subcommand(
@@ -197,11 +2268,61 @@ impl Cli {
                     .about("Complete a todo")
                     .arg(
                         Arg::new("id")
-                            .required(true)
+                            .required_unless_present("started")
                             .long("id")
                             .short('i')
                             .help("ID of the todo")
-                            .value_parser(value_parser!(Uuid)),
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("started")
+                            .required(false)
+                            .long("started")
+                            .visible_alias("finish-day")
+                            .conflicts_with("id")
+                            .help("Complete every todo that is in progress, needs --yes")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("yes")
+                            .required(false)
+                            .long("yes")
+                            .short('y')
+                            .requires("started")
+                            .help("Confirm completing several todos at once")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("note")
+                            .required(false)
+                            .long("note")
+                            .short('n')
+                            .help("Note describing how the todo was completed")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("at")
+                            .required(false)
+                            .long("at")
+                            .conflicts_with("started")
+                            .help("When the todo was completed if not just now, e.g. 2024-03-01 17:30")
+                            .value_parser(time::parse_timestamp),
+                    )
+                    .arg(
+                        Arg::new("quiet")
+                            .required(false)
+                            .long("quiet")
+                            .short('q')
+                            .help("Don't print how many todos are left")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("cascade-parent")
+                            .required(false)
+                            .long("cascade-parent")
+                            .visible_alias("auto-complete-parents")
+                            .help("Also complete the parent once its last open subtask is done, and so on up")
+                            .action(ArgAction::SetTrue),
                     ),
             )
             .subcommand(
@@ -219,10 +2340,55 @@ impl Cli {
                     ),
             )
             .subcommand(
-                Command::new("delete")
-                    .long_flag("delete")
-                    .short_flag('d')
-                    .about("Delete a todo")
+                Command::new("next")
+                    .about("Show the most urgent open todo")
+                    .after_help(URGENCY_HELP),
+            )
+            .subcommand(
+                Command::new("top")
+                    .about("Show the most urgent open todos")
+                    .arg(
+                        Arg::new("count")
+                            .required(false)
+                            .long("count")
+                            .short('n')
+                            .help("How many todos to show")
+                            .value_parser(value_parser!(usize))
+                            .default_value("5"),
+                    )
+                    .after_help(URGENCY_HELP),
+            )
+            .subcommand(
+                Command::new("watch")
+                    .about("Keep showing the most urgent open todos, refreshed until interrupted")
+                    .arg(
+                        Arg::new("count")
+                            .required(false)
+                            .long("count")
+                            .short('n')
+                            .help("How many todos to show")
+                            .value_parser(value_parser!(usize))
+                            .default_value("5"),
+                    )
+                    .arg(
+                        Arg::new("interval")
+                            .required(false)
+                            .long("interval")
+                            .help("How often to re-read the todo file, e.g. 5m")
+                            .value_parser(time::parse_duration)
+                            .default_value("1m"),
+                    )
+                    .arg(
+                        Arg::new("notify")
+                            .required(false)
+                            .long("notify")
+                            .help("Show a desktop notification when a todo is due within notify_due_within [default: 1h] or overdue")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("reopen")
+                    .about("Move a completed todo back to in progress")
                     .arg(
                         Arg::new("id")
                             .required(true)
@@ -232,265 +2398,3914 @@ impl Cli {
                             .value_parser(value_parser!(String)),
                     ),
             )
-            .subcommand(Command::new("sync").about("Sync with git"))
-            .get_matches();
-        println!("Loading...");
-        self.load_todos()?;
-        println!("Exited Load");
+            .subcommand(
+                Command::new("set-priority")
+                    .about("Change just the priority of a todo")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("level")
+                            .required(true)
+                            .help("0-255, lower is more important, or high, medium, low or none")
+                            .value_parser(parse_priority),
+                    ),
+            )
+            .subcommand(
+                Command::new("set-due")
+                    .about("Change just the due date of a todo")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("when")
+                            .required(true)
+                            .help("When the todo is due, e.g. 2024-03-01, 2024-03-01 17:00, tomorrow, friday or 3d")
+                            .value_parser(time::parse_due),
+                    ),
+            )
+            .subcommand(
+                Command::new("clear-due")
+                    .about("Remove the due date of a todo")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    ),
+            )
+            .subcommand(
+                Command::new("touch")
+                    .about("Mark a todo as still relevant without changing anything else")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    ),
+            )
+            .subcommand(
+                Command::new("tag")
+                    .about("Add, remove or rename a tag on every todo matching the filters")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("add")
+                            .about("Add a tag to the matching todos")
+                            .arg(
+                                Arg::new("name")
+                                    .required(true)
+                                    .help("The tag to add")
+                                    .value_parser(value_parser!(String)),
+                            )
+                            .args(tag_batch_args()),
+                    )
+                    .subcommand(
+                        Command::new("remove")
+                            .about("Remove a tag from the matching todos")
+                            .arg(
+                                Arg::new("name")
+                                    .required(true)
+                                    .help("The tag to remove")
+                                    .value_parser(value_parser!(String)),
+                            )
+                            .args(tag_batch_args()),
+                    )
+                    .subcommand(
+                        Command::new("rename")
+                            .about("Rename a tag on the matching todos")
+                            .arg(
+                                Arg::new("from")
+                                    .required(true)
+                                    .help("The tag to rename")
+                                    .value_parser(value_parser!(String)),
+                            )
+                            .arg(
+                                Arg::new("to")
+                                    .required(true)
+                                    .help("Its new name")
+                                    .value_parser(value_parser!(String)),
+                            )
+                            .args(tag_batch_args()),
+                    ),
+            )
+            .subcommand(
+                Command::new("status")
+                    .about("Print just the status of a todo, e.g. for a shell prompt")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("short")
+                            .required(false)
+                            .long("short")
+                            .help("Print the one-letter form, e.g. P for pending")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("show")
+                    .about("Show everything about a todo, descriptions and notes in full")
+                    .arg(
+                        Arg::new("id")
+                            .required_unless_present("todo")
+                            .long("id")
+                            .short('i')
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("todo")
+                            .conflicts_with("id")
+                            .help("ID of the todo, instead of -i")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("json")
+                            .required(false)
+                            .long("json")
+                            .help("Print the todo as JSON, in the format of todos.json")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("field")
+                            .required(false)
+                            .long("field")
+                            .conflicts_with("json")
+                            .help("Print only this field, e.g. title or status, as stored in todos.json")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("history")
+                            .required(false)
+                            .long("history")
+                            .visible_alias("diff")
+                            .conflicts_with_all(["json", "field"])
+                            .help("Print the changes to the todo recorded for undo, oldest first")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("compact-dates")
+                            .required(false)
+                            .long("compact-dates")
+                            .help("Show dates within compact_dates_within of now as e.g. 2d ago [default: 7d]")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("snooze")
+                    .about("Hide a todo from list, next and top for a while")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .long("id")
+                            .short('i')
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("until")
+                            .required_unless_present_any(["for", "every"])
+                            .long("until")
+                            .conflicts_with("for")
+                            .help("When the todo comes back, e.g. 2024-03-01 or 2024-03-01 09:00")
+                            .value_parser(time::parse_timestamp),
+                    )
+                    .arg(
+                        Arg::new("for")
+                            .long("for")
+                            .help("How long to snooze for, e.g. 3d or 2w")
+                            .value_parser(time::parse_duration),
+                    )
+                    .arg(
+                        Arg::new("every")
+                            .long("every")
+                            .visible_alias("recurring")
+                            .help("Snooze again for this long whenever the todo comes back and is left alone as long, e.g. 1d; also the first snooze without --until or --for")
+                            .value_parser(time::parse_duration),
+                    )
+                    .arg(
+                        Arg::new("force")
+                            .required(false)
+                            .long("force")
+                            .help("Snooze even if the todo comes back after it is due")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("delete")
+                    .long_flag("delete")
+                    .short_flag('d')
+                    .about("Delete a todo")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .long("id")
+                            .short('i')
+                            .help("ID of the todo")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("reason")
+                            .required(false)
+                            .long("reason")
+                            .short('r')
+                            .help("Reason the todo was deleted")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("at")
+                            .required(false)
+                            .long("at")
+                            .help("When the todo was deleted if not just now, e.g. 2024-03-01 17:30")
+                            .value_parser(time::parse_timestamp),
+                    ),
+            )
+            .subcommand(
+                Command::new("sync")
+                    .about("Archive finished todos, then commit and push with git")
+                    .arg(
+                        Arg::new("prune-empty-archives")
+                            .required(false)
+                            .long("prune-empty-archives")
+                            .help("Remove archive files that contain no todos")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("archive-only")
+                            .required(false)
+                            .long("archive-only")
+                            .conflicts_with("git-only")
+                            .help("Only archive, without touching git")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("git-only")
+                            .required(false)
+                            .long("git-only")
+                            .help("Only commit and push what is already there")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("sign")
+                            .required(false)
+                            .short('S')
+                            .long("sign")
+                            .conflicts_with("archive-only")
+                            .help("Sign the commit, using git's configured signing key")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("pull")
+                            .required(false)
+                            .long("pull")
+                            .conflicts_with("archive-only")
+                            .help("Pull with git pull --rebase first and merge the pulled todos into the local ones")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("merge-strategy")
+                            .required(false)
+                            .long("merge-strategy")
+                            .requires("pull")
+                            .help("Which copy to keep of a todo changed both locally and in the pulled list")
+                            .value_parser(value_parser!(Strategy))
+                            .default_value("newest-wins"),
+                    ),
+            )
+            .subcommand(
+                Command::new("count")
+                    .about("Count the todos matching the filters")
+                    .arg(
+                        Arg::new("by")
+                            .required(false)
+                            .long("by")
+                            .help("Print one `group: n` line per group instead of a total")
+                            .value_parser(value_parser!(GroupBy)),
+                    )
+                    .arg(
+                        Arg::new("porcelain")
+                            .required(false)
+                            .long("porcelain")
+                            .help("Print the count, and groups with --by, in a stable line format for scripts")
+                            .num_args(0..=1)
+                            .require_equals(true)
+                            .default_missing_value("v1")
+                            .value_parser(value_parser!(PorcelainVersion)),
+                    )
+                    .args(filter_args())
+                    .args(gate_args()),
+            )
+            .subcommand(
+                Command::new("diff")
+                    .about("Show how the todos changed since the last git commit"),
+            )
+            .subcommand(
+                Command::new("recover")
+                    .about("Restore a todo no longer in the list from the git history")
+                    .arg(
+                        Arg::new("id")
+                            .required(true)
+                            .help("UUID of the todo, or the start of one")
+                            .value_parser(value_parser!(String)),
+                    ),
+            )
+            .subcommand(
+                Command::new("undo")
+                    .about("Undo the last change to the todos")
+                    .arg(
+                        Arg::new("list")
+                            .required(false)
+                            .long("list")
+                            .help("Print the changes that can be undone, most recent first, instead")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("to")
+                            .required(false)
+                            .long("to")
+                            .help("Undo every change up to and including this one, numbered as --list shows them")
+                            .value_parser(value_parser!(u64).range(1..))
+                            .conflicts_with("list"),
+                    ),
+            )
+            .subcommand(
+                Command::new("stats")
+                    .about("Show counts per status and completions over time")
+                    .arg(
+                        Arg::new("bucket")
+                            .required(false)
+                            .long("bucket")
+                            .help("Period to group completions by")
+                            .value_parser(value_parser!(BucketSize))
+                            .default_value("week"),
+                    )
+                    .arg(
+                        Arg::new("csv")
+                            .required(false)
+                            .long("csv")
+                            .conflicts_with("json")
+                            .help("Print metrics as CSV")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("json")
+                            .required(false)
+                            .long("json")
+                            .conflicts_with("porcelain")
+                            .help("Print metrics as JSON")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("porcelain")
+                            .required(false)
+                            .long("porcelain")
+                            .conflicts_with("csv")
+                            .help("Print metrics in a stable line format for scripts")
+                            .num_args(0..=1)
+                            .require_equals(true)
+                            .default_missing_value("v1")
+                            .value_parser(value_parser!(PorcelainVersion)),
+                    ),
+            )
+            .subcommand(
+                Command::new("normalize-priorities")
+                    .about("Renumber priorities as 1, 2, 3... keeping their order"),
+            )
+            .subcommand(
+                Command::new("sweep")
+                    .about("Delete the todos matching the sweep_rules in the config")
+                    .arg(
+                        Arg::new("dry-run")
+                            .required(false)
+                            .long("dry-run")
+                            .help("Only show what would be deleted")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("bulk-add")
+                    .about("Add a todo for each line of a file")
+                    .arg(
+                        Arg::new("file")
+                            .required(true)
+                            .help("File with one `title | priority | tags` entry per line")
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(
+                        Arg::new("dup-check")
+                            .required(false)
+                            .long("dup-check")
+                            .help("Warn about titles an open todo already has, as add does")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("export")
+                    .about("Export todos to another format")
+                    .arg(
+                        Arg::new("format")
+                            .required(true)
+                            .long("format")
+                            .short('f')
+                            .help("Format to export to")
+                            .value_parser(value_parser!(ExportFormat)),
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .required(false)
+                            .long("output")
+                            .short('o')
+                            .help("File to write to instead of stdout")
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(
+                        Arg::new("flatten")
+                            .required(false)
+                            .long("flatten")
+                            .help("With --format json, write flat objects with derived fields for analytics tools")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("gzip")
+                            .required(false)
+                            .long("gzip")
+                            .help("With --format json, compress the export with gzip, e.g. for -o backup.json.gz")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .args(filter_args()),
+            )
+            .subcommand(
+                Command::new("doctor")
+                    .about("Check the todo list for inconsistencies")
+                    .arg(
+                        Arg::new("fix")
+                            .required(false)
+                            .long("fix")
+                            .help("Repair the issues that can be fixed automatically")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("import")
+                    .about("Add the todos from a todo file, e.g. one from another machine")
+                    .arg(
+                        Arg::new("file")
+                            .required(true)
+                            .help("Todo file to import, in the format of todos.json, optionally gzip-compressed")
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(
+                        Arg::new("merge-strategy")
+                            .required(false)
+                            .long("merge-strategy")
+                            .help("Which copy to keep of a todo that is in both lists but differs")
+                            .value_parser(value_parser!(Strategy))
+                            .default_value("newest-wins"),
+                    ),
+            )
+            .subcommand(
+                Command::new("validate")
+                    .about("Check any todo file for problems, without changing it")
+                    .arg(
+                        Arg::new("file")
+                            .required(true)
+                            .help("Todo file to check")
+                            .value_parser(value_parser!(PathBuf)),
+                    ),
+            )
+            .subcommand(
+                Command::new("config")
+                    .about("Inspect the configuration")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("show")
+                            .about("Print the effective settings and where each came from"),
+                    )
+                    .subcommand(Command::new("path").about("Print where the config file lives"))
+                    .subcommand(
+                        Command::new("validate")
+                            .about("Check the config file and TOTO_* variables for mistakes"),
+                    )
+                    .after_help(
+                        "Settings come from command line flags, then TOTO_* environment \
+                         variables (e.g. TOTO_TZ), then config.json in the data directory, \
+                         then the built-in defaults.",
+                    ),
+            )
+            .try_get_matches_from(args)?;
+
+        // Validation collects every problem, where loading stops at the first.
+        if let Some(("config", config_matches)) = matches.subcommand()
+            && let Some(("validate", _)) = config_matches.subcommand()
+        {
+            return self.validate_config();
+        }
+
+        self.config = Config::load(&self.file_path)?;
+        if let Some(id_mode) = matches.get_one::<IdMode>("id-mode") {
+            self.config.id_mode = *id_mode;
+            self.config.mark_from_command_line("id_mode");
+        }
+        if matches.get_flag("raw-id") {
+            self.config.raw_id = true;
+            self.config.mark_from_command_line("raw_id");
+        }
+        if let Some(max_title_bytes) = matches.get_one::<usize>("max-title-bytes") {
+            self.config.max_title_bytes = *max_title_bytes;
+            self.config.mark_from_command_line("max_title_bytes");
+        }
+        if matches.get_flag("strict-ids") {
+            self.config.strict_ids = true;
+            self.config.mark_from_command_line("strict_ids");
+        }
+        if let Some(tz) = matches.get_one::<Tz>("tz") {
+            self.config.tz = *tz;
+            self.config.mark_from_command_line("tz");
+        }
+        if let Some(date_format) = matches.get_one::<String>("date-format") {
+            self.config.date_format = date_format.clone();
+            self.config.mark_from_command_line("date_format");
+        }
+        self.skip_integrity = matches.get_flag("skip-integrity");
+
+        // Configuration is inspected before the todo file is touched.
+        if let Some(("config", config_matches)) = matches.subcommand() {
+            return self.config_command(config_matches);
+        }
+
+        // The doctor reads the raw file itself since a damaged file may not load.
+        if let Some(("doctor", doctor_matches)) = matches.subcommand() {
+            return self.doctor(doctor_matches.get_flag("fix"));
+        }
+        if let Some(("validate", validate_matches)) = matches.subcommand() {
+            return self.validate_file(validate_matches.get_one::<PathBuf>("file").unwrap());
+        }
+        // Watching re-reads the file on every refresh, saving it only when
+        // catching up changed something, so it can't share the load below.
+        if let Some(("watch", watch_matches)) = matches.subcommand() {
+            return self.watch(
+                *watch_matches.get_one::<TimeDelta>("interval").unwrap(),
+                *watch_matches.get_one::<usize>("count").unwrap(),
+                watch_matches.get_flag("notify"),
+            );
+        }
+
+        self.load()?;
+        let before = self.ordered_todos();
+
+        // A failed --fail-if-any or --fail-if-none check, reported once the
+        // todos are saved.
+        let mut gate = Ok(());
+        match matches.subcommand() {
+            Some(("add", add_matches)) => {
+                let words: Vec<&String> = add_matches
+                    .get_many::<String>("words")
+                    .unwrap_or_default()
+                    .collect();
+                if words.len() > 1 {
+                    writeln!(
+                        self.output.err(),
+                        "Warning: joined {} words into the title, quote it or use -t to be sure: toto add \"{}\"",
+                        words.len(),
+                        words
+                            .iter()
+                            .map(|word| word.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    )?;
+                }
+                let template = match add_matches.get_one::<String>("template-from") {
+                    Some(source) => {
+                        let Some(template) = self.template(self.parse_todo_id(source)?) else {
+                            bail!("Template todo not found");
+                        };
+                        template
+                    }
+                    None => {
+                        let (title, description, priority) = self.add_fields(add_matches)?;
+                        NewTodo {
+                            description,
+                            priority,
+                            ..NewTodo::new(title)
+                        }
+                    }
+                };
+                let given =
+                    |name: &str| add_matches.value_source(name) == Some(ValueSource::CommandLine);
+                let mut tags = template.tags;
+                tags.extend(
+                    add_matches
+                        .get_many::<String>("tag")
+                        .unwrap_or_default()
+                        .cloned(),
+                );
+                let new = NewTodo {
+                    id: add_matches.get_one::<Uuid>("id").copied(),
+                    title: add_title(add_matches).unwrap_or(template.title),
+                    description: add_matches
+                        .get_one::<String>("description")
+                        .cloned()
+                        .or(template.description),
+                    priority: if given("priority") {
+                        *add_matches.get_one::<u8>("priority").unwrap()
+                    } else {
+                        template.priority
+                    },
+                    // `--priority 255` asks for no priority, whatever the tags.
+                    priority_from_tags: !given("priority"),
+                    in_progress: add_matches.get_flag("in-progress"),
+                    created_at: add_matches.get_one::<DateTime<Utc>>("at").copied(),
+                    started_at: add_matches.get_one::<DateTime<Utc>>("started-at").copied(),
+                    done: add_matches.get_flag("done"),
+                    completed_at: add_matches
+                        .get_one::<DateTime<Utc>>("completed-at")
+                        .copied(),
+                    due_at: add_matches.get_one::<DateTime<Utc>>("due").copied(),
+                    parent: match add_matches.get_one::<String>("parent") {
+                        Some(parent) => Some(self.parse_todo_id(parent)?),
+                        None => None,
+                    },
+                    blocked_by: add_matches
+                        .get_many::<String>("blocked-by")
+                        .unwrap_or_default()
+                        .map(|id| self.parse_todo_id(id))
+                        .collect::<Result<Vec<Uuid>>>()?,
+                    auto_start: add_matches.get_flag("auto-start"),
+                    tags,
+                    color: add_matches.get_one::<Color>("color").copied(),
+                    icon: add_matches.get_one::<String>("icon").cloned(),
+                };
+                if add_matches.get_flag("uuid-v7") {
+                    // v7 ids sort by creation time; existing v4 ids keep working.
+                    self.id_generator = Box::new(Uuid::now_v7);
+                }
+
+                let dedupe = add_matches.get_flag("dedupe");
+                if dedupe && let Some(existing) = self.find_open_by_title(&new.title) {
+                    let outcome = self.merge_into(
+                        existing,
+                        new.description.as_ref(),
+                        &new.priority,
+                        &new.tags,
+                    );
+                    self.report(&outcome)?;
+                } else {
+                    // With --dedupe a match was merged above, so this is
+                    // only reached without one.
+                    if !add_matches.get_flag("allow-duplicate")
+                        && let Some(duplicate) = self.find_open_by_title(&new.title)
+                    {
+                        writeln!(
+                            self.output.err(),
+                            "Warning: an open todo with this title already exists: {}",
+                            duplicate
+                        )?;
+                    }
+                    let id = self.add(new)?;
+                    if dedupe {
+                        writeln!(
+                            self.output.out(),
+                            "No open todo with that title, added a new one."
+                        )?;
+                    }
+                    self.report(&Outcome::Added(id))?;
+                }
+            }
+            Some(("list", list_matches)) => {
+                let verbose = list_matches.get_flag("verbose");
+                let flat_archived = list_matches.get_flag("flat-archived");
+                let mut layout = Layout {
+                    ellipsis: list_matches
+                        .get_one::<String>("ellipsis")
+                        .unwrap_or(&self.config.ellipsis)
+                        .clone(),
+                    auto_columns: list_matches.get_flag("columns-auto"),
+                    no_truncate: list_matches.get_flag("no-truncate"),
+                    tiny_below: self.config.tiny_width,
+                    highlight: list_matches.get_one::<String>("highlight").cloned(),
+                    bars: list_matches.get_flag("bars"),
+                    no_header: list_matches.get_flag("no-header"),
+                    dates: self.date_style(list_matches.get_flag("compact-dates")),
+                    max_width: list_matches
+                        .get_one::<u64>("limit-width")
+                        .map(|width| *width as usize),
+                    priority_symbols: list_matches
+                        .get_flag("priority-symbols")
+                        .then(|| self.config.priority_symbols.clone()),
+                    borders: list_matches
+                        .get_flag("borders")
+                        .then(|| Borders::detect(list_matches.get_flag("ascii"))),
+                    tag_colors: self.config.tag_colors.clone(),
+                    size: if list_matches.get_flag("wide") {
+                        Some(Size::Large)
+                    } else if list_matches.get_flag("narrow") {
+                        Some(Size::Small)
+                    } else {
+                        None
+                    },
+                    ..Layout::default()
+                };
+                if list_matches.get_flag("no-color") || std::env::var_os("NO_COLOR").is_some() {
+                    layout.color = false;
+                }
+                if let Some(aligns) = list_matches.get_many::<(Column, Align)>("align") {
+                    layout.align.extend(aligns.copied());
+                }
+                let view = self.list_view(list_matches)?;
+                let mut selection = self.selection(&view);
+                selection.filter.hide_snoozed = !list_matches.get_flag("include-snoozed");
+                let window = match list_matches.get_one::<u64>("page") {
+                    Some(page) => Window::Page {
+                        page: *page as usize,
+                        size: *list_matches.get_one::<u64>("page-size").unwrap() as usize,
+                    },
+                    None => Window::Range {
+                        offset: list_matches
+                            .get_one::<usize>("offset")
+                            .copied()
+                            .unwrap_or(0),
+                        limit: list_matches.get_one::<usize>("limit").copied(),
+                    },
+                };
+                let pending_only = list_matches.get_flag("pending-only");
+                let separator = list_matches
+                    .get_one::<String>("field-separator")
+                    .map_or("\t", String::as_str);
+                let listed = if list_matches.get_flag("json") {
+                    let compact = list_matches.get_flag("compact");
+                    self.list_json(&selection, window, pending_only, compact)?
+                } else if list_matches.get_flag("plain") {
+                    self.list_plain(&selection, window, pending_only, separator)?
+                } else if list_matches.get_flag("ids-only") {
+                    self.list_ids(&selection, window, pending_only, separator)?
+                } else if flat_archived {
+                    self.list_flat_archived(&layout, &selection.filter, verbose)?
+                } else if list_matches.get_flag("since-commit") {
+                    let changed = self.changed_since_commit()?;
+                    self.list_todos(
+                        &layout,
+                        &selection,
+                        window,
+                        verbose,
+                        pending_only,
+                        Some(&changed),
+                    )?
+                } else {
+                    self.list_todos(&layout, &selection, window, verbose, pending_only, None)?
+                };
+                gate = check_gate(list_matches, listed);
+            }
+            Some(("update", update_matches)) => {
+                let id = update_matches.get_one::<String>("id").unwrap();
+                let title = update_matches.get_one::<String>("title");
+                let description = update_matches.get_one::<String>("description");
+                let priority = update_matches.get_one::<u8>("priority");
+                let in_progress = update_matches.get_one::<bool>("in-progress");
+                let completed = update_matches.get_one::<bool>("completed");
+                let deleted = update_matches.get_one::<bool>("deleted");
+                let color = if update_matches.get_flag("clear-color") {
+                    Some(None)
+                } else {
+                    update_matches.get_one::<Color>("color").copied().map(Some)
+                };
+                let icon = update_matches.get_one::<String>("icon");
+                let due_at = update_matches.get_one::<DateTime<Utc>>("due").copied();
+                let outcome = self.update_todo(
+                    id,
+                    title,
+                    description,
+                    priority,
+                    in_progress,
+                    completed,
+                    deleted,
+                    color,
+                    icon,
+                    due_at,
+                )?;
+                self.report(&outcome)?;
+            }
+            Some(("start", start_matches)) => {
+                let id = start_matches.get_one::<String>("id").unwrap();
+                let outcome = self.start_todo(id)?;
+                self.report(&outcome)?;
+            }
+            Some(("next", _)) => {
+                self.top(&self.default_layout(), 1)?;
+            }
+            Some(("top", top_matches)) => {
+                let count = *top_matches.get_one::<usize>("count").unwrap();
+                self.top(&self.default_layout(), count)?;
+            }
+            Some(("set-priority", set_priority_matches)) => {
+                let id = set_priority_matches.get_one::<String>("id").unwrap();
+                let todo_id = self.parse_todo_id(id)?;
+                let level = *set_priority_matches.get_one::<u8>("level").unwrap();
+                let outcome = self.set_priority(todo_id, level)?;
+                self.report(&outcome)?;
+            }
+            Some(("set-due", set_due_matches)) => {
+                let id = set_due_matches.get_one::<String>("id").unwrap();
+                let todo_id = self.parse_todo_id(id)?;
+                let when = *set_due_matches.get_one::<DateTime<Utc>>("when").unwrap();
+                let outcome = self.set_due(todo_id, Some(when))?;
+                self.report(&outcome)?;
+            }
+            Some(("clear-due", clear_due_matches)) => {
+                let id = clear_due_matches.get_one::<String>("id").unwrap();
+                let todo_id = self.parse_todo_id(id)?;
+                let outcome = self.set_due(todo_id, None)?;
+                self.report(&outcome)?;
+            }
+            Some(("touch", touch_matches)) => {
+                let id = self.parse_todo_id(touch_matches.get_one::<String>("id").unwrap())?;
+                let outcome = self.touch(id)?;
+                self.report(&outcome)?;
+            }
+            Some(("tag", tag_matches)) => {
+                let name = |matches: &ArgMatches, id: &str| {
+                    matches.get_one::<String>(id).unwrap().trim().to_lowercase()
+                };
+                let (op, op_matches) = match tag_matches.subcommand() {
+                    Some(("add", m)) => (TagOp::Add(name(m, "name")), m),
+                    Some(("remove", m)) => (TagOp::Remove(name(m, "name")), m),
+                    Some(("rename", m)) => {
+                        let op = TagOp::Rename {
+                            from: name(m, "from"),
+                            to: name(m, "to"),
+                        };
+                        (op, m)
+                    }
+                    _ => unreachable!("tag requires a subcommand"),
+                };
+                let mut view = ListView::default();
+                view.apply_matches(op_matches);
+                let selection = self.selection(&view);
+                let affected = self
+                    .list_filtered(&selection)
+                    .iter()
+                    .filter(|todo| op.changes(&todo.data.tags).is_some())
+                    .count();
+                if affected > TAG_BATCH_CONFIRM && !op_matches.get_flag("yes") {
+                    bail!(
+                        "This changes the tags of {} todos, pass --yes to go ahead",
+                        affected
+                    );
+                }
+                let changed = self.retag(&selection, &op).len();
+                let message = match &op {
+                    TagOp::Add(tag) => format!("Tagged {} todo(s) with \"{}\".", changed, tag),
+                    TagOp::Remove(tag) => {
+                        format!("Removed \"{}\" from {} todo(s).", tag, changed)
+                    }
+                    TagOp::Rename { from, to } => {
+                        format!("Renamed \"{}\" to \"{}\" on {} todo(s).", from, to, changed)
+                    }
+                };
+                writeln!(self.output.out(), "{}", message)?;
+            }
+            Some(("status", status_matches)) => {
+                let id = self.parse_todo_id(status_matches.get_one::<String>("id").unwrap())?;
+                let Some(todo) = self.todo(id) else {
+                    bail!("Todo not found");
+                };
+                let status = if status_matches.get_flag("short") {
+                    todo.data.status.letter()
+                } else {
+                    todo.data.status.name()
+                };
+                writeln!(self.output.out(), "{}", status)?;
+            }
+            Some(("import", import_matches)) => {
+                let path = import_matches.get_one::<PathBuf>("file").unwrap();
+                if !path.exists() {
+                    bail!("Failed to read {}, it does not exist", path.display());
+                }
+                let todos = read_todos(path)?;
+                let strategy = *import_matches
+                    .get_one::<Strategy>("merge-strategy")
+                    .unwrap();
+                let outcome = self.import(todos, strategy)?;
+                self.report(&outcome)?;
+            }
+            Some(("show", show_matches)) => {
+                let id = show_matches
+                    .get_one::<String>("id")
+                    .or(show_matches.get_one::<String>("todo"))
+                    .unwrap();
+                let Some(todo) = self.todo(self.parse_todo_id(id)?) else {
+                    bail!("Todo not found");
+                };
+                if show_matches.get_flag("history") {
+                    let entries = journal::load(&self.file_path)?;
+                    let steps = journal::history(&entries, &self.ordered_todos(), todo.id);
+                    if steps.is_empty() {
+                        writeln!(
+                            self.output.out(),
+                            "No recorded changes to \"{}\" (the journal keeps the last {} changes to the list).",
+                            todo.data.title,
+                            journal::JOURNAL_LIMIT
+                        )?;
+                    }
+                    let history = journal::render_history(&steps, &self.config.date_style());
+                    write!(self.output.out(), "{}", history)?;
+                } else if let Some(name) = show_matches.get_one::<String>("field") {
+                    let Some(value) = todo.field(name) else {
+                        bail!(
+                            "Unknown field '{}', expected one of: {}",
+                            name,
+                            Todo::FIELDS.join(", ")
+                        );
+                    };
+                    match value {
+                        // Strings print bare so scripts needn't unquote them.
+                        Value::String(text) => writeln!(self.output.out(), "{}", text)?,
+                        Value::Null => writeln!(self.output.out())?,
+                        value => writeln!(self.output.out(), "{}", value)?,
+                    }
+                } else if show_matches.get_flag("json") {
+                    let json =
+                        serde_json::to_string_pretty(&todo).context("Failed to serialize todo")?;
+                    writeln!(self.output.out(), "{}", json)?;
+                } else {
+                    let dates = self.date_style(show_matches.get_flag("compact-dates"));
+                    term::print_details(&mut *self.output.out(), &dates, &todo)?;
+                }
+            }
+            Some(("snooze", snooze_matches)) => {
+                let id = snooze_matches.get_one::<String>("id").unwrap();
+                let every = snooze_matches.get_one::<TimeDelta>("every").copied();
+                let until = match snooze_matches.get_one::<DateTime<Utc>>("until") {
+                    Some(until) => *until,
+                    None => {
+                        let snooze_for = snooze_matches.get_one::<TimeDelta>("for").copied();
+                        Utc::now() + snooze_for.or(every).unwrap()
+                    }
+                };
+                let todo_id = self.parse_todo_id(id)?;
+                let force = snooze_matches.get_flag("force");
+                let outcome = self.snooze_every(todo_id, until, every, force)?;
+                self.report(&outcome)?;
+            }
+            Some(("reopen", reopen_matches)) => {
+                let id = reopen_matches.get_one::<String>("id").unwrap();
+                let outcome = self.reopen_todo(id)?;
+                self.report(&outcome)?;
+            }
+            Some(("complete", complete_matches)) => {
+                let note = complete_matches.get_one::<String>("note");
+                let quiet = complete_matches.get_flag("quiet");
+                if complete_matches.get_flag("cascade-parent") {
+                    self.config.auto_complete_parents = true;
+                    self.config.mark_from_command_line("auto_complete_parents");
+                }
+                if complete_matches.get_flag("started") {
+                    if !complete_matches.get_flag("yes") {
+                        bail!("This completes every todo in progress, pass --yes to go ahead");
+                    }
+                    let completed = self.complete_started(note.map(String::as_str))?;
+                    for id in &completed {
+                        writeln!(
+                            self.output.out(),
+                            "Completed \"{}\"",
+                            self.todo_map[id].title
+                        )?;
+                    }
+                    if completed.is_empty() {
+                        writeln!(self.output.out(), "No todos in progress.")?;
+                    }
+                } else {
+                    let id = complete_matches.get_one::<String>("id").unwrap();
+                    let at = complete_matches.get_one::<DateTime<Utc>>("at").copied();
+                    let outcome = self.complete_todo(id, note, at)?;
+                    if let Outcome::Completed { parents, .. } = &outcome {
+                        for parent in parents {
+                            writeln!(
+                                self.output.out(),
+                                "Completed \"{}\" too, all of its subtasks are done.",
+                                self.todo_map[parent].title
+                            )?;
+                        }
+                    }
+                    // Quiet only hides the progress line, not problems.
+                    if !(quiet && matches!(outcome, Outcome::Completed { .. })) {
+                        self.report(&outcome)?;
+                    }
+                }
+            }
+            Some(("delete", delete_matches)) => {
+                let id = delete_matches.get_one::<String>("id").unwrap();
+                let reason = delete_matches.get_one::<String>("reason");
+                let at = delete_matches.get_one::<DateTime<Utc>>("at").copied();
+                let outcome = self.delete_todo(id, reason, at)?;
+                self.report(&outcome)?;
+            }
+            Some(("sync", sync_matches)) => {
+                let mode = if sync_matches.get_flag("archive-only") {
+                    SyncMode::ArchiveOnly
+                } else if sync_matches.get_flag("git-only") {
+                    SyncMode::GitOnly
+                } else {
+                    SyncMode::Both
+                };
+                let pull = sync_matches
+                    .get_flag("pull")
+                    .then(|| *sync_matches.get_one::<Strategy>("merge-strategy").unwrap());
+                self.sync(
+                    mode,
+                    sync_matches.get_flag("prune-empty-archives"),
+                    sync_matches.get_flag("sign"),
+                    pull,
+                )?;
+            }
+            Some(("count", count_matches)) => {
+                let mut view = ListView::default();
+                view.apply_matches(count_matches);
+                let todos = self.ordered_todos();
+                let selected: Vec<&Todo> = self
+                    .selection(&view)
+                    .apply(&todos)
+                    .into_iter()
+                    .map(|(_, todo)| todo)
+                    .collect();
+                let by = count_matches.get_one::<GroupBy>("by").copied();
+                if let Some(version) = count_matches.get_one::<PorcelainVersion>("porcelain") {
+                    let groups = by.map(|by| (by, stats::group_counts(&selected, by)));
+                    let groups = groups.as_ref().map(|(by, groups)| (*by, groups.as_slice()));
+                    let rendered = stats::render_count_porcelain(selected.len(), groups, *version);
+                    write!(self.output.out(), "{}", rendered)?;
+                } else {
+                    match by {
+                        Some(by) => {
+                            for (group, count) in stats::group_counts(&selected, by) {
+                                writeln!(self.output.out(), "{}: {}", group, count)?;
+                            }
+                        }
+                        None => writeln!(self.output.out(), "{}", selected.len())?,
+                    }
+                }
+                gate = check_gate(count_matches, selected.len());
+            }
+            Some(("diff", _)) => {
+                self.diff()?;
+            }
+            Some(("recover", recover_matches)) => {
+                let outcome = self.recover(recover_matches.get_one::<String>("id").unwrap())?;
+                self.report(&outcome)?;
+            }
+            Some(("undo", undo_matches)) => {
+                if undo_matches.get_flag("list") {
+                    let entries = journal::load(&self.file_path)?;
+                    if entries.is_empty() {
+                        writeln!(self.output.out(), "Nothing to undo.")?;
+                    }
+                    let list = journal::render_list(&entries, &self.config.date_style());
+                    write!(self.output.out(), "{}", list)?;
+                } else {
+                    let count = undo_matches.get_one::<u64>("to").copied().unwrap_or(1);
+                    self.undo(count as usize)?;
+                }
+            }
+            Some(("stats", stats_matches)) => {
+                let bucket = *stats_matches.get_one::<BucketSize>("bucket").unwrap();
+                let todos = self.ordered_todos();
+                let stats = stats::compute(&todos, bucket, self.config.count_deleted_in_stats);
+                if let Some(version) = stats_matches.get_one::<PorcelainVersion>("porcelain") {
+                    write!(self.output.out(), "{}", stats.render_porcelain(*version))?;
+                } else if stats_matches.get_flag("csv") {
+                    write!(self.output.out(), "{}", stats.render_csv())?;
+                } else if stats_matches.get_flag("json") {
+                    writeln!(
+                        self.output.out(),
+                        "{}",
+                        stats.render_json().context("Failed to serialize stats")?
+                    )?;
+                } else {
+                    write!(self.output.out(), "{}", stats.render_human())?;
+                }
+            }
+            Some(("normalize-priorities", _)) => {
+                let outcome = self.normalize_priorities()?;
+                self.report(&outcome)?;
+            }
+            Some(("sweep", sweep_matches)) => {
+                self.sweep(sweep_matches.get_flag("dry-run"))?;
+            }
+            Some(("bulk-add", bulk_matches)) => {
+                let path = bulk_matches.get_one::<PathBuf>("file").unwrap();
+                let dup_check = bulk_matches.get_flag("dup-check");
+                let outcome = self.bulk_add(path, dup_check)?;
+                self.report(&outcome)?;
+            }
+            Some(("export", export_matches)) => {
+                let format = *export_matches.get_one::<ExportFormat>("format").unwrap();
+                let output = export_matches.get_one::<PathBuf>("output");
+                // Export only uses the options given now, never the saved view.
+                let mut view = ListView::default();
+                view.apply_matches(export_matches);
+                let flatten = export_matches.get_flag("flatten");
+                let gzip = export_matches.get_flag("gzip");
+                self.export(format, flatten, gzip, &self.selection(&view), output)?;
+            }
+            _ => {}
+        };
+        // Undoing isn't journaled itself. Nor is sync, whose archive and
+        // commit live outside the todo file; every earlier snapshot still
+        // has the todos it archived or pulled in, so the journal starts
+        // over instead.
+        if let Some(operation) = matches.subcommand_name()
+            && operation != "undo"
+            && let Some(entry) =
+                journal::Entry::new(operation, Utc::now(), before, &self.ordered_todos())
+        {
+            let mut entries = Vec::new();
+            if operation != "sync" {
+                entries = journal::load(&self.file_path)?;
+                journal::record(&mut entries, entry);
+            }
+            journal::save(&self.file_path, &entries)?;
+        }
+        self.save_todos()?;
+
+        gate
+    }
+
+    /// Puts the list back the way it was before the last `count` journaled
+    /// changes and drops them from the journal. A `sync` that changed the
+    /// list clears the journal, so undo never brings back archived todos.
+    ///
+    /// ```
+    /// use toto::Cli;
+    /// use toto::output::Output;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-undo-sync-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| {
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(std::io::sink(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))
+    /// };
+    /// let titles = || -> anyhow::Result<Vec<String>> {
+    ///     let mut cli = Cli::new(path.clone());
+    ///     cli.load()?;
+    ///     Ok(cli.todos().into_iter().map(|todo| todo.data.title).collect())
+    /// };
+    /// run(&["add", "Pack the boxes"])?;
+    /// run(&["add", "Book the van"])?;
+    /// run(&["complete", "--id", "0"])?;
+    /// run(&["undo"])?;
+    /// run(&["complete", "--id", "0"])?;
+    ///
+    /// run(&["sync", "--archive-only"])?;
+    /// assert_eq!(titles()?, ["Book the van"]);
+    /// run(&["undo"])?;
+    /// assert_eq!(titles()?, ["Book the van"]);
+    ///
+    /// // Changes after the sync are journaled as usual.
+    /// run(&["add", "Return the keys"])?;
+    /// run(&["undo"])?;
+    /// assert_eq!(titles()?, ["Book the van"]);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn undo(&mut self, count: usize) -> Result<()> {
+        let mut entries = journal::load(&self.file_path)?;
+        if entries.is_empty() {
+            writeln!(self.output.out(), "Nothing to undo.")?;
+            return Ok(());
+        }
+        if count > entries.len() {
+            bail!(
+                "Only {} change(s) can be undone, see `toto undo --list`",
+                entries.len()
+            );
+        }
+        let undone = entries.split_off(entries.len() - count);
+        let restored = &undone[0];
+        self.todo_map = restored
+            .before
+            .iter()
+            .map(|todo| (todo.id, todo.data.clone()))
+            .collect();
+        journal::save(&self.file_path, &entries)?;
+        for entry in undone.iter().rev() {
+            writeln!(
+                self.output.out(),
+                "Undid {}: {}",
+                entry.operation,
+                entry.titles.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads the todo file into the map. A todo whose id appears more than
+    /// once keeps its most recently updated copy, with a warning, or fails
+    /// the load under `strict_ids`.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    /// use toto::output::{Buffer, Output};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-duplicates-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let copy = |title: &str, in_progress_at: Option<i64>| {
+    ///     serde_json::json!({
+    ///         "id": uuid::Uuid::from_u128(1),
+    ///         "data": {
+    ///             "title": title, "description": null, "priority": 1,
+    ///             "status": if in_progress_at.is_some() { "InProgress" } else { "Pending" },
+    ///             "created_at": 1_000, "in_progress_at": in_progress_at,
+    ///             "completed_at": null, "deleted_at": null,
+    ///         },
+    ///     })
+    /// };
+    /// let todos = serde_json::json!([copy("New title", Some(2_000)), copy("Old title", None)]);
+    /// std::fs::write(dir.join("todos.json"), todos.to_string())?;
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let err = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(std::io::sink(), err.clone()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok(err.contents())
+    /// };
+    ///
+    /// let err = run(&["--strict-ids", "list"]).unwrap_err();
+    /// assert_eq!(err.to_string(), "Found 1 todo id(s) used more than once, run `toto doctor` to inspect them");
+    ///
+    /// let warnings = run(&["list"])?;
+    /// assert!(warnings.contains(
+    ///     "Warning: todo 00000000-0000-0000-0000-000000000001 appears more than once, keeping the most recently updated copy\n"
+    /// ), "{}", warnings);
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// assert_eq!(todos.len(), 1);
+    /// assert_eq!(todos[0].data.title, "New title");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn load_todos(&mut self) -> Result<()> {
+        let file_path = format!("{}/todos.json", self.file_path);
+
+        // The same as `read_todos`, with the checksum checked on the raw
+        // bytes in between.
+        if let Some(contents) = read_todo_bytes(Path::new(&file_path))? {
+            // Checked before parsing, since a truncated file often won't.
+            if !self.skip_integrity
+                && !integrity::verify(Path::new(&file_path), &contents)
+                    .context("Failed to read the todo file checksum")?
+            {
+                self.integrity_mismatch = true;
+                writeln!(
+                    self.output.err(),
+                    "Warning: {} doesn't match its recorded checksum in {}, it was changed or damaged since toto last saved it (run `toto doctor --fix` to accept it as it is, or pass --skip-integrity to skip this check)",
+                    file_path,
+                    integrity::sidecar(Path::new(&file_path)).display()
+                )?;
+            }
+            let todos = decode_todos(Path::new(&file_path), &contents)?;
+
+            // The map holds one todo per id, so a badly merged file would
+            // otherwise lose all but the last copy without a word.
+            let mut duplicates: Vec<Uuid> = Vec::new();
+            for todo in todos {
+                match self.todo_map.get(&todo.id) {
+                    Some(kept) => {
+                        if !duplicates.contains(&todo.id) {
+                            duplicates.push(todo.id);
+                        }
+                        // Like `doctor --fix`, ties keep the earlier entry.
+                        if doctor::latest_timestamp(&todo.data) > doctor::latest_timestamp(kept) {
+                            self.todo_map.insert(todo.id, todo.data);
+                        }
+                    }
+                    None => {
+                        self.todo_map.insert(todo.id, todo.data);
+                    }
+                }
+            }
+            if !duplicates.is_empty() && self.config.strict_ids {
+                bail!(
+                    "Found {} todo id(s) used more than once, run `toto doctor` to inspect them",
+                    duplicates.len()
+                );
+            }
+            for id in duplicates {
+                writeln!(
+                    self.output.err(),
+                    "Warning: todo {} appears more than once, keeping the most recently updated copy",
+                    id
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save_todos(&self) -> Result<()> {
+        let file_path = format!("{}/todos.json", self.file_path);
+
+        // Attempt to open the file create it if it doesn't exist
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&file_path)
+            .context("Failed to open or create todo file")?;
+
+        let mut writer = BufWriter::new(file);
+
+        // A stable order keeps the file from changing, and git from seeing a
+        // diff, when nothing but the map's iteration order did.
+        let todos = self.ordered_todos();
+
+        let contents =
+            serde_json::to_vec_pretty(&todos).context("Failed to serialize todo list")?;
+        writer
+            .write_all(&contents)
+            .and_then(|()| writer.flush())
+            .context("Failed to write todo file")?;
+        if !self.integrity_mismatch {
+            integrity::record(Path::new(&file_path), &contents)
+                .context("Failed to write the todo file checksum")?;
+        }
+        Ok(())
+    }
+
+    /// A new todo copying the title, with " (copy)" added, description,
+    /// priority and tags of the todo `id`, for `add --template-from`. Its
+    /// status and timestamps start afresh and it gets a new id once added.
+    ///
+    /// ```
+    /// use toto::{Cli, NewTodo, TodoStatus};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let source = cli.add(NewTodo {
+    ///     description: Some(String::from("Before the 5th")),
+    ///     priority: 2,
+    ///     tags: vec![String::from("bills")],
+    ///     ..NewTodo::new("Pay rent")
+    /// })?;
+    /// cli.complete(source, None)?;
+    ///
+    /// let copy = cli.add(cli.template(source).unwrap())?;
+    /// assert_ne!(copy, source);
+    /// let copy = cli.todo(copy).unwrap().data;
+    /// assert_eq!(copy.title, "Pay rent (copy)");
+    /// assert_eq!(copy.description.as_deref(), Some("Before the 5th"));
+    /// assert_eq!((copy.priority, copy.tags), (2, vec![String::from("bills")]));
+    /// assert_eq!(copy.status, TodoStatus::Pending);
+    /// assert!(copy.completed_at.is_none());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// `add --template-from` starts from it, with the other flags given
+    /// overriding its fields and adding to its tags:
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-template-from-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto", "add"].iter().chain(args));
+    /// run(&["--title", "Pay rent", "--description", "Before the 5th", "--priority", "2", "--tag", "bills"])?;
+    /// run(&["--template-from", "0"])?;
+    /// run(&["--template-from", "0", "--title", "Pay rent in March", "--priority", "1", "--tag", "march"])?;
+    ///
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// let find = |title: &str| todos.iter().find(|todo| todo.data.title == title).unwrap().data.clone();
+    /// let copy = find("Pay rent (copy)");
+    /// assert_eq!((copy.priority, copy.tags, copy.description.as_deref()), (2, vec![String::from("bills")], Some("Before the 5th")));
+    /// let march = find("Pay rent in March");
+    /// assert_eq!((march.priority, march.tags.join(",")), (1, String::from("bills,march")));
+    /// assert_eq!(march.description.as_deref(), Some("Before the 5th"));
+    ///
+    /// let missing = uuid::Uuid::from_u128(7).to_string();
+    /// let err = run(&["--template-from", &missing]).unwrap_err();
+    /// assert_eq!(err.to_string(), "Template todo not found");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn template(&self, id: Uuid) -> Option<NewTodo> {
+        let source = self.todo_map.get(&id)?;
+        Some(NewTodo {
+            description: source.description.clone(),
+            priority: source.priority,
+            tags: source.tags.clone(),
+            ..NewTodo::new(format!("{} (copy)", source.title))
+        })
+    }
+
+    /// Adds a todo and returns its id. Nothing is written until `save`.
+    ///
+    /// ```
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let id = cli.add(NewTodo {
+    ///     priority: 1,
+    ///     tags: vec![String::from("home")],
+    ///     ..NewTodo::new("Water the plants")
+    /// })?;
+    /// assert_eq!(cli.todo(id).unwrap().data.title, "Water the plants");
+    ///
+    /// // A chosen id is used as is, but only once.
+    /// let chosen = uuid::Uuid::parse_str("0190f1a2-6c1e-7d3b-9f65-3c2b1a4d5e6f")?;
+    /// let renew = || NewTodo { id: Some(chosen), ..NewTodo::new("Renew the lease") };
+    /// assert_eq!(cli.add(renew())?, chosen);
+    /// assert!(cli.add(renew()).is_err());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// `add --id` does the same from the command line.
+    ///
+    /// ```
+    /// use toto::output::Output;
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-add-id-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| {
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(std::io::sink(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))
+    /// };
+    /// let id = "0190f1a2-6c1e-7d3b-9f65-3c2b1a4d5e6f";
+    /// run(&["add", "--title", "Renew the lease", "--priority", "1", "--id", id])?;
+    /// let error = run(&["add", "--title", "Pay the deposit", "--priority", "1", "--id", id]).unwrap_err();
+    /// assert_eq!(error.to_string(), format!("A todo with id {} already exists", id));
+    ///
+    /// let mut cli = Cli::new(path.clone());
+    /// cli.load()?;
+    /// let todos = cli.todos();
+    /// assert_eq!(todos.len(), 1);
+    /// assert_eq!((todos[0].id.to_string(), todos[0].data.title.as_str()), (id.to_string(), "Renew the lease"));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// An unprioritised todo takes the most important priority
+    /// `tag_priorities` maps its tags to; a priority given explicitly wins,
+    /// even `--priority 255`.
+    ///
+    /// ```
+    /// use toto::config::{Config, Source};
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut config = Config::default();
+    /// config.set("tag_priorities", "urgent=high", Source::File)?;
+    /// let mut cli = Cli::new(String::from(".")).with_config(config);
+    /// let urgent = || NewTodo { tags: vec![String::from("Urgent")], ..NewTodo::new("Fix the leak") };
+    /// let id = cli.add(urgent())?;
+    /// assert_eq!(cli.todo(id).unwrap().data.priority, 0);
+    /// let id = cli.add(NewTodo { priority: 40, ..urgent() })?;
+    /// assert_eq!(cli.todo(id).unwrap().data.priority, 40);
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-tag-priority-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// std::fs::write(dir.join("config.json"), r#"{"tag_priorities": "urgent=high"}"#)?;
+    /// let add = |args: &[&str]| -> anyhow::Result<u8> {
+    ///     let mut cli = Cli::new(path.clone());
+    ///     cli.run_from([&["toto", "add", "--title", "Fix the leak", "--tag", "urgent"], args].concat())?;
+    ///     cli.load()?;
+    ///     Ok(cli.todos().into_iter().last().unwrap().data.priority)
+    /// };
+    /// assert_eq!(add(&[])?, 0);
+    /// assert_eq!(add(&["--priority", "255"])?, 255);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// Backdated timestamps have to make a possible history:
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo, TodoStatus};
+    /// use toto::time::parse_timestamp;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-backdate-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto", "add", "--priority", "1"].iter().chain(args));
+    /// run(&[
+    ///     "--title", "Move the servers",
+    ///     "--at", "2024-03-01T09:00:00Z",
+    ///     "--in-progress", "--started-at", "2024-03-02T09:00:00Z",
+    ///     "--done", "--completed-at", "2024-03-03T09:00:00Z",
+    /// ])?;
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// let todo = &todos[0].data;
+    /// let at = |s| parse_timestamp(s).ok();
+    /// assert_eq!(todo.status, TodoStatus::Completed);
+    /// assert_eq!(
+    ///     (Some(todo.created_at), todo.in_progress_at, todo.completed_at),
+    ///     (at("2024-03-01T09:00:00Z"), at("2024-03-02T09:00:00Z"), at("2024-03-03T09:00:00Z"))
+    /// );
+    ///
+    /// // Started before it was created.
+    /// let early = ["--title", "Paint", "--at", "2024-03-02", "--in-progress", "--started-at", "2024-03-01"];
+    /// assert!(run(&early).is_err());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn add(&mut self, new: NewTodo) -> Result<Uuid> {
+        let NewTodo {
+            id,
+            title,
+            description,
+            priority,
+            priority_from_tags,
+            in_progress,
+            created_at,
+            started_at,
+            done,
+            completed_at,
+            due_at,
+            parent,
+            blocked_by,
+            auto_start,
+            tags,
+            color,
+            icon,
+        } = new;
+        self.check_title(&title)?;
+        if let Some(parent) = parent
+            && !self.todo_map.contains_key(&parent)
+        {
+            bail!("Parent todo not found");
+        }
+        if blocked_by.iter().any(|id| !self.todo_map.contains_key(id)) {
+            bail!("Blocking todo not found");
+        }
+
+        let now = Utc::now();
+        let created_at = created_at.unwrap_or(now);
+        let in_progress_at = in_progress.then(|| started_at.unwrap_or(now));
+        let completed_at = done.then(|| completed_at.unwrap_or(now));
+
+        // Backdated timestamps must still describe a possible history.
+        if let Some(started) = in_progress_at
+            && started < created_at
+        {
+            bail!("The todo cannot be started before it was created");
+        }
+        if let Some(completed) = completed_at
+            && completed < in_progress_at.unwrap_or(created_at)
+        {
+            bail!("The todo cannot be completed before it was created or started");
+        }
+
+        let tags = normalize_tags(&tags);
+        let priority = if priority == u8::MAX && priority_from_tags {
+            tags.iter()
+                .filter_map(|tag| self.config.tag_priorities.get(tag).copied())
+                .min()
+                .unwrap_or(priority)
+        } else {
+            priority
+        };
+
+        let id = match id {
+            Some(id) if self.todo_map.contains_key(&id) => {
+                bail!("A todo with id {} already exists", id)
+            }
+            Some(id) => id,
+            None => (self.id_generator)(),
+        };
+        let todo = Todo {
+            id,
+            data: TodoData {
+                title,
+                description,
+                priority,
+                in_progress_at,
+                created_at,
+                completed_at,
+                deleted_at: None,
+                notes: Vec::new(),
+                parent,
+                tags,
+                color,
+                icon,
+                due_at,
+                blocked_by,
+                auto_start,
+                snoozed_until: None,
+                last_touched_at: None,
+                snooze_interval: None,
+                resnoozes: 0,
+                status: if done {
+                    TodoStatus::Completed
+                } else if in_progress {
+                    TodoStatus::InProgress
+                } else {
+                    TodoStatus::Pending
+                },
+            },
+        };
+        self.todo_map.insert(id, todo.data);
+        Ok(id)
+    }
+
+    /// The title, description and priority for `add`. Without a title, and
+    /// only at a terminal, the user is prompted for whichever of them were
+    /// not given; scripts get an error straight away instead of hanging.
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-add-prompt-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let err = Buffer::default();
+    /// let answers = "\nBuy stamps\n\nurgent\nh\n";
+    /// Cli::new(path.clone())
+    ///     .with_output(Output::new(std::io::sink(), err.clone()).with_input(answers.as_bytes()))
+    ///     .with_interactive(true)
+    ///     .run_from(["toto", "add"])?;
+    /// let prompts = err.contents();
+    /// assert!(prompts.starts_with("Title: An answer is required.\nTitle: Description (optional): Priority: "));
+    /// assert!(prompts.contains("'urgent' is not a valid answer"));
+    ///
+    /// let mut cli = Cli::new(path.clone());
+    /// cli.load()?;
+    /// let todo = &cli.todos()[0].data;
+    /// assert_eq!((todo.title.as_str(), todo.description.as_deref(), todo.priority), ("Buy stamps", None, 0));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// Without a terminal nothing is asked, nothing is read from stdin and
+    /// nothing is added:
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-add-scripted-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let err = Buffer::default();
+    /// let scripted = Cli::new(path.clone())
+    ///     .with_output(Output::new(std::io::sink(), err.clone()).with_input("Buy stamps\n".as_bytes()))
+    ///     .with_interactive(false)
+    ///     .run_from(["toto", "add"]);
+    /// assert_eq!(scripted.unwrap_err().to_string(), "A title is required, pass it after add or with -t/--title");
+    /// assert_eq!(err.contents(), "");
+    /// assert!(!dir.join("todos.json").exists());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn add_fields(&self, add_matches: &ArgMatches) -> Result<(String, Option<String>, u8)> {
+        let description = add_matches.get_one::<String>("description").cloned();
+        let mut priority = *add_matches.get_one::<u8>("priority").unwrap();
+        if let Some(title) = add_title(add_matches) {
+            if add_matches.get_flag("interactive-priority") && self.interactive {
+                priority = ask_priority(&self.output, priority)?;
+            }
+            return Ok((title, description, priority));
+        }
+        if !self.interactive {
+            bail!("A title is required, pass it after add or with -t/--title");
+        }
+
+        let title = prompt::ask_required(&self.output, "Title")?;
+        let description = match description {
+            Some(description) => Some(description),
+            None => {
+                Some(prompt::ask(&self.output, "Description (optional)")?).filter(|d| !d.is_empty())
+            }
+        };
+        let priority = if add_matches.value_source("priority") == Some(ValueSource::CommandLine) {
+            priority
+        } else {
+            ask_priority(&self.output, priority)?
+        };
+        Ok((title, description, priority))
+    }
+
+    /// The first open todo, in list order, whose title is exactly `title`.
+    /// `add` looks for one every time unless told not to, so this scans the
+    /// map once without copying or sorting it.
+    ///
+    /// `add` warns when it finds one, but adds the todo all the same:
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    /// use toto::output::{Buffer, Output};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-duplicate-title-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let add = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let err = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(std::io::sink(), err.clone()))
+    ///         .run_from([&["toto", "add", "--priority", "1"], args].concat())?;
+    ///     Ok(err.contents())
+    /// };
+    /// assert_eq!(add(&["--title", "Milk"])?, "");
+    /// assert_eq!(add(&["--title", "Bread"])?, "");
+    /// let warning = add(&["--title", "Milk"])?;
+    /// assert!(warning.starts_with("Warning: an open todo with this title already exists: "), "{}", warning);
+    /// assert_eq!(add(&["--title", "Milk", "--allow-duplicate"])?, "");
+    ///
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// assert_eq!(todos.iter().filter(|todo| todo.data.title == "Milk").count(), 3);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn find_open_by_title(&self, title: &str) -> Option<Uuid> {
+        self.todo_map
+            .iter()
+            .filter(|(_, data)| {
+                matches!(data.status, TodoStatus::Pending | TodoStatus::InProgress)
+                    && data.title == title
+            })
+            .min_by_key(|(id, data)| list_order(**id, data))
+            .map(|(id, _)| *id)
+    }
+
+    /// Folds a repeated `add` into an existing todo: the description becomes
+    /// a note, the more important of the two priorities wins and the tags
+    /// are combined.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-dedupe-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// run(&["add", "-t", "Milk", "-p", "10", "--tag", "dairy"])?;
+    /// run(&["add", "-t", "Milk", "-d", "Two litres", "-p", "3", "--tag", "shop", "--dedupe"])?;
+    /// run(&["add", "-t", "Bread", "-p", "1", "--dedupe"])?;
+    ///
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// assert_eq!(todos.len(), 2);
+    /// let milk = todos.iter().find(|t| t.data.title == "Milk").unwrap();
+    /// assert_eq!((milk.data.priority, milk.data.tags.join(",")), (3, String::from("dairy,shop")));
+    /// assert_eq!(milk.data.notes[0].text, "Two litres");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn merge_into(
+        &mut self,
+        id: Uuid,
+        description: Option<&String>,
+        priority: &u8,
+        tags: &[String],
+    ) -> Outcome {
+        let Some(todo) = self.todo_map.get_mut(&id) else {
+            return Outcome::NotFound;
+        };
+        if let Some(description) = description {
+            todo.notes.push(Note {
+                text: description.clone(),
+                created_at: Utc::now(),
+            });
+        }
+        todo.priority = todo.priority.min(*priority);
+        let combined: Vec<String> = todo.tags.iter().chain(tags).cloned().collect();
+        todo.tags = normalize_tags(&combined);
+        Outcome::Merged(id)
+    }
 
-        match matches.subcommand() {
-            Some(("add", add_matches)) => {
-                println!("calling add");
-                let title = add_matches.get_one::<String>("title").unwrap();
-                let description = add_matches.get_one::<String>("description");
-                let priority = add_matches.get_one::<u8>("priority").unwrap();
-                let in_progress = add_matches.get_one::<bool>("in-progress").unwrap();
-
-                println!(
-                    "Adding with args Title {:?} Desc {:?} Priority {:?} In Prog{:?}",
-                    title, description, priority, in_progress
-                );
-                self.add_todo(title, description, priority, in_progress);
+    fn check_title(&self, title: &str) -> Result<()> {
+        if title.len() > self.config.max_title_bytes {
+            bail!(
+                "Title is {} bytes, over the limit of {} (see --max-title-bytes)",
+                title.len(),
+                self.config.max_title_bytes
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds a pending todo for every line of `path`. Lines are either just a
+    /// title or `title | priority | tags` with comma separated tags; blank
+    /// lines and lines starting with `#` are skipped. With `dup_check`, like
+    /// `add`, it warns about titles an open todo already has, including one
+    /// added earlier in the file.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-bulk-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |file: &str, contents: &str| {
+    ///     let file = dir.join(file);
+    ///     std::fs::write(&file, contents)?;
+    ///     Cli::new(path.clone()).run_from(["toto".as_ref(), "bulk-add".as_ref(), file.as_os_str()])
+    /// };
+    /// run("good.txt", "# groceries\nMilk\n\nEggs | 3 | shop,dairy\n")?;
+    /// // One bad line and nothing from the file is added.
+    /// assert!(run("bad.txt", "Bread\nButter | lots\n").is_err());
+    ///
+    /// let mut todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// todos.sort_by_key(|todo| todo.data.priority);
+    /// let todos: Vec<_> = todos
+    ///     .into_iter()
+    ///     .map(|todo| (todo.data.title, todo.data.priority, todo.data.tags.join(",")))
+    ///     .collect();
+    /// assert_eq!(
+    ///     todos,
+    ///     [
+    ///         (String::from("Eggs"), 3, String::from("shop,dairy")),
+    ///         (String::from("Milk"), 255, String::new()),
+    ///     ]
+    /// );
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// Unlike `add`, it only looks for duplicates with `--dup-check`, so
+    /// scripted imports stay quiet and fast. The open titles are then
+    /// collected once for the whole file, so many finished todos don't slow
+    /// every line down; `benches/bulk_add.rs` times both:
+    ///
+    /// ```
+    /// use toto::cli::{Cli, NewTodo, Todo};
+    /// use toto::output::{Buffer, Output};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-bulk-dup-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let mut cli = Cli::new(path.clone());
+    /// for n in 0..1000 {
+    ///     cli.add(NewTodo { done: true, ..NewTodo::new(format!("Chore {}", n)) })?;
+    /// }
+    /// cli.save()?;
+    /// std::fs::write(dir.join("more.txt"), "Chore 7\nLaundry\nLaundry\n")?;
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let err = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(std::io::sink(), err.clone()))
+    ///         .run_from([&["toto"], args].concat())?;
+    ///     Ok(err.contents())
+    /// };
+    /// let more = dir.join("more.txt").to_string_lossy().into_owned();
+    ///
+    /// // Only the finished chore's title is free, the second Laundry isn't.
+    /// let warnings = run(&["bulk-add", "--dup-check", &more])?;
+    /// assert_eq!(warnings.matches("Warning: an open todo with this title already exists").count(), 1);
+    ///
+    /// assert_eq!(run(&["bulk-add", &more])?, "");
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// assert_eq!(todos.iter().filter(|todo| todo.data.title == "Laundry").count(), 4);
+    /// assert_eq!(todos.iter().filter(|todo| todo.data.title == "Chore 7").count(), 3);
+    /// assert_eq!(todos.len(), 1006);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn bulk_add(&mut self, path: &PathBuf, dup_check: bool) -> Result<Outcome> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        // Parse everything up front so a bad line doesn't leave a partial import.
+        let mut entries = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
-            Some(("list", list_matches)) => {
-                let verbose = list_matches.get_flag("verbose");
-                self.list_todos(verbose);
+            let mut fields = line.split('|').map(str::trim);
+            let title = fields.next().unwrap_or_default();
+            if title.is_empty() {
+                bail!("Line {} has no title", number + 1);
             }
-            Some(("update", update_matches)) => {
-                let id = update_matches.get_one::<String>("id").unwrap();
-                let title = update_matches.get_one::<String>("title");
-                let description = update_matches.get_one::<String>("description");
-                let priority = update_matches.get_one::<u8>("priority");
-                let in_progress = update_matches.get_one::<bool>("in-progress");
-                let completed = update_matches.get_one::<bool>("completed");
-                let deleted = update_matches.get_one::<bool>("deleted");
-                self.update_todo(
-                    id,
-                    title,
-                    description,
-                    priority,
-                    in_progress,
-                    completed,
-                    deleted,
+            let priority = match fields.next() {
+                Some(priority) if !priority.is_empty() => priority
+                    .parse::<u8>()
+                    .with_context(|| format!("Line {} has an invalid priority", number + 1))?,
+                _ => u8::MAX,
+            };
+            let tags: Vec<String> = fields
+                .next()
+                .map(|tags| tags.split(',').map(String::from).collect())
+                .unwrap_or_default();
+            entries.push((title.to_string(), priority, tags));
+        }
+
+        // The first open todo, in list order, for each title, gathered in one
+        // pass rather than a `find_open_by_title` scan per line.
+        let mut open: HashMap<String, (u8, DateTime<Utc>, Uuid)> = HashMap::new();
+        if dup_check {
+            for (&id, data) in &self.todo_map {
+                if matches!(data.status, TodoStatus::Pending | TodoStatus::InProgress) {
+                    let order = list_order(id, data);
+                    open.entry(data.title.clone())
+                        .and_modify(|first| *first = (*first).min(order))
+                        .or_insert(order);
+                }
+            }
+        }
+
+        let count = entries.len();
+        for (title, priority, tags) in entries {
+            let duplicate = open.get(&title).map(|&(_, _, id)| id);
+            if let Some(duplicate) = duplicate {
+                writeln!(
+                    self.output.err(),
+                    "Warning: an open todo with this title already exists: {}",
+                    duplicate
                 )?;
             }
-            Some(("start", start_matches)) => {
-                let id = start_matches.get_one::<String>("id").unwrap();
-                self.start_todo(id)?;
+            let id = self.add(NewTodo {
+                priority,
+                tags,
+                ..NewTodo::new(title.clone())
+            })?;
+            if dup_check && duplicate.is_none() {
+                open.insert(title, list_order(id, &self.todo_map[&id]));
             }
-            Some(("complete", complete_matches)) => {
-                let id = complete_matches.get_one::<String>("id").unwrap();
-                self.complete_todo(id)?;
+        }
+        Ok(Outcome::BulkAdded(count))
+    }
+
+    /// Updates the given fields of a todo.
+    ///
+    /// The status flags are reversible. Passing `true` sets the matching
+    /// timestamp (keeping an existing one) and the status. Passing `false`
+    /// clears the matching timestamp and falls back to the status implied by
+    /// the timestamps that remain, so un-completing a started todo returns it
+    /// to InProgress and un-starting it returns it to Pending.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo, TodoData, TodoStatus};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-toggle-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// Cli::new(path.clone()).run_from(["toto", "add", "--title", "Paint the fence", "--priority", "1"])?;
+    /// let update = |flag: &str, value: &str| -> anyhow::Result<TodoData> {
+    ///     Cli::new(path.clone()).run_from(["toto", "update", "--id", "0", flag, value])?;
+    ///     let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    ///     Ok(todos[0].data.clone())
+    /// };
+    ///
+    /// let started = update("--in-progress", "true")?;
+    /// assert_eq!(started.status, TodoStatus::InProgress);
+    /// assert!(started.in_progress_at.is_some());
+    ///
+    /// let done = update("--completed", "true")?;
+    /// assert_eq!(done.status, TodoStatus::Completed);
+    /// assert!(done.completed_at.is_some());
+    ///
+    /// // Un-completing keeps the start, so the todo is back in progress.
+    /// let reopened = update("--completed", "false")?;
+    /// assert_eq!(reopened.status, TodoStatus::InProgress);
+    /// assert_eq!(reopened.completed_at, None);
+    /// assert_eq!(reopened.in_progress_at, started.in_progress_at);
+    ///
+    /// let pending = update("--in-progress", "false")?;
+    /// assert_eq!(pending.status, TodoStatus::Pending);
+    /// assert_eq!(pending.in_progress_at, None);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn update_todo(
+        &mut self,
+        id: &str,
+        title: Option<&String>,
+        description: Option<&String>,
+        priority: Option<&u8>,
+        in_progress: Option<&bool>,
+        completed: Option<&bool>,
+        deleted: Option<&bool>,
+        color: Option<Option<Color>>,
+        icon: Option<&String>,
+        due_at: Option<DateTime<Utc>>,
+    ) -> Result<Outcome> {
+        if let Some(title) = title {
+            self.check_title(title)?;
+        }
+        let todo_id = self.parse_todo_id(id)?;
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        if let Some(title) = title {
+            todo.title = title.clone();
+        }
+        if let Some(color) = color {
+            // `Some(None)` removes the current one.
+            todo.color = color;
+        }
+        if due_at.is_some() {
+            todo.due_at = due_at;
+        }
+        if let Some(icon) = icon {
+            // An empty icon removes the current one.
+            todo.icon = (!icon.is_empty()).then(|| icon.clone());
+        }
+        if let Some(description) = description {
+            // Only update description if some value is provided
+            todo.description = Some(description.clone());
+        }
+        if let Some(priority) = priority {
+            todo.priority = *priority;
+        }
+        match in_progress {
+            Some(true) => {
+                if todo.in_progress_at.is_none() {
+                    todo.in_progress_at = Some(Utc::now());
+                }
+                todo.status = TodoStatus::InProgress;
             }
-            Some(("delete", delete_matches)) => {
-                let id = delete_matches.get_one::<String>("id").unwrap();
-                self.delete_todo(id)?;
+            Some(false) => {
+                todo.in_progress_at = None;
+                todo.status = todo.status_from_timestamps();
+            }
+            None => {}
+        }
+        match completed {
+            Some(true) => {
+                if todo.completed_at.is_none() {
+                    todo.completed_at = Some(Utc::now());
+                }
+                todo.status = TodoStatus::Completed;
             }
-            Some(("sync", _)) => {
-                self.sync()?;
+            Some(false) => {
+                todo.completed_at = None;
+                todo.status = todo.status_from_timestamps();
             }
-            _ => {}
+            None => {}
+        }
+        match deleted {
+            Some(true) => {
+                if todo.deleted_at.is_none() {
+                    todo.deleted_at = Some(Utc::now());
+                }
+                todo.status = TodoStatus::Deleted;
+            }
+            Some(false) => {
+                todo.deleted_at = None;
+                todo.status = todo.status_from_timestamps();
+            }
+            None => {}
+        }
+        Ok(Outcome::Updated(todo_id))
+    }
+
+    fn start_todo(&mut self, id: &str) -> Result<Outcome> {
+        let todo_id = self.parse_todo_id(id)?;
+        self.start(todo_id)
+    }
+
+    pub fn start(&mut self, todo_id: Uuid) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
         };
-        println!("Saving...");
-        self.save_todos()?;
+        if todo.in_progress_at.is_none() {
+            todo.in_progress_at = Some(Utc::now());
+        }
+        todo.status = TodoStatus::InProgress;
+        Ok(Outcome::Started(todo_id))
+    }
+
+    /// Hides an open todo until `until`. Waking up after the due date would
+    /// put it straight into the overdue pile, so that is refused unless
+    /// `force` is set.
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-snooze-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| -> anyhow::Result<(String, String)> {
+    ///     let (out, err) = (Buffer::default(), Buffer::default());
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), err.clone()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok((out.contents(), err.contents()))
+    /// };
+    /// let id = "0";
+    /// let due = (chrono::Local::now() + chrono::Duration::days(2)).format("%Y-%m-%d %H:%M").to_string();
+    /// run(&["add", "--title", "Pay rent", "--priority", "1", "--due", &due])?;
+    /// let snoozed = || -> anyhow::Result<bool> {
+    ///     let mut cli = Cli::new(path.clone());
+    ///     cli.load()?;
+    ///     Ok(cli.todos()[0].data.snoozed_until.is_some())
+    /// };
+    ///
+    /// let (out, err) = run(&["snooze", "--id", id, "--for", "3d"])?;
+    /// assert_eq!(out, "");
+    /// assert!(err.starts_with("Warning: the todo is due "), "{}", err);
+    /// assert!(err.ends_with("Pass --force to snooze it anyway.\n"));
+    /// assert!(!snoozed()?);
+    ///
+    /// let (out, err) = run(&["snooze", "--id", id, "--for", "3d", "--force"])?;
+    /// assert!(out.starts_with("Snoozed until "), "{}", out);
+    /// assert_eq!(err, "");
+    /// assert!(snoozed()?);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn snooze(&mut self, todo_id: Uuid, until: DateTime<Utc>, force: bool) -> Result<Outcome> {
+        self.snooze_every(todo_id, until, None, force)
+    }
+
+    /// Like `snooze`, and with `every` set the todo is snoozed for that long
+    /// again whenever it comes back and is left alone, see `resnooze`. A
+    /// plain `snooze` ends that.
+    pub fn snooze_every(
+        &mut self,
+        todo_id: Uuid,
+        until: DateTime<Utc>,
+        every: Option<TimeDelta>,
+        force: bool,
+    ) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        if !matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress) {
+            bail!(
+                "Only open todos can be snoozed, this one is {:?}",
+                todo.status
+            );
+        }
+        if let Some(due_at) = todo.due_at
+            && until > due_at
+            && !force
+        {
+            return Ok(Outcome::SnoozedPastDue {
+                id: todo_id,
+                due_at,
+            });
+        }
+        todo.snoozed_until = Some(until);
+        todo.snooze_interval = every.map(|every| every.num_seconds());
+        todo.resnoozes = 0;
+        Ok(Outcome::Snoozed { id: todo_id, until })
+    }
+
+    /// Snoozes todos from `snooze --every` again once they have been back
+    /// for a whole interval without being touched, started or finished.
+    /// After `MAX_RESNOOZES` times in a row a todo is left in the list for
+    /// good; those are returned so they can be pointed out. Runs after every
+    /// load.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::cli::MAX_RESNOOZES;
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let now = Utc::now();
+    /// let day = TimeDelta::days(1);
+    /// let id = cli.add(NewTodo::new("Descale the kettle"))?;
+    /// cli.snooze_every(id, now + day, Some(day), false)?;
+    ///
+    /// // Back after a day, and still untouched a day later: snoozed for
+    /// // another day.
+    /// cli.resnooze(now + day + TimeDelta::hours(12));
+    /// assert_eq!(cli.todo(id).unwrap().data.snoozed_until, Some(now + day));
+    /// assert!(cli.resnooze(now + day * 2).is_empty());
+    /// let todo = cli.todo(id).unwrap().data;
+    /// assert_eq!(todo.snoozed_until, Some(now + day * 3));
+    /// assert_eq!(todo.resnoozes, 1);
+    ///
+    /// // Put off long enough, it stops being snoozed.
+    /// assert_eq!(cli.resnooze(now + day * 100), [id]);
+    /// let todo = cli.todo(id).unwrap().data;
+    /// assert_eq!(todo.resnoozes, MAX_RESNOOZES);
+    /// assert_eq!(todo.snooze_interval, None);
+    /// assert!(!todo.is_snoozed(now + day * 100));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn resnooze(&mut self, now: DateTime<Utc>) -> Vec<Uuid> {
+        let mut given_up = Vec::new();
+        for (&id, todo) in self.todo_map.iter_mut() {
+            let (Some(seconds), Some(back_at)) = (todo.snooze_interval, todo.snoozed_until) else {
+                continue;
+            };
+            let every = TimeDelta::seconds(seconds);
+            if now < back_at + every {
+                continue;
+            }
+            let acted_on = !matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress)
+                || [todo.last_touched_at, todo.in_progress_at]
+                    .into_iter()
+                    .flatten()
+                    .any(|at| at >= back_at);
+            if acted_on {
+                todo.snooze_interval = None;
+                todo.resnoozes = 0;
+                continue;
+            }
+            let mut until = back_at;
+            while now >= until + every {
+                if todo.resnoozes >= MAX_RESNOOZES {
+                    todo.snooze_interval = None;
+                    given_up.push(id);
+                    break;
+                }
+                until += every * 2;
+                todo.resnoozes += 1;
+            }
+            todo.snoozed_until = Some(until);
+        }
+        given_up
+    }
+
+    /// Changes only the priority of a todo.
+    ///
+    /// ```
+    /// use toto::cli::parse_priority;
+    /// use toto::{Cli, NewTodo, Outcome};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let id = cli.add(NewTodo::new("Triage inbox"))?;
+    /// let outcome = cli.set_priority(id, parse_priority("high").unwrap())?;
+    /// assert_eq!(outcome, Outcome::PriorityChanged { id, from: 255, to: 0 });
+    /// cli.set_priority(id, parse_priority("7").unwrap())?;
+    /// assert_eq!(cli.todo(id).unwrap().data.priority, 7);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_priority(&mut self, todo_id: Uuid, priority: u8) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        let from = std::mem::replace(&mut todo.priority, priority);
+        Ok(Outcome::PriorityChanged {
+            id: todo_id,
+            from,
+            to: priority,
+        })
+    }
+
+    /// Changes only the due date of a todo, or removes it with `None`.
+    ///
+    /// ```
+    /// use chrono::{Local, TimeZone};
+    /// use toto::time::parse_due_at;
+    /// use toto::{Cli, NewTodo, Outcome};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let id = cli.add(NewTodo::new("Return the library books"))?;
+    /// let wednesday = Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+    /// let friday = parse_due_at("friday", wednesday).unwrap();
+    /// let outcome = cli.set_due(id, Some(friday))?;
+    /// assert_eq!(outcome, Outcome::DueChanged { id, from: None, to: Some(friday) });
+    /// let stored = cli.todo(id).unwrap().data.due_at;
+    /// assert_eq!(stored, Some(Local.with_ymd_and_hms(2024, 5, 3, 0, 0, 0).unwrap().to_utc()));
+    ///
+    /// cli.set_due(id, None)?;
+    /// assert_eq!(cli.todo(id).unwrap().data.due_at, None);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_due(&mut self, todo_id: Uuid, due_at: Option<DateTime<Utc>>) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        let from = std::mem::replace(&mut todo.due_at, due_at);
+        Ok(Outcome::DueChanged {
+            id: todo_id,
+            from,
+            to: due_at,
+        })
+    }
+
+    /// Marks a todo as still relevant, so it stops counting as stale.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::{Cli, ListFilter, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let id = cli.add(NewTodo {
+    ///     created_at: Some(Utc::now() - TimeDelta::days(40)),
+    ///     ..NewTodo::new("Sort out the garage")
+    /// })?;
+    /// let stale = ListFilter {
+    ///     stale_after: Some(TimeDelta::days(30)),
+    ///     ..ListFilter::default()
+    /// };
+    /// assert!(stale.matches(&cli.todo(id).unwrap().data));
+    ///
+    /// cli.touch(id)?;
+    /// let todo = cli.todo(id).unwrap().data;
+    /// assert!(todo.last_touched_at.is_some());
+    /// assert!(!stale.matches(&todo));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn touch(&mut self, todo_id: Uuid) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        todo.last_touched_at = Some(Utc::now());
+        Ok(Outcome::Updated(todo_id))
+    }
+
+    fn reopen_todo(&mut self, id: &str) -> Result<Outcome> {
+        let todo_id = self.parse_todo_id(id)?;
+        self.reopen(todo_id)
+    }
+
+    /// Sends a completed todo back to InProgress for more work. Unlike
+    /// `update --completed false` it never lands on Pending: the original
+    /// start time is kept, or set now if the todo was never started.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo, TodoData, TodoStatus};
+    /// use toto::time::parse_timestamp;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-reopen-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// let add = |args: &[&str]| run(&[&["add", "--priority", "1"], args].concat());
+    /// add(&[
+    ///     "--title", "Write the report", "--at", "2024-03-01",
+    ///     "--in-progress", "--started-at", "2024-03-02", "--done",
+    /// ])?;
+    /// add(&["--title", "Tidy up", "--at", "2024-03-03", "--done"])?;
+    /// add(&["--title", "Read the feedback", "--at", "2024-03-04"])?;
+    /// let todo = |title: &str| -> anyhow::Result<TodoData> {
+    ///     let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    ///     Ok(todos.into_iter().find(|todo| todo.data.title == title).unwrap().data)
+    /// };
+    ///
+    /// run(&["reopen", "--id", "0"])?;
+    /// let report = todo("Write the report")?;
+    /// assert_eq!(report.status, TodoStatus::InProgress);
+    /// let started = parse_timestamp("2024-03-02").ok();
+    /// assert_eq!((report.in_progress_at, report.completed_at), (started, None));
+    ///
+    /// run(&["reopen", "--id", "1"])?;
+    /// assert!(todo("Tidy up")?.in_progress_at.is_some());
+    ///
+    /// let err = run(&["reopen", "--id", "2"]).unwrap_err();
+    /// assert_eq!(err.to_string(), "Only completed todos can be reopened, this one is Pending");
+    /// assert_eq!(todo("Read the feedback")?.status, TodoStatus::Pending);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn reopen(&mut self, todo_id: Uuid) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        if todo.status != TodoStatus::Completed {
+            bail!(
+                "Only completed todos can be reopened, this one is {:?}",
+                todo.status
+            );
+        }
+        todo.completed_at = None;
+        if todo.in_progress_at.is_none() {
+            todo.in_progress_at = Some(Utc::now());
+        }
+        todo.status = TodoStatus::InProgress;
+        Ok(Outcome::Reopened(todo_id))
+    }
+
+    fn complete_todo(
+        &mut self,
+        id: &str,
+        note: Option<&String>,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Outcome> {
+        let todo_id = self.parse_todo_id(id)?;
+        let note = note.map(String::as_str);
+        match at {
+            Some(at) => self.complete_at(todo_id, note, at),
+            None => self.complete(todo_id, note),
+        }
+    }
+
+    /// Changes the tags of every todo `selection` picks as `op` says and
+    /// returns the ids of those that changed, in the selection's order.
+    ///
+    /// ```
+    /// use toto::cli::TagOp;
+    /// use toto::{Cli, ListFilter, NewTodo, Selection, TodoStatus};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let old = |title| NewTodo { tags: vec![String::from("old")], ..NewTodo::new(title) };
+    /// let shelf = cli.add(old("Fix the shelf"))?;
+    /// let lamp = cli.add(old("Fix the lamp"))?;
+    /// let fence = cli.add(NewTodo { done: true, ..old("Paint the fence") })?;
+    ///
+    /// let pending = Selection {
+    ///     filter: ListFilter { statuses: vec![TodoStatus::Pending], ..ListFilter::default() },
+    ///     ..Selection::default()
+    /// };
+    /// assert_eq!(cli.retag(&pending, &TagOp::Add(String::from("Home"))), [shelf, lamp]);
+    /// assert!(cli.todo(fence).unwrap().data.tags == ["old"]);
+    ///
+    /// let tagged_old = Selection {
+    ///     filter: ListFilter { any_tags: vec![String::from("old")], ..ListFilter::default() },
+    ///     ..Selection::default()
+    /// };
+    /// assert_eq!(cli.retag(&tagged_old, &TagOp::Remove(String::from("home"))).len(), 2);
+    /// assert!(cli.todo(shelf).unwrap().data.tags == ["old"]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn retag(&mut self, selection: &Selection, op: &TagOp) -> Vec<Uuid> {
+        let mut changed = Vec::new();
+        for todo in self.list_filtered(selection) {
+            if let Some(tags) = op.changes(&todo.data.tags)
+                && let Some(data) = self.todo_map.get_mut(&todo.id)
+            {
+                data.tags = tags;
+                changed.push(todo.id);
+            }
+        }
+        changed
+    }
+
+    /// Completes every in-progress todo, in list order, and returns their
+    /// ids. Pending todos are left alone.
+    ///
+    /// ```
+    /// use toto::{Cli, NewTodo, TodoStatus};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let started = NewTodo { in_progress: true, ..NewTodo::new("Write tests") };
+    /// let started = cli.add(started)?;
+    /// let waiting = cli.add(NewTodo::new("Deploy"))?;
+    ///
+    /// assert_eq!(cli.complete_started(None)?, [started]);
+    /// let status = |id| cli.todo(id).unwrap().data.status;
+    /// assert_eq!(status(started), TodoStatus::Completed);
+    /// assert_eq!(status(waiting), TodoStatus::Pending);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn complete_started(&mut self, note: Option<&str>) -> Result<Vec<Uuid>> {
+        let started: Vec<Uuid> = self
+            .ordered_todos()
+            .into_iter()
+            .filter(|todo| todo.data.status == TodoStatus::InProgress)
+            .map(|todo| todo.id)
+            .collect();
+        for id in &started {
+            self.complete(*id, note)?;
+        }
+        Ok(started)
+    }
+
+    /// Marks a todo completed, attaching `note` if given. With
+    /// `auto_complete_parents` a parent left without open subtasks is
+    /// completed as well, and so on up the tree.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-note-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// run(&["add", "--title", "Fix the leak", "--priority", "1"])?;
+    /// run(&["complete", "--id", "0", "--note", "Replaced the washer"])?;
+    ///
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// let notes = &todos[0].data.notes;
+    /// assert_eq!(notes.len(), 1);
+    /// assert_eq!(notes[0].text, "Replaced the washer");
+    /// assert_eq!(Some(notes[0].created_at), todos[0].data.completed_at);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn complete(&mut self, todo_id: Uuid, note: Option<&str>) -> Result<Outcome> {
+        self.mark_completed(todo_id, note, Utc::now())
+    }
+
+    /// Like `complete`, but records the todo as completed at `at`, which
+    /// can't be in the future or before the todo was created or started.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let now = Utc::now();
+    /// let created = NewTodo { created_at: Some(now - TimeDelta::hours(3)), ..NewTodo::new("Fix CI") };
+    /// let id = cli.add(created)?;
+    ///
+    /// let err = cli.complete_at(id, None, now - TimeDelta::hours(4)).unwrap_err();
+    /// assert!(err.to_string().contains("before the todo was created"));
+    /// cli.complete_at(id, None, now - TimeDelta::hours(1))?;
+    /// assert_eq!(cli.todo(id).unwrap().data.completed_at, Some(now - TimeDelta::hours(1)));
+    ///
+    /// // Plain `complete` takes the current time as it is.
+    /// let ahead = cli.add(NewTodo { created_at: Some(now + TimeDelta::days(1)), ..NewTodo::new("Skew") })?;
+    /// cli.complete(ahead, None)?;
+    /// assert!(cli.todo(ahead).unwrap().data.completed_at.is_some());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn complete_at(
+        &mut self,
+        todo_id: Uuid,
+        note: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<Outcome> {
+        if let Some(todo) = self.todo_map.get(&todo_id)
+            && todo.completed_at.is_none()
+        {
+            check_backdate(todo, at)?;
+        }
+        self.mark_completed(todo_id, note, at)
+    }
+
+    // Completes a todo at `now` as given. Plain `complete` doesn't check it
+    // against the other timestamps, which may lie ahead of this clock when
+    // they were given with `add --at` or came from another machine.
+    fn mark_completed(
+        &mut self,
+        todo_id: Uuid,
+        note: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        if todo.completed_at.is_some() {
+            return Ok(Outcome::AlreadyCompleted(todo_id));
+        }
+        todo.completed_at = Some(now);
+        todo.status = TodoStatus::Completed;
+        if let Some(note) = note {
+            todo.notes.push(Note {
+                text: note.to_string(),
+                created_at: now,
+            });
+        }
+        let parents = if self.config.auto_complete_parents {
+            self.complete_finished_parents(todo_id, now)
+        } else {
+            Vec::new()
+        };
+        Ok(Outcome::Completed {
+            id: todo_id,
+            remaining: self.remaining(),
+            parents,
+        })
+    }
+
+    /// Completes the ancestors of `todo_id` whose subtasks are now all done,
+    /// stopping at the first that still has open ones. Deleted subtasks
+    /// don't count, as in the list's progress column.
+    ///
+    /// ```
+    /// use toto::{Cli, TodoStatus};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-cascade-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// let add = |id: &str, title: &str, parent: Option<&str>| -> anyhow::Result<()> {
+    ///     let id = uuid::Uuid::parse_str(id)?;
+    ///     let mut args = vec!["toto", "add", "--title", title, "--priority", "1"];
+    ///     args.extend(parent.map(|parent| ["--parent", parent]).into_iter().flatten());
+    ///     Cli::new(path.clone()).with_id_generator(move || id).run_from(args)
+    /// };
+    /// let status = |id: &str| -> anyhow::Result<TodoStatus> {
+    ///     let mut cli = Cli::new(path.clone());
+    ///     cli.load()?;
+    ///     Ok(cli.todo(uuid::Uuid::parse_str(id)?).unwrap().data.status)
+    /// };
+    /// let [moving, packing, books, plates] = [
+    ///     "a1000000-0000-4000-8000-000000000000",
+    ///     "b2000000-0000-4000-8000-000000000000",
+    ///     "c3000000-0000-4000-8000-000000000000",
+    ///     "d4000000-0000-4000-8000-000000000000",
+    /// ];
+    /// add(moving, "Move house", None)?;
+    /// add(packing, "Pack", Some(moving))?;
+    /// add(books, "Books", Some(packing))?;
+    /// add(plates, "Plates", Some(packing))?;
+    ///
+    /// run(&["complete", "--cascade-parent", "--id", books])?;
+    /// assert_eq!(status(packing)?, TodoStatus::Pending);
+    /// run(&["complete", "--cascade-parent", "--id", plates])?;
+    /// assert_eq!((status(packing)?, status(moving)?), (TodoStatus::Completed, TodoStatus::Completed));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn complete_finished_parents(&mut self, todo_id: Uuid, now: DateTime<Utc>) -> Vec<Uuid> {
+        let mut completed = Vec::new();
+        let mut current = self.todo_map.get(&todo_id).and_then(|todo| todo.parent);
+        while let Some(parent_id) = current {
+            let open = |todo: &TodoData| {
+                matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress)
+            };
+            let children_done = !self
+                .todo_map
+                .values()
+                .any(|todo| todo.parent == Some(parent_id) && open(todo));
+            let Some(parent) = self.todo_map.get_mut(&parent_id) else {
+                break;
+            };
+            if !open(parent) || !children_done {
+                break;
+            }
+            parent.completed_at = Some(now);
+            parent.status = TodoStatus::Completed;
+            completed.push(parent_id);
+            current = parent.parent;
+        }
+        completed
+    }
+
+    // Pending and in-progress todos, reported after a completion.
+    fn remaining(&self) -> usize {
+        self.todo_map
+            .values()
+            .filter(|todo| matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress))
+            .count()
+    }
+
+    fn delete_todo(
+        &mut self,
+        id: &str,
+        reason: Option<&String>,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Outcome> {
+        let todo_id = self.parse_todo_id(id)?;
+        let reason = reason.map(String::as_str);
+        match at {
+            Some(at) => self.delete_at(todo_id, reason, at),
+            None => self.delete(todo_id, reason),
+        }
+    }
+
+    /// Marks a todo deleted, recording `reason` as a note if given.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo, TodoStatus};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-reason-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// run(&["add", "--title", "Buy a kettle", "--priority", "1"])?;
+    /// run(&["delete", "--id", "0", "--reason", "Got one as a gift"])?;
+    ///
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// assert_eq!(todos[0].data.status, TodoStatus::Deleted);
+    /// let notes = &todos[0].data.notes;
+    /// assert_eq!(notes.len(), 1);
+    /// assert_eq!(notes[0].text, "Got one as a gift");
+    /// assert_eq!(Some(notes[0].created_at), todos[0].data.deleted_at);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn delete(&mut self, todo_id: Uuid, reason: Option<&str>) -> Result<Outcome> {
+        self.mark_deleted(todo_id, reason, Utc::now())
+    }
+
+    /// Like `delete`, but records the todo as deleted at `at`, checked the
+    /// same way as in `complete_at`.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::{Cli, NewTodo, TodoStatus};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let now = Utc::now();
+    /// let started = NewTodo {
+    ///     created_at: Some(now - TimeDelta::hours(3)),
+    ///     in_progress: true,
+    ///     started_at: Some(now - TimeDelta::hours(2)),
+    ///     ..NewTodo::new("Fix CI")
+    /// };
+    /// let id = cli.add(started)?;
+    ///
+    /// let err = cli.delete_at(id, None, now + TimeDelta::hours(1)).unwrap_err();
+    /// assert!(err.to_string().contains("is in the future"));
+    /// let err = cli.delete_at(id, None, now - TimeDelta::hours(3)).unwrap_err();
+    /// assert!(err.to_string().contains("before the todo was started"));
+    /// assert_eq!(cli.todo(id).unwrap().data.status, TodoStatus::InProgress);
+    ///
+    /// // Without a time there's nothing to check, even for a todo from a
+    /// // clock running ahead.
+    /// let ahead = cli.add(NewTodo { created_at: Some(now + TimeDelta::days(1)), ..NewTodo::new("Skew") })?;
+    /// cli.delete(ahead, None)?;
+    /// assert_eq!(cli.todo(ahead).unwrap().data.status, TodoStatus::Deleted);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn delete_at(
+        &mut self,
+        todo_id: Uuid,
+        reason: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<Outcome> {
+        if let Some(todo) = self.todo_map.get(&todo_id)
+            && todo.deleted_at.is_none()
+        {
+            check_backdate(todo, at)?;
+        }
+        self.mark_deleted(todo_id, reason, at)
+    }
+
+    // Deletes a todo at `now` as given, unchecked like `mark_completed`.
+    fn mark_deleted(
+        &mut self,
+        todo_id: Uuid,
+        reason: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Outcome> {
+        let Some(todo) = self.todo_map.get_mut(&todo_id) else {
+            return Ok(Outcome::NotFound);
+        };
+        if todo.deleted_at.is_some() {
+            return Ok(Outcome::AlreadyDeleted(todo_id));
+        }
+        todo.deleted_at = Some(now);
+        todo.status = TodoStatus::Deleted;
+        if let Some(reason) = reason {
+            todo.notes.push(Note {
+                text: reason.to_string(),
+                created_at: now,
+            });
+        }
+        Ok(Outcome::Deleted(todo_id))
+    }
+
+    /// Prints the selected todos and returns how many there were. With
+    /// `changed`, only those in it are printed, each flagged with its label.
+    fn list_todos(
+        &self,
+        layout: &Layout,
+        selection: &Selection,
+        window: Window,
+        verbose: bool,
+        pending_only: bool,
+        changed: Option<&HashMap<Uuid, &'static str>>,
+    ) -> Result<usize> {
+        self.id_base_notice()?;
+        let mut out = self.output.out();
+        if layout.borders.is_none() {
+            term::splash(&mut *out, layout)?;
+        }
+        let (positions, todos) = self.listed_todos(pending_only);
+        let progress = self.child_progress();
+        let mut rows = Vec::new();
+        for (index, todo) in selection.apply(&todos) {
+            let id = positions
+                .as_ref()
+                .map_or(index, |positions| positions[index]);
+            let flag = changed.map(|changed| changed.get(&todo.id).copied());
+            if flag == Some(None) {
+                continue;
+            }
+            rows.push(Row {
+                progress: progress.get(&todo.id).copied(),
+                flag: flag.flatten(),
+                ..Row::new(todo, Some(self.human_id(id)))
+            });
+        }
+        let (range, page) = window.bounds(rows.len());
+        let listed = range.len();
+        if let Some(borders) = layout.borders {
+            term::print_table(&mut *out, layout, verbose, &rows[range], borders)?;
+        } else {
+            for row in &rows[range] {
+                term::print_todo(&mut *out, layout, verbose, row)?;
+            }
+        }
+        if let Some((page, pages)) = page {
+            writeln!(out, "page {} of {}", page, pages)?;
+        }
+        Ok(listed)
+    }
+
+    /// Prints the todos `list` would show as JSON, returning how many.
+    fn list_json(
+        &self,
+        selection: &Selection,
+        window: Window,
+        pending_only: bool,
+        compact: bool,
+    ) -> Result<usize> {
+        let (_, todos) = self.listed_todos(pending_only);
+        let rows = selection.apply(&todos);
+        let (range, _) = window.bounds(rows.len());
+        let selected: Vec<Todo> = rows[range]
+            .iter()
+            .map(|(_, todo)| (*todo).clone())
+            .collect();
+        let json = export::to_json(&selected, compact).context("Failed to serialize todo list")?;
+        writeln!(self.output.out(), "{}", json)?;
+        Ok(selected.len())
+    }
+
+    /// Prints the todos `list` would show as `export::to_plain` records,
+    /// returning how many.
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-plain-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let out = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok(out.contents())
+    /// };
+    /// run(&["add", "--title", "Milk, eggs", "--priority", "1"])?;
+    /// let plain = run(&["list", "--plain", "--field-separator", ","])?;
+    /// assert!(plain.starts_with("0,") && plain.ends_with(",pending,1,Milk\\, eggs\n"), "{}", plain);
+    /// assert_eq!(run(&["list", "--ids-only", "--field-separator", ","])?.lines().count(), 1);
+    /// // It only separates the line formats for scripts.
+    /// assert!(run(&["list", "--field-separator", ","]).is_err());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn list_plain(
+        &self,
+        selection: &Selection,
+        window: Window,
+        pending_only: bool,
+        separator: &str,
+    ) -> Result<usize> {
+        let (positions, todos) = self.listed_todos(pending_only);
+        let rows: Vec<(usize, &Todo)> = selection
+            .apply(&todos)
+            .into_iter()
+            .map(|(index, todo)| {
+                let position = positions
+                    .as_ref()
+                    .map_or(index, |positions| positions[index]);
+                (self.human_id(position), todo)
+            })
+            .collect();
+        let (range, _) = window.bounds(rows.len());
+        write!(
+            self.output.out(),
+            "{}",
+            export::to_plain(&rows[range.clone()], separator)
+        )?;
+        Ok(range.len())
+    }
+
+    /// Prints the UUIDs of the todos `list` would show, returning how many.
+    fn list_ids(
+        &self,
+        selection: &Selection,
+        window: Window,
+        pending_only: bool,
+        separator: &str,
+    ) -> Result<usize> {
+        let (_, todos) = self.listed_todos(pending_only);
+        let rows = selection.apply(&todos);
+        let (range, _) = window.bounds(rows.len());
+        let selected: Vec<Todo> = rows[range]
+            .iter()
+            .map(|(_, todo)| (*todo).clone())
+            .collect();
+        write!(
+            self.output.out(),
+            "{}",
+            export::to_ids(&selected, separator)
+        )?;
+        Ok(selected.len())
+    }
+
+    /// The todos `list` starts from. With `pending_only` just the open ones
+    /// are taken, together with their positions among all todos, which the
+    /// human ids need.
+    fn listed_todos(&self, pending_only: bool) -> (Option<Vec<usize>>, Vec<Todo>) {
+        if pending_only {
+            let (positions, todos) = self.open_todos().into_iter().unzip();
+            (Some(positions), todos)
+        } else {
+            (None, self.ordered_todos())
+        }
+    }
+
+    /// Prints the `count` most urgent actionable todos.
+    fn top(&self, layout: &Layout, count: usize) -> Result<()> {
+        let todos = self.ordered_todos();
+        let progress = self.child_progress();
+        let selection = Selection {
+            filter: ListFilter {
+                actionable: true,
+                ..ListFilter::default()
+            },
+            sort: SortKey::Urgency,
+            urgency: self.config.urgency,
+            ..Selection::default()
+        };
+        let mut rows = selection.apply(&todos);
+        if rows.is_empty() {
+            writeln!(self.output.out(), "Nothing to do.")?;
+            return Ok(());
+        }
+        rows.truncate(count);
 
+        self.id_base_notice()?;
+        let mut out = self.output.out();
+        term::splash(&mut *out, layout)?;
+        for (id, todo) in rows {
+            let row = Row {
+                progress: progress.get(&todo.id).copied(),
+                ..Row::new(todo, Some(self.human_id(id)))
+            };
+            term::print_todo(&mut *out, layout, false, &row)?;
+        }
         Ok(())
     }
 
-    fn load_todos(&mut self) -> Result<()> {
-        let file_path = format!("{}/todos.json", self.file_path);
+    /// Shows `top` again every `interval` until interrupted, re-reading the
+    /// todo file each time. With `notify`, todos coming due or becoming
+    /// overdue pop up a desktop notification, once each.
+    ///
+    /// ```
+    /// use toto::Cli;
+    ///
+    /// let err = Cli::new(String::from(".")).run_from(["toto", "watch", "--interval", "0m"]).unwrap_err();
+    /// assert_eq!(err.to_string(), "The interval can't be zero");
+    /// ```
+    fn watch(&mut self, interval: TimeDelta, count: usize, notify: bool) -> Result<()> {
+        if notify && !remind::AVAILABLE {
+            bail!(
+                "This toto was built without desktop notifications, rebuild it with --features notify"
+            );
+        }
+        let interval = interval
+            .to_std()
+            .ok()
+            .filter(|interval| !interval.is_zero())
+            .context("The interval can't be zero")?;
+        let clear = io::stdout().is_terminal();
+        let mut reminders = Reminders::default();
+        loop {
+            self.todo_map.clear();
+            self.load_todos()?;
+            // Saved straight away, or every tick would snooze and start the
+            // same todos again and repeat the warnings.
+            let loaded = self.todo_map.clone();
+            self.catch_up()?;
+            if self.todo_map != loaded {
+                self.save()?;
+            }
+            if clear {
+                write!(self.output.out(), "\x1b[2J\x1b[H")?;
+            }
+            self.top(&self.default_layout(), count)?;
+            if notify {
+                let todos = self.ordered_todos();
+                let now = Utc::now();
+                for (reminder, todo) in reminders.due(&todos, now, self.config.notify_due_within) {
+                    // A notification daemon going away shouldn't stop the
+                    // list from refreshing.
+                    if let Err(err) = remind::notify(&reminder.message(&todo.data.title)) {
+                        writeln!(self.output.err(), "Warning: {:#}", err)?;
+                    }
+                }
+            }
+            std::thread::sleep(interval);
+        }
+    }
 
-        // Attempt to open the file create it if it doesn't exist
-        if let Ok(file) = OpenOptions::new()
-            // .create(true)
-            .read(true)
+    // The selection a view describes, with the settings it depends on.
+    fn selection(&self, view: &ListView) -> Selection {
+        Selection {
+            filter: ListFilter {
+                tz: self.config.tz,
+                ..view.filter()
+            },
+            sort: view.sort.unwrap_or_default(),
+            nulls: view.sort_nulls.unwrap_or_default(),
+            urgency: self.config.urgency,
+        }
+    }
+
+    // The table layout for commands without list's layout options.
+    fn default_layout(&self) -> Layout {
+        Layout {
+            ellipsis: self.config.ellipsis.clone(),
+            tiny_below: self.config.tiny_width,
+            dates: self.config.date_style(),
+            tag_colors: self.config.tag_colors.clone(),
+            ..Layout::default()
+        }
+    }
+
+    // The configured date style, relative near now with `--compact-dates`.
+    fn date_style(&self, compact: bool) -> DateStyle {
+        DateStyle {
+            relative_within: compact.then_some(self.config.compact_dates_within),
+            ..self.config.date_style()
+        }
+    }
+
+    /// Combines the options given to `list` with the view saved by earlier
+    /// runs, saving the result whenever an option was given explicitly.
+    ///
+    /// ```
+    /// use toto::cli::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-view-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let view = dir.join("view.json");
+    /// let list = |args: &[&str]| Cli::new(path.clone()).run_from(["toto", "list"].iter().chain(args));
+    /// let saved_sort = || -> anyhow::Result<serde_json::Value> {
+    ///     let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&view)?)?;
+    ///     Ok(saved["sort"].clone())
+    /// };
+    ///
+    /// list(&["--sort", "title"])?;
+    /// assert_eq!(saved_sort()?, "title");
+    /// // A plain `list` uses the saved view and leaves it as it is.
+    /// list(&[])?;
+    /// assert_eq!(saved_sort()?, "title");
+    /// list(&["--title-contains", "milk"])?;
+    /// assert_eq!(saved_sort()?, "title");
+    /// list(&["--reset-view"])?;
+    /// assert!(!view.exists());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn list_view(&self, list_matches: &ArgMatches) -> Result<ListView> {
+        let view_path = format!("{}/view.json", self.file_path);
+        let mut view = if list_matches.get_flag("reset-view") {
+            if let Err(err) = fs::remove_file(&view_path)
+                && err.kind() != ErrorKind::NotFound
+            {
+                return Err(err).context("Failed to remove saved view");
+            }
+            ListView::default()
+        } else {
+            match fs::read_to_string(&view_path) {
+                Ok(contents) => {
+                    serde_json::from_str(&contents).context("Failed to deserialize saved view")?
+                }
+                Err(_) => ListView::default(),
+            }
+        };
+
+        if !view.apply_matches(list_matches) {
+            if view != ListView::default() {
+                writeln!(
+                    self.output.err(),
+                    "Using the saved list view (clear it with --reset-view)."
+                )?;
+            }
+            return Ok(view);
+        }
+
+        let file = OpenOptions::new()
             .write(true)
-            .open(file_path)
-        {
-            println!("Opened or created");
+            .create(true)
+            .truncate(true)
+            .open(&view_path)
+            .context("Failed to open or create view file")?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &view)
+            .context("Failed to serialize view")?;
+        Ok(view)
+    }
 
-            let metadata = file.metadata().context("Failed to get file metadata")?;
+    /// Whether everything `todo` is blocked by has been completed. Blockers
+    /// that are no longer in the list have been archived, which only
+    /// happens to finished todos, so they count as done; deleted blockers
+    /// count too since they will never be completed.
+    fn blockers_done(&self, todo: &TodoData) -> bool {
+        !todo.is_blocked(|id| self.todo_map.get(id).map(|blocker| blocker.status.clone()))
+    }
 
-            // Early return if we created the file and it is empty.
-            if metadata.len() == 0 {
-                println!("returning early cuz empty");
-                return Ok(());
+    /// Starts pending todos whose blockers are all done, if they asked for
+    /// it with `--auto-start` or `auto_start_unblocked` is set. Runs after
+    /// every load so dependency chains move along without a separate step,
+    /// and says so on stderr to keep any command's output intact.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo, TodoStatus};
+    /// use toto::output::{Buffer, Output};
+    /// use uuid::Uuid;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-auto-start-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// let add = |id: &str, args: &[&str]| -> anyhow::Result<()> {
+    ///     let id = Uuid::parse_str(id)?;
+    ///     Cli::new(path.clone())
+    ///         .with_id_generator(move || id)
+    ///         .run_from([&["toto", "add", "--priority", "1"], args].concat())
+    /// };
+    /// let status = |id: &str| -> anyhow::Result<TodoStatus> {
+    ///     let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    ///     let id = Uuid::parse_str(id)?;
+    ///     Ok(todos.into_iter().find(|todo| todo.id == id).unwrap().data.status)
+    /// };
+    /// let [test, review, deploy, announce] = [
+    ///     "a1000000-0000-4000-8000-000000000000",
+    ///     "b2000000-0000-4000-8000-000000000000",
+    ///     "c3000000-0000-4000-8000-000000000000",
+    ///     "d4000000-0000-4000-8000-000000000000",
+    /// ];
+    /// add(test, &["--title", "Test"])?;
+    /// add(review, &["--title", "Review"])?;
+    /// let blocked = ["--blocked-by", test, "--blocked-by", review];
+    /// add(deploy, &[&["--title", "Deploy", "--auto-start"][..], &blocked].concat())?;
+    /// add(announce, &[&["--title", "Announce"][..], &blocked].concat())?;
+    ///
+    /// run(&["complete", "--id", test])?;
+    /// assert_eq!(status(deploy)?, TodoStatus::Pending);
+    /// run(&["complete", "--id", review])?;
+    /// let (out, err) = (Buffer::default(), Buffer::default());
+    /// Cli::new(path.clone())
+    ///     .with_output(Output::new(out.clone(), err.clone()))
+    ///     .run_from(["toto", "list", "--json"])?;
+    /// serde_json::from_str::<serde_json::Value>(&out.contents())?;
+    /// assert!(err.contents().contains("Started \"Deploy\", everything it waited on is done."));
+    /// assert_eq!(status(deploy)?, TodoStatus::InProgress);
+    /// assert_eq!(status(announce)?, TodoStatus::Pending);
+    ///
+    /// // With the config option every unblocked todo starts.
+    /// std::fs::write(dir.join("config.json"), r#"{"auto_start_unblocked": true}"#)?;
+    /// run(&["list"])?;
+    /// assert_eq!(status(announce)?, TodoStatus::InProgress);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn start_unblocked(&mut self) -> Result<()> {
+        let ready: Vec<Uuid> = self
+            .todo_map
+            .iter()
+            .filter(|(_, todo)| {
+                todo.status == TodoStatus::Pending
+                    && !todo.blocked_by.is_empty()
+                    && (todo.auto_start || self.config.auto_start_unblocked)
+                    && self.blockers_done(todo)
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        let now = Utc::now();
+        for id in ready {
+            if let Some(todo) = self.todo_map.get_mut(&id) {
+                todo.in_progress_at = Some(now);
+                todo.status = TodoStatus::InProgress;
+                writeln!(
+                    self.output.err(),
+                    "Started \"{}\", everything it waited on is done.",
+                    todo.title
+                )?;
             }
+        }
+        Ok(())
+    }
 
-            let reader = BufReader::new(file);
-            let todos: Vec<Todo> =
-                serde_json::from_reader(reader).context("Failed to deserialize todo list")?;
+    /// Counts completed and total children for every todo that has any.
+    fn child_progress(&self) -> HashMap<Uuid, (usize, usize)> {
+        count_children(self.todo_map.values())
+    }
 
-            for todo in todos {
-                println!("LOADING: Read and inserted a todo");
-                self.todo_map.insert(todo.id, todo.data);
+    /// Lists active and archived todos together ordered by creation date.
+    /// A todo present in both the active list and an archive is shown once,
+    /// using the active copy. Archived rows have no human id.
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-include-archived-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// // The passport was archived once done, then reopened.
+    /// let mut cli = Cli::new(path.clone());
+    /// let restored = cli.add(NewTodo { priority: 1, ..NewTodo::new("Passport") })?;
+    /// cli.complete(restored, None)?;
+    /// let mut old = Cli::new(path.clone());
+    /// let flights = old.add(NewTodo { priority: 1, done: true, ..NewTodo::new("Flights") })?;
+    /// let archived = vec![cli.todo(restored).unwrap(), old.todo(flights).unwrap()];
+    /// std::fs::write(dir.join("completed_20240101.json"), serde_json::to_string(&archived)?)?;
+    /// cli.reopen(restored)?;
+    /// cli.save()?;
+    ///
+    /// let out = Buffer::default();
+    /// Cli::new(path.clone())
+    ///     .with_output(Output::new(out.clone(), std::io::sink()))
+    ///     .run_from(["toto", "list", "--include-archived"])?;
+    /// let out = out.contents();
+    /// let passport: Vec<&str> = out.lines().filter(|line| line.contains("Passport")).collect();
+    /// assert_eq!(passport.len(), 1, "{}", out);
+    /// assert!(passport[0].ends_with("| I"), "{}", out);
+    /// let flights: Vec<&str> = out.lines().filter(|line| line.contains("Flights")).collect();
+    /// assert_eq!(flights.len(), 1, "{}", out);
+    /// // Only archived, so it has no row number.
+    /// assert!(flights[0].trim_start().starts_with("- "), "{}", out);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn list_flat_archived(
+        &self,
+        layout: &Layout,
+        filter: &ListFilter,
+        verbose: bool,
+    ) -> Result<usize> {
+        let rows: Vec<(Option<usize>, Todo)> =
+            merge::with_archived(self.ordered_todos(), self.load_archived_todos()?)
+                .into_iter()
+                .filter(|(_, todo)| filter.matches(&todo.data))
+                .map(|(position, todo)| (position.map(|index| self.human_id(index)), todo))
+                .collect();
+
+        let progress = self.child_progress();
+        self.id_base_notice()?;
+        let mut out = self.output.out();
+        term::splash(&mut *out, layout)?;
+        for (id, todo) in rows.iter() {
+            let row = Row {
+                progress: progress.get(&todo.id).copied(),
+                ..Row::new(todo, *id)
+            };
+            term::print_todo(&mut *out, layout, verbose, &row)?;
+        }
+        Ok(rows.len())
+    }
+
+    /// Paths of every `completed_*.json` archive in the data directory, oldest first.
+    fn archive_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(&self.file_path)
+            .with_context(|| format!("Failed to read directory {}", self.file_path))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("completed_") && name.ends_with(".json"))
+            })
+            .collect();
+        // Archive names embed the date as YYYYMMDD so lexical order is chronological.
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Reads every `completed_*.json` archive in the data directory, oldest first.
+    fn load_archived_todos(&self) -> Result<Vec<Todo>> {
+        let mut archived = Vec::new();
+        for path in self.archive_paths()? {
+            archived.extend(read_todos(&path)?);
+        }
+        Ok(archived)
+    }
+
+    /// Deletes every todo a sweep rule matches, noting the rule on it, and
+    /// reports each one. With `dry_run` nothing changes.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::output::{Buffer, Output};
+    /// use toto::{Cli, NewTodo, Todo, TodoStatus};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-sweep-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let long_ago = Some(Utc::now() - TimeDelta::days(120));
+    /// let maybe = vec![String::from("maybe")];
+    /// let mut cli = Cli::new(path.clone());
+    /// let learn = cli.add(NewTodo { created_at: long_ago, tags: maybe.clone(), ..NewTodo::new("Learn Go") })?;
+    /// cli.add(NewTodo { tags: maybe.clone(), ..NewTodo::new("Paint the hall") })?;
+    /// cli.add(NewTodo { created_at: long_ago, ..NewTodo::new("Pay rent") })?;
+    /// cli.add(NewTodo { created_at: long_ago, tags: maybe, done: true, ..NewTodo::new("Learn Rust") })?;
+    /// cli.save()?;
+    /// std::fs::write(
+    ///     dir.join("config.json"),
+    ///     r#"{"sweep_rules": [{"tags": ["maybe"], "older_than_days": 90}]}"#,
+    /// )?;
+    /// let sweep = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let out = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from([&["toto", "sweep"], args].concat())?;
+    ///     Ok(out.contents())
+    /// };
+    /// let read = || -> anyhow::Result<Vec<Todo>> {
+    ///     let mut todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    ///     todos.sort_by_key(|todo| todo.id);
+    ///     Ok(todos)
+    /// };
+    /// let before = read()?;
+    /// let file = std::fs::read(dir.join("todos.json"))?;
+    ///
+    /// assert_eq!(sweep(&["--dry-run"])?, "Would delete \"Learn Go\" (rule 1)\n");
+    /// assert_eq!(std::fs::read(dir.join("todos.json"))?, file);
+    ///
+    /// assert_eq!(sweep(&[])?, "Deleted \"Learn Go\" (rule 1)\n");
+    /// let after = read()?;
+    /// for (old, new) in before.iter().zip(&after) {
+    ///     if new.id == learn {
+    ///         assert_eq!(new.data.status, TodoStatus::Deleted);
+    ///         assert_eq!(new.data.notes.last().unwrap().text, "Swept by rule 1");
+    ///     } else {
+    ///         assert_eq!(new.data, old.data);
+    ///     }
+    /// }
+    /// assert_eq!(sweep(&[])?, "Nothing to sweep.\n");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn sweep(&mut self, dry_run: bool) -> Result<()> {
+        if self.config.sweep_rules.is_empty() {
+            writeln!(
+                self.output.out(),
+                "No sweep rules, add sweep_rules to {}.",
+                config::CONFIG_FILE
+            )?;
+            return Ok(());
+        }
+        let now = Utc::now();
+        let swept: Vec<(Uuid, usize)> = self
+            .ordered_todos()
+            .iter()
+            .filter_map(|todo| {
+                let rule = self
+                    .config
+                    .sweep_rules
+                    .iter()
+                    .position(|rule| rule.matches(&todo.data, now))?;
+                Some((todo.id, rule + 1))
+            })
+            .collect();
+        if swept.is_empty() {
+            writeln!(self.output.out(), "Nothing to sweep.")?;
+        }
+        for (id, rule) in swept {
+            let todo = self.todo_map.get_mut(&id).unwrap();
+            if dry_run {
+                writeln!(
+                    self.output.out(),
+                    "Would delete \"{}\" (rule {})",
+                    todo.title,
+                    rule
+                )?;
+                continue;
             }
+            todo.deleted_at = Some(now);
+            todo.status = TodoStatus::Deleted;
+            todo.notes.push(Note {
+                text: format!("Swept by rule {}", rule),
+                created_at: now,
+            });
+            writeln!(
+                self.output.out(),
+                "Deleted \"{}\" (rule {})",
+                todo.title,
+                rule
+            )?;
         }
-
         Ok(())
     }
 
-    fn save_todos(&self) -> Result<()> {
-        let file_path = format!("{}/todos.json", self.file_path);
-
-        println!("Saving to {}", file_path);
-        // Attempt to open the file create it if it doesn't exist
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(file_path)
-            .context("Failed to open or create todo file")?;
+    /// Compresses the priorities in use into 1, 2, 3... preserving their
+    /// relative order. The default priority of 255 means "unprioritised" and
+    /// is left alone.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-normalize-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// for (title, priority) in [("Urgent", "3"), ("Soon", "40"), ("Also soon", "40"), ("Whenever", "255")] {
+    ///     run(&["add", "--title", title, "--priority", priority])?;
+    /// }
+    /// run(&["normalize-priorities"])?;
+    ///
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// let mut priorities: Vec<u8> = todos.iter().map(|todo| todo.data.priority).collect();
+    /// priorities.sort();
+    /// assert_eq!(priorities, [1, 2, 2, 255]);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// With every priority from 0 to 254 in use there is no room below 255,
+    /// so it fails without renumbering anything:
+    ///
+    /// ```
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-normalize-full-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let mut cli = Cli::new(path.clone());
+    /// for priority in 0..255 {
+    ///     cli.add(NewTodo { priority, ..NewTodo::new(format!("Chore {}", priority)) })?;
+    /// }
+    /// cli.save()?;
+    /// let before = std::fs::read(dir.join("todos.json"))?;
+    /// let err = Cli::new(path).run_from(["toto", "normalize-priorities"]).unwrap_err();
+    /// assert!(err.to_string().starts_with("255 priorities are in use"), "{}", err);
+    /// assert_eq!(std::fs::read(dir.join("todos.json"))?, before);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn normalize_priorities(&mut self) -> Result<Outcome> {
+        let mut used: Vec<u8> = self
+            .todo_map
+            .values()
+            .map(|todo| todo.priority)
+            .filter(|&priority| priority != u8::MAX)
+            .collect();
+        used.sort_unstable();
+        used.dedup();
 
-        let writer = BufWriter::new(file);
+        // Numbering from 1 fits 254 of them below the unprioritised 255.
+        if used.len() > usize::from(u8::MAX - 1) {
+            bail!(
+                "{} priorities are in use, too many to renumber from 1 below the unprioritised 255",
+                used.len()
+            );
+        }
+        let remap: HashMap<u8, u8> = used
+            .iter()
+            .enumerate()
+            .map(|(index, &old)| (old, index as u8 + 1))
+            .collect();
+        for todo in self.todo_map.values_mut() {
+            if let Some(&new) = remap.get(&todo.priority) {
+                todo.priority = new;
+            }
+        }
 
-        let todos: Vec<Todo> = self
-            .todo_map
+        let changed: Vec<(u8, u8)> = used
             .iter()
-            .map(|(&id, data)| Todo {
-                id,
-                data: data.clone(),
-            })
+            .map(|old| (*old, remap[old]))
+            .filter(|(old, new)| old != new)
             .collect();
+        Ok(Outcome::PrioritiesNormalized(changed))
+    }
 
-        serde_json::to_writer_pretty(writer, &todos).context("Failed to serialize todo list")?;
+    /// Writes the todos `selection` picks, in its order, to `output` or
+    /// stdout.
+    ///
+    /// ```
+    /// use toto::cli::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-export-filter-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// let add = |args: &[&str]| run(&[&["add", "--priority", "1"], args].concat());
+    /// add(&["--tag", "shop", "--title", "Milk"])?;
+    /// add(&["--tag", "shop", "--title", "Eggs", "--done"])?;
+    /// add(&["--title", "Taxes"])?;
+    /// add(&["--tag", "shop", "--title", "Bread"])?;
+    ///
+    /// let file = dir.join("export.org").to_string_lossy().into_owned();
+    /// let options = ["--tag", "shop", "--status", "pending", "--sort", "title"];
+    /// run(&[&["export", "-f", "org", "-o", file.as_str()][..], &options].concat())?;
+    /// let exported = std::fs::read_to_string(&file)?;
+    /// let headlines: Vec<&str> = exported.lines().filter(|line| line.starts_with('*')).collect();
+    /// assert_eq!(headlines, ["* TODO [#A] Bread :shop:", "* TODO [#A] Milk :shop:"]);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn export(
+        &self,
+        format: ExportFormat,
+        flatten: bool,
+        gzip: bool,
+        selection: &Selection,
+        output: Option<&PathBuf>,
+    ) -> Result<()> {
+        if flatten && format != ExportFormat::Json {
+            bail!("--flatten only applies to --format json");
+        }
+        if gzip && format != ExportFormat::Json {
+            bail!("--gzip only applies to --format json");
+        }
+        if gzip && !gzip::AVAILABLE {
+            bail!("This toto was built without gzip support, rebuild it with --features gzip");
+        }
+        if gzip && output.is_none() && io::stdout().is_terminal() {
+            bail!("Refusing to write gzip to a terminal, pass -o or redirect stdout");
+        }
+        let todos = self.ordered_todos();
+        let selected: Vec<Todo> = selection
+            .apply(&todos)
+            .into_iter()
+            .map(|(_, todo)| todo.clone())
+            .collect();
+        let rendered = if flatten {
+            export::to_flat_json(&selected, Utc::now())
+        } else {
+            export::render(format, &selected, &self.config.date_style())
+        };
+        let rendered = if gzip {
+            gzip::compress(rendered.as_bytes())?
+        } else {
+            rendered.into_bytes()
+        };
+        match output {
+            Some(path) => fs::write(path, rendered)
+                .with_context(|| format!("Failed to write export to {}", path.display()))?,
+            None => self.output.out().write_all(&rendered)?,
+        }
         Ok(())
     }
 
-    fn add_todo(
-        &mut self,
-        title: &String,
-        description: Option<&String>,
-        priority: &u8,
-        in_progress: &bool,
-    ) {
-        let id = Uuid::new_v4();
-        let todo = Todo {
-            id,
-            data: TodoData {
-                title: title.clone(),
-                description: description.map(|s| s.clone()),
-                priority: *priority,
-                in_progress_at: if *in_progress { Some(Utc::now()) } else { None },
-                created_at: Utc::now(),
-                completed_at: None,
-                deleted_at: None,
-                status: if *in_progress {
-                    TodoStatus::InProgress
-                } else {
-                    TodoStatus::Pending
-                },
-            },
-        };
-        self.todo_map.insert(id, todo.data);
+    /// Deletes archive files that hold no todos, either because they are
+    /// empty or contain an empty list. Archives that fail to parse are left
+    /// alone so nothing is lost. Returns the removed paths.
+    ///
+    /// ```
+    /// use toto::cli::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-prune-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let kept = serde_json::json!([{
+    ///     "id": uuid::Uuid::from_u128(1),
+    ///     "data": {
+    ///         "title": "Kept", "description": "", "priority": 1, "status": "Completed",
+    ///         "created_at": 0, "in_progress_at": null, "completed_at": 0, "deleted_at": null,
+    ///     },
+    /// }]);
+    /// let archives = [
+    ///     ("completed_20240101.json", String::new()),
+    ///     ("completed_20240102.json", String::from(" [ ]\n")),
+    ///     ("completed_20240103.json", kept.to_string()),
+    ///     ("completed_20240104.json", String::from("not json")),
+    /// ];
+    /// for (name, contents) in &archives {
+    ///     std::fs::write(dir.join(name), contents)?;
+    /// }
+    ///
+    /// Cli::new(path).run_from(["toto", "sync", "--archive-only", "--prune-empty-archives"])?;
+    /// let exists = |name: &str| dir.join(name).exists();
+    /// assert!(!exists("completed_20240101.json") && !exists("completed_20240102.json"));
+    /// assert!(exists("completed_20240103.json") && exists("completed_20240104.json"));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn prune_empty_archives(&self) -> Result<Vec<PathBuf>> {
+        let mut pruned = Vec::new();
+        for path in self.archive_paths()? {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read archive {}", path.display()))?;
+            let empty = contents.trim().is_empty()
+                || serde_json::from_str::<Vec<Value>>(&contents)
+                    .is_ok_and(|todos| todos.is_empty());
+            if empty {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove archive {}", path.display()))?;
+                pruned.push(path);
+            }
+        }
+        Ok(pruned)
     }
-    fn update_todo(
+
+    /// Archives finished todos and commits and pushes the data directory.
+    /// Either half can run on its own. With `pull`, what other machines
+    /// pushed is pulled and merged in first, see `pull`.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use std::process::Command;
+    /// use toto::cli::SyncMode;
+    /// use toto::merge::Strategy;
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// # let base = std::env::temp_dir().join(format!("toto-doc-pull-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&base);
+    /// # std::fs::create_dir_all(&base)?;
+    /// let git = |dir: &Path, args: &[&str]| -> anyhow::Result<()> {
+    ///     let status = Command::new("git").arg("-C").arg(dir).args(args).output()?.status;
+    ///     anyhow::ensure!(status.success(), "git {:?} failed", args);
+    ///     Ok(())
+    /// };
+    /// // Two machines sharing one remote.
+    /// git(&base, &["init", "-q", "--bare", "remote.git"])?;
+    /// for machine in ["laptop", "desktop"] {
+    ///     git(&base, &["clone", "-q", "remote.git", machine])?;
+    ///     git(&base.join(machine), &["config", "user.name", "Toto"])?;
+    ///     git(&base.join(machine), &["config", "user.email", "toto@example.com"])?;
+    /// }
+    /// let open = |machine: &str| -> anyhow::Result<Cli> {
+    ///     let mut cli = Cli::new(base.join(machine).to_string_lossy().into_owned());
+    ///     cli.load()?;
+    ///     Ok(cli)
+    /// };
+    /// let titles = |cli: &Cli| -> Vec<String> {
+    ///     cli.todos().into_iter().map(|todo| todo.data.title).collect()
+    /// };
+    ///
+    /// let mut laptop = open("laptop")?;
+    /// laptop.add(NewTodo::new("Book the train"))?;
+    /// laptop.save()?;
+    /// laptop.sync(SyncMode::GitOnly, false, false, None)?;
+    ///
+    /// let mut desktop = open("desktop")?;
+    /// desktop.add(NewTodo::new("Call the plumber"))?;
+    /// desktop.sync(SyncMode::GitOnly, false, false, Some(Strategy::NewestWins))?;
+    /// assert_eq!(titles(&desktop).len(), 2);
+    ///
+    /// let mut laptop = open("laptop")?;
+    /// laptop.sync(SyncMode::GitOnly, false, false, Some(Strategy::NewestWins))?;
+    /// assert!(titles(&laptop).contains(&String::from("Call the plumber")));
+    ///
+    /// // The desktop finishes both and archives them. The laptop finished
+    /// // the train too, but its pull drops both instead of archiving them
+    /// // again.
+    /// let id_of = |cli: &Cli, title: &str| cli.todos().into_iter().find(|todo| todo.data.title == title).unwrap().id;
+    /// let mut desktop = open("desktop")?;
+    /// desktop.sync(SyncMode::GitOnly, false, false, Some(Strategy::NewestWins))?;
+    /// let (train, plumber) = (id_of(&desktop, "Book the train"), id_of(&desktop, "Call the plumber"));
+    /// desktop.complete(train, None)?;
+    /// desktop.complete(plumber, None)?;
+    /// desktop.sync(SyncMode::Both, false, false, None)?;
+    ///
+    /// let mut laptop = open("laptop")?;
+    /// laptop.complete(train, None)?;
+    /// laptop.sync(SyncMode::Both, false, false, Some(Strategy::NewestWins))?;
+    /// assert!(titles(&laptop).is_empty());
+    /// let archive = std::fs::read_dir(base.join("laptop"))?
+    ///     .map(|entry| entry.unwrap().path())
+    ///     .find(|path| path.file_name().unwrap().to_string_lossy().starts_with("completed_"))
+    ///     .unwrap();
+    /// let archived: Vec<toto::Todo> = serde_json::from_slice(&std::fs::read(archive)?)?;
+    /// let mut ids: Vec<_> = archived.iter().map(|todo| todo.id).collect();
+    /// ids.sort();
+    /// let mut expected = vec![train, plumber];
+    /// expected.sort();
+    /// assert_eq!(ids, expected);
+    /// # std::fs::remove_dir_all(&base)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn sync(
         &mut self,
-        id: &String,
-        title: Option<&String>,
-        description: Option<&String>,
-        priority: Option<&u8>,
-        in_progress: Option<&bool>,
-        completed: Option<&bool>,
-        deleted: Option<&bool>,
+        mode: SyncMode,
+        prune_empty_archives: bool,
+        sign: bool,
+        pull: Option<Strategy>,
     ) -> Result<()> {
-        if let Ok(todo_id) = self.parse_todo_id(id) {
-            if let Some(todo) = self.todo_map.get_mut(&todo_id) {
-                if let Some(title) = title {
-                    todo.title = title.clone();
-                }
-                if let Some(description) = description {
-                    // Only update description if some value is provided
-                    todo.description = Some(description.clone());
-                }
-                if let Some(priority) = priority {
-                    todo.priority = *priority;
-                }
-                if let Some(in_progress) = in_progress {
-                    if *in_progress {
-                        if todo.in_progress_at.is_none() {
-                            todo.in_progress_at = Some(Utc::now());
-                        }
-                        todo.status = TodoStatus::InProgress;
-                    }
-                }
-                if let Some(completed) = completed {
-                    if *completed {
-                        if todo.completed_at.is_none() {
-                            todo.completed_at = Some(Utc::now());
-                        }
-                        todo.status = TodoStatus::Completed;
-                    }
-                }
-                if let Some(deleted) = deleted {
-                    if *deleted {
-                        if todo.deleted_at.is_none() {
-                            todo.completed_at = Some(Utc::now());
-                        }
-                        todo.status = TodoStatus::Deleted;
-                    }
-                }
-            } else {
-                println!("Todo not found");
-            }
+        if let Some(strategy) = pull {
+            self.pull(strategy)?;
         }
-        Ok(())
-    }
 
-    fn start_todo(&mut self, id: &String) -> Result<()> {
-        if let Ok(todo_id) = self.parse_todo_id(id) {
-            if let Some(todo) = self.todo_map.get_mut(&todo_id) {
-                if todo.in_progress_at.is_none() {
-                    todo.in_progress_at = Some(Utc::now());
-                }
-                todo.status = TodoStatus::InProgress;
-            } else {
-                println!("Todo not found");
+        if prune_empty_archives {
+            let pruned = self.prune_empty_archives()?;
+            for path in &pruned {
+                writeln!(self.output.out(), "Pruned empty archive {}", path.display())?;
+            }
+            if pruned.is_empty() {
+                writeln!(self.output.out(), "No empty archives to prune.")?;
             }
         }
-        Ok(())
-    }
-    fn complete_todo(&mut self, id: &String) -> Result<()> {
-        if let Ok(todo_id) = self.parse_todo_id(id) {
-            if let Some(todo) = self.todo_map.get_mut(&todo_id) {
-                if todo.completed_at.is_none() {
-                    todo.completed_at = Some(Utc::now());
-                    todo.status = TodoStatus::Completed;
-                } else {
-                    println!("Todo is already completed");
-                }
+
+        if mode != SyncMode::GitOnly {
+            let archived = self.archive_finished()?;
+            if archived == 0 {
+                writeln!(
+                    self.output.out(),
+                    "No completed or deleted todos to archive."
+                )?;
             } else {
-                eprintln!("Todo not found");
+                writeln!(self.output.out(), "Archived {} todo(s).", archived)?;
             }
+            // Write the trimmed list now so the commit below includes it.
+            self.save_todos()?;
+        }
+
+        if mode != SyncMode::ArchiveOnly {
+            let date_str = Utc::now().format("%Y%m%d").to_string();
+            self.git_commit_and_push(&format!("archive {}", date_str), sign)?;
         }
         Ok(())
     }
 
-    fn delete_todo(&mut self, id: &String) -> Result<()> {
-        if let Ok(todo_id) = self.parse_todo_id(id) {
-            if let Some(todo) = self.todo_map.get_mut(&todo_id) {
-                if todo.deleted_at.is_none() {
-                    todo.deleted_at = Some(Utc::now());
-                    todo.status = TodoStatus::Deleted;
-                } else {
-                    println!("Todo is already deleted");
-                }
-            } else {
-                eprintln!("Todo not found");
+    /// Runs `git pull --rebase` and merges the pulled todos into the ones in
+    /// memory, keeping by `strategy` whichever copy of a todo changed on
+    /// both sides. The todo file is set back to its last commit first so
+    /// only commits are rebased; if the pull fails the local todos are
+    /// written back and nothing else happens.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use std::process::Command;
+    /// use toto::cli::SyncMode;
+    /// use toto::merge::Strategy;
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// # let base = std::env::temp_dir().join(format!("toto-doc-pull-abort-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&base);
+    /// # std::fs::create_dir_all(&base)?;
+    /// let git = |dir: &Path, args: &[&str]| -> anyhow::Result<String> {
+    ///     let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    ///     anyhow::ensure!(output.status.success(), "git {:?} failed", args);
+    ///     Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    /// };
+    /// git(&base, &["init", "-q", "--bare", "remote.git"])?;
+    /// git(&base, &["clone", "-q", "remote.git", "home"])?;
+    /// let home = base.join("home");
+    /// git(&home, &["config", "user.name", "Toto"])?;
+    /// git(&home, &["config", "user.email", "toto@example.com"])?;
+    /// let path = home.to_string_lossy().into_owned();
+    ///
+    /// let mut cli = Cli::new(path.clone());
+    /// cli.add(NewTodo::new("Plan the trip"))?;
+    /// cli.save()?;
+    /// cli.sync(SyncMode::GitOnly, false, false, None)?;
+    ///
+    /// // The remote has gone away, so the pull fails.
+    /// git(&home, &["remote", "set-url", "origin", "../missing.git"])?;
+    /// let mut cli = Cli::new(path.clone());
+    /// cli.load()?;
+    /// cli.add(NewTodo { done: true, ..NewTodo::new("Pay the deposit") })?;
+    /// let err = cli.sync(SyncMode::Both, false, false, Some(Strategy::NewestWins)).unwrap_err();
+    /// assert_eq!(err.to_string(), "Pull failed, nothing was archived or committed");
+    ///
+    /// let mut reloaded = Cli::new(path);
+    /// reloaded.load()?;
+    /// assert_eq!(reloaded.todos().len(), 2);
+    /// assert_eq!(git(&home, &["rev-list", "--all", "--count"])?, "1");
+    /// assert!(!std::fs::read_dir(&home)?.any(|e| e.unwrap().file_name().to_string_lossy().starts_with("completed_")));
+    /// # std::fs::remove_dir_all(&base)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// Other local changes are stashed for the pull and put back after it.
+    /// If they clash with what was pulled, git keeps them in the stash and
+    /// still reports success, so that is checked for and nothing is
+    /// committed on top.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use std::process::Command;
+    /// use toto::Cli;
+    /// use toto::output::Output;
+    ///
+    /// # let base = std::env::temp_dir().join(format!("toto-doc-pull-stash-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&base);
+    /// # std::fs::create_dir_all(&base)?;
+    /// let git = |dir: &Path, args: &[&str]| -> anyhow::Result<String> {
+    ///     let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    ///     anyhow::ensure!(output.status.success(), "git {:?} failed", args);
+    ///     Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    /// };
+    /// git(&base, &["init", "-q", "--bare", "remote.git"])?;
+    /// for clone in ["home", "work"] {
+    ///     git(&base, &["clone", "-q", "remote.git", clone])?;
+    ///     git(&base.join(clone), &["config", "user.name", "Toto"])?;
+    ///     git(&base.join(clone), &["config", "user.email", "toto@example.com"])?;
+    /// }
+    /// let (home, work) = (base.join("home"), base.join("work"));
+    /// let run = |dir: &Path, args: &[&str]| {
+    ///     Cli::new(dir.to_string_lossy().into_owned())
+    ///         .with_output(Output::new(std::io::sink(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))
+    /// };
+    ///
+    /// // Notes kept next to the todos, committed by hand.
+    /// std::fs::write(home.join("notes.md"), "movers: call Monday\n")?;
+    /// git(&home, &["add", "notes.md"])?;
+    /// git(&home, &["commit", "-q", "-m", "notes"])?;
+    /// run(&home, &["add", "Pack the boxes"])?;
+    /// run(&home, &["sync"])?;
+    /// git(&work, &["pull", "-q"])?;
+    ///
+    /// std::fs::write(home.join("notes.md"), "movers: call Tuesday\n")?;
+    /// git(&home, &["commit", "-q", "-am", "notes"])?;
+    /// git(&home, &["push", "-q"])?;
+    /// std::fs::write(work.join("notes.md"), "movers: booked\n")?;
+    /// run(&work, &["add", "Book the van"])?;
+    ///
+    /// let head = git(&work, &["rev-parse", "HEAD"])?;
+    /// let err = run(&work, &["sync", "--pull"]).unwrap_err();
+    /// assert!(err.to_string().contains("kept in git's stash"), "{}", err);
+    /// assert_eq!(git(&work, &["stash", "list"])?.lines().count(), 1);
+    /// // The pulled commit is there, but nothing was committed on top.
+    /// assert_eq!(git(&work, &["rev-parse", "HEAD"])?, git(&home, &["rev-parse", "HEAD"])?);
+    /// assert_ne!(git(&work, &["rev-parse", "HEAD"])?, head);
+    /// let mut cli = Cli::new(work.to_string_lossy().into_owned());
+    /// cli.load()?;
+    /// assert_eq!(cli.todos().len(), 2);
+    /// # std::fs::remove_dir_all(&base)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn pull(&mut self, strategy: Strategy) -> Result<()> {
+        let tracked = self.git(&["ls-files", "todos.json", "todos.json.sha256"])?;
+        let tracked: Vec<&str> = tracked.lines().collect();
+        if !tracked.is_empty() {
+            let mut args = vec!["checkout", "--"];
+            args.extend(&tracked);
+            self.git(&args)?;
+        }
+        let before = self.committed_todos()?.unwrap_or_default();
+        let stash = self.stash_head();
+        if let Err(err) = self.git(&["pull", "--rebase", "--autostash"]) {
+            // A conflicting rebase stops half way; put the branch back.
+            let _ = self.git(&["rebase", "--abort"]);
+            self.save_todos()?;
+            return Err(err.context("Pull failed, nothing was archived or committed"));
+        }
+        // git reports success even when the autostash does not apply
+        // cleanly afterwards, leaving the changes in a new stash entry or
+        // the files half merged. Committing on top would bury them.
+        let unmerged = self.git(&["ls-files", "--unmerged"])?;
+        if self.stash_head() != stash || !unmerged.trim().is_empty() {
+            self.save_todos()?;
+            bail!(
+                "Pulled, but local changes could not be reapplied and are kept in git's stash; \
+                 resolve them with `git stash pop` before syncing again. Nothing was archived or committed"
+            );
+        }
+
+        let local = std::mem::take(&mut self.todo_map);
+        self.load_todos()?;
+        let pulled: Vec<Todo> = std::mem::replace(&mut self.todo_map, local)
+            .into_iter()
+            .map(|(id, data)| Todo { id, data })
+            .collect();
+
+        // Merging only adds, so todos another machine archived or dropped
+        // would otherwise stay here and be archived a second time. They
+        // go unless they were changed here and are still open.
+        let pulled_ids: HashSet<Uuid> = pulled.iter().map(|todo| todo.id).collect();
+        let mut dropped = 0;
+        for committed in before {
+            if pulled_ids.contains(&committed.id) {
+                continue;
+            }
+            let gone = self.todo_map.get(&committed.id).is_some_and(|local| {
+                *local == committed.data
+                    || !matches!(local.status, TodoStatus::Pending | TodoStatus::InProgress)
+            });
+            if gone {
+                self.todo_map.remove(&committed.id);
+                dropped += 1;
             }
         }
-        Ok(())
-    }
+        if dropped > 0 {
+            writeln!(
+                self.output.out(),
+                "Dropped {} todo(s) archived or removed on another machine.",
+                dropped
+            )?;
+        }
 
-    fn list_todos(&self, verbose: bool) {
-        term::splash();
-        let todos = self.ordered_todos();
-        for (id, todo) in todos.iter().enumerate() {
-            term::print_todo(verbose, todo, id);
+        if let Outcome::Imported {
+            added,
+            replaced,
+            kept,
+        } = self.import(pulled, strategy)?
+        {
+            writeln!(
+                self.output.out(),
+                "Pulled {} new todo(s), took {} changed one(s) and kept {} local one(s).",
+                added,
+                replaced,
+                kept
+            )?;
         }
+        self.save_todos()
     }
 
-    pub fn sync(&mut self) -> Result<()> {
+    /// Moves completed and deleted todos into today's `completed_YYYYMMDD.json`,
+    /// returning how many were moved.
+    fn archive_finished(&mut self) -> Result<usize> {
         // Collect all keys whose TodoData indicates completion or deletion.
         let keys_to_archive: Vec<Uuid> = self
             .todo_map
@@ -509,108 +6324,1388 @@ impl Cli {
                 });
             }
         }
-
-        // If there are no todos to archive, we can exit early.
         if archived_todos.is_empty() {
-            println!("No completed or deleted todos to archive.");
-            return Ok(());
+            return Ok(0);
         }
+        let count = archived_todos.len();
 
         // Build filename with current date in YYYYMMDD format.
         let date_str = Utc::now().format("%Y%m%d").to_string();
-        let read_file_path = format!("{}/completed_{}.json", self.file_path, date_str);
-        let write_file_path = format!("{}/completed_{}.json", self.file_path, date_str);
+        let archive_path = format!("{}/completed_{}.json", self.file_path, date_str);
 
-        // Attempt to open the file create it if it doesn't exist
-        let read_file = OpenOptions::new()
+        let mut archive = read_todos(Path::new(&archive_path))?;
+
+        // Extend the existing todos with the newly archived ones, replacing
+        // any copy already there, e.g. one pulled from another machine.
+        let ids: HashSet<Uuid> = archived_todos.iter().map(|todo| todo.id).collect();
+        archive.retain(|todo| !ids.contains(&todo.id));
+        archive.extend(archived_todos);
+
+        let write_file = OpenOptions::new()
             .create(true)
-            .read(true)
+            .truncate(true)
             .write(true)
-            .open(read_file_path)
-            .context("Failed to open or create todo file")?;
+            .open(&archive_path)
+            .context("Failed to open or create archive file")?;
+        serde_json::to_writer_pretty(BufWriter::new(write_file), &archive)
+            .context("Failed to serialize todo list")?;
+        Ok(count)
+    }
+
+    /// The todo list as of the last commit in the data directory, or `None`
+    /// outside a repository or before the first commit.
+    fn committed_todos(&self) -> Result<Option<Vec<Todo>>> {
+        let Ok(committed) = self.git(&["show", "HEAD:./todos.json"]) else {
+            return Ok(None);
+        };
+        let todos = parse_todos(committed.as_bytes())
+            .context("Failed to parse the committed todos.json")?;
+        Ok(Some(todos))
+    }
+
+    /// Labels the todos that are new or modified since the last commit.
+    /// Removed todos are left out, since they are not in the list any more.
+    /// Before there is a commit every todo counts as new, as the next
+    /// `sync` would commit them all.
+    ///
+    /// ```
+    /// use std::process::Command;
+    /// use toto::Cli;
+    /// use toto::output::{Buffer, Output};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-since-commit-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let git = |args: &[&str]| -> anyhow::Result<()> {
+    ///     let status = Command::new("git").arg("-C").arg(&dir).args(args).output()?.status;
+    ///     anyhow::ensure!(status.success(), "git {:?} failed", args);
+    ///     Ok(())
+    /// };
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let out = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok(out.contents())
+    /// };
+    /// let flagged = |listing: &str, title: &str| {
+    ///     listing.lines().find(|line| line.contains(title)).map(|line| line.ends_with("[new]"))
+    /// };
+    /// // Outside a repository every todo is new rather than an error.
+    /// run(&["add", "--title", "Alpha", "--priority", "1"])?;
+    /// assert_eq!(flagged(&run(&["list", "--since-commit"])?, "Alpha"), Some(true));
+    ///
+    /// git(&["init", "-q"])?;
+    /// git(&["config", "user.name", "Toto"])?;
+    /// git(&["config", "user.email", "toto@example.com"])?;
+    /// assert_eq!(flagged(&run(&["list", "--since-commit"])?, "Alpha"), Some(true));
+    /// git(&["add", "-A"])?;
+    /// git(&["commit", "-q", "-m", "baseline"])?;
+    /// run(&["add", "--title", "Charlie", "--priority", "1"])?;
+    /// let listing = run(&["list", "--since-commit"])?;
+    /// assert_eq!(flagged(&listing, "Charlie"), Some(true));
+    /// // Alpha is committed and unchanged, so it isn't listed at all.
+    /// assert_eq!(flagged(&listing, "Alpha"), None);
+    ///
+    /// // A committed todos.json that cannot be parsed is reported.
+    /// std::fs::write(dir.join("todos.json"), "not json")?;
+    /// git(&["commit", "-q", "-am", "broken"])?;
+    /// std::fs::write(dir.join("todos.json"), "[]")?;
+    /// let err = Cli::new(path.clone()).run_from(["toto", "list", "--since-commit"]).unwrap_err();
+    /// assert_eq!(err.to_string(), "Failed to parse the committed todos.json");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn changed_since_commit(&self) -> Result<HashMap<Uuid, &'static str>> {
+        let committed = match self.committed_todos()? {
+            Some(committed) => committed,
+            None => {
+                writeln!(
+                    self.output.err(),
+                    "No committed todos.json to compare with, every todo is new."
+                )?;
+                Vec::new()
+            }
+        };
+        Ok(diff::diff(&committed, &self.ordered_todos())
+            .into_iter()
+            .filter_map(|change| match change {
+                Change::Added(todo) => Some((todo.id, "new")),
+                Change::Modified { id, .. } => Some((id, "modified")),
+                Change::Removed(_) => None,
+            })
+            .collect())
+    }
+
+    /// Restores the todo `id`, a UUID or the start of one, from the newest
+    /// commit of `todos.json` that has it, e.g. after it was archived or
+    /// swept by mistake. Fails if the list still has it or no commit does.
+    /// A todo that was finished in that commit is reopened, or the next
+    /// sync would archive it again.
+    ///
+    /// ```
+    /// use std::process::Command;
+    /// use toto::output::Output;
+    /// use toto::{Cli, NewTodo, Outcome, TodoStatus};
+    ///
+    /// # let base = std::env::temp_dir().join(format!("toto-doc-recover-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&base);
+    /// // The todos live in a directory of a bigger repository.
+    /// let dir = base.join("todo");
+    /// std::fs::create_dir_all(&dir)?;
+    /// let git = |args: &[&str]| {
+    ///     let status = Command::new("git").arg("-C").arg(&dir).args(args).status().unwrap();
+    ///     assert!(status.success());
+    /// };
+    /// assert!(Command::new("git").arg("-C").arg(&base).args(["init", "-q"]).status()?.success());
+    /// git(&["config", "user.email", "me@example.com"]);
+    /// git(&["config", "user.name", "Me"]);
+    /// let path = dir.to_string_lossy().into_owned();
+    /// let cli = || Cli::new(path.clone()).with_output(Output::new(std::io::sink(), std::io::sink()));
+    ///
+    /// let mut first = cli();
+    /// let id = first.add(NewTodo::new("Renew the domain"))?;
+    /// let taxes = first.add(NewTodo { done: true, ..NewTodo::new("File the taxes") })?;
+    /// first.save()?;
+    /// git(&["add", "todos.json"]);
+    /// git(&["commit", "-qm", "Add a todo"]);
+    /// std::fs::write(dir.join("todos.json"), "[]")?;
+    /// git(&["commit", "-qam", "Lose it"]);
+    ///
+    /// let mut later = cli();
+    /// later.load()?;
+    /// let prefix = &id.to_string()[..8];
+    /// assert!(matches!(later.recover(prefix)?, Outcome::Recovered { id: found, .. } if found == id));
+    /// assert_eq!(later.todo(id).unwrap().data.title, "Renew the domain");
+    /// let err = later.recover(prefix).unwrap_err();
+    /// assert!(err.to_string().contains("still in the list"));
+    ///
+    /// let taken = [id, taxes].map(|id| id.to_string().remove(0));
+    /// let unknown = "0123456789abcdef".chars().find(|c| !taken.contains(c)).unwrap();
+    /// let err = later.recover(&unknown.to_string()).unwrap_err();
+    /// assert!(err.to_string().contains("No committed version"));
+    ///
+    /// let outcome = later.recover(&taxes.to_string())?;
+    /// assert!(matches!(outcome, Outcome::Recovered { reopened: true, .. }));
+    /// let data = later.todo(taxes).unwrap().data;
+    /// assert_eq!((data.status, data.completed_at), (TodoStatus::Pending, None));
+    /// # std::fs::remove_dir_all(&base)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn recover(&mut self, id: &str) -> Result<Outcome> {
+        let prefix = id.trim().to_lowercase();
+        let commits = self
+            .git(&["log", "--format=%h", "--", "todos.json"])
+            .context("Recovering needs the data directory to be a git repository")?;
+        for commit in commits.lines() {
+            // Commits that deleted or damaged the file have nothing to offer.
+            let Ok(contents) = self.git(&["show", &format!("{}:./todos.json", commit)]) else {
+                continue;
+            };
+            let Ok(todos) = parse_todos(contents.as_bytes()) else {
+                continue;
+            };
+            let mut found = todos
+                .into_iter()
+                .filter(|todo| todo.id.to_string().starts_with(&prefix));
+            match (found.next(), found.next()) {
+                (Some(todo), None) => {
+                    if self.todo_map.contains_key(&todo.id) {
+                        bail!("Todo {} is still in the list", todo.id);
+                    }
+                    let mut data = todo.data;
+                    let reopened = data.completed_at.is_some() || data.deleted_at.is_some();
+                    data.completed_at = None;
+                    data.deleted_at = None;
+                    data.status = data.status_from_timestamps();
+                    self.todo_map.insert(todo.id, data);
+                    return Ok(Outcome::Recovered {
+                        id: todo.id,
+                        commit: commit.to_string(),
+                        reopened,
+                    });
+                }
+                (Some(_), Some(_)) => bail!("Todo id {} is ambiguous in commit {}", id, commit),
+                (None, _) => {}
+            }
+        }
+        bail!(
+            "No committed version of todos.json has a todo with id {}",
+            id
+        )
+    }
 
-        let metadata = read_file
-            .metadata()
-            .context("Failed to get file metadata")?;
+    /// Prints the todos added, removed or modified since `todos.json` was
+    /// last committed.
+    ///
+    /// ```
+    /// use std::process::Command;
+    /// use toto::cli::{Cli, Todo};
+    /// use toto::diff;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-diff-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let git = |args: &[&str]| -> anyhow::Result<Vec<u8>> {
+    ///     let output = Command::new("git").arg("-C").arg(&dir).args(args).output()?;
+    ///     anyhow::ensure!(output.status.success(), "git {:?} failed", args);
+    ///     Ok(output.stdout)
+    /// };
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// let alpha = uuid::Uuid::parse_str("a1000000-0000-4000-8000-000000000000")?;
+    /// git(&["init", "-q"])?;
+    /// git(&["config", "user.name", "Toto"])?;
+    /// git(&["config", "user.email", "toto@example.com"])?;
+    /// Cli::new(path.clone())
+    ///     .with_id_generator(move || alpha)
+    ///     .run_from(["toto", "add", "--title", "Alpha", "--priority", "1"])?;
+    /// // Nothing committed yet is not an error.
+    /// run(&["diff"])?;
+    ///
+    /// git(&["add", "-A"])?;
+    /// git(&["commit", "-q", "-m", "baseline"])?;
+    /// run(&["update", "--id", &alpha.to_string(), "--title", "Alpha two"])?;
+    /// run(&["diff"])?;
+    ///
+    /// let committed: Vec<Todo> = serde_json::from_slice(&git(&["show", "HEAD:todos.json"])?)?;
+    /// let current: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// let changes: Vec<String> = diff::diff(&committed, &current).iter().map(|c| c.to_string()).collect();
+    /// assert_eq!(changes, ["~ a1000000 Alpha two\n    title: \"Alpha\" -> \"Alpha two\""]);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// The data directory may be anywhere inside the repository:
+    ///
+    /// ```
+    /// use std::process::Command;
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let base = std::env::temp_dir().join(format!("toto-doc-diff-sub-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&base);
+    /// let dir = base.join("notes").join("todo");
+    /// std::fs::create_dir_all(&dir)?;
+    /// let path = dir.to_string_lossy().into_owned();
+    /// let git = |args: &[&str]| -> anyhow::Result<()> {
+    ///     let status = Command::new("git").arg("-C").arg(&dir).args(args).output()?.status;
+    ///     anyhow::ensure!(status.success(), "git {:?} failed", args);
+    ///     Ok(())
+    /// };
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let out = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok(out.contents())
+    /// };
+    /// assert!(Command::new("git").arg("-C").arg(&base).args(["init", "-q"]).status()?.success());
+    /// git(&["config", "user.name", "Toto"])?;
+    /// git(&["config", "user.email", "toto@example.com"])?;
+    /// let alpha = uuid::Uuid::parse_str("a1000000-0000-4000-8000-000000000000")?;
+    /// Cli::new(path.clone())
+    ///     .with_id_generator(move || alpha)
+    ///     .run_from(["toto", "add", "--title", "Alpha", "--priority", "1"])?;
+    /// assert_eq!(run(&["diff"])?, "No committed todos.json to compare with.\n");
+    ///
+    /// git(&["add", "todos.json"])?;
+    /// git(&["commit", "-q", "-m", "baseline"])?;
+    /// assert_eq!(run(&["diff"])?, "No changes since the last commit.\n");
+    /// run(&["update", "--id", &alpha.to_string(), "--title", "Alpha two"])?;
+    /// assert_eq!(run(&["diff"])?, "~ a1000000 Alpha two\n    title: \"Alpha\" -> \"Alpha two\"\n");
+    /// # std::fs::remove_dir_all(&base)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn diff(&self) -> Result<()> {
+        // Outside a repository, or before the first commit, there is
+        // nothing to compare with, which is not an error.
+        let Some(committed) = self.committed_todos()? else {
+            writeln!(
+                self.output.out(),
+                "No committed todos.json to compare with."
+            )?;
+            return Ok(());
+        };
 
-        let mut archive = Vec::new();
-        if metadata.len() > 0 {
-            // Read existing archived todos from file if it exists.
-            let reader = BufReader::new(read_file);
-            archive = serde_json::from_reader(reader).context("Failed to deserialize todo list")?;
+        let changes = diff::diff(&committed, &self.ordered_todos());
+        if changes.is_empty() {
+            writeln!(self.output.out(), "No changes since the last commit.")?;
+        }
+        for change in changes {
+            writeln!(self.output.out(), "{}", change)?;
         }
+        Ok(())
+    }
 
-        // Extend the existing todos with the newly archived ones.
-        archive.extend(archived_todos);
+    /// Commits the todo list and archives and pushes them. With nothing
+    /// changed the commit is skipped but earlier commits are still pushed.
+    ///
+    /// With `sign` the commit is made with `git commit -S`, so git picks the
+    /// key from `user.signingkey` and `gpg.format` as usual, and a commit
+    /// that cannot be signed is an error rather than going in unsigned.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use std::process::Command;
+    /// use toto::cli::Cli;
+    ///
+    /// # let base = std::env::temp_dir().join(format!("toto-doc-sign-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&base);
+    /// # std::fs::create_dir_all(&base)?;
+    /// let git = |dir: &Path, args: &[&str]| -> anyhow::Result<String> {
+    ///     let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    ///     anyhow::ensure!(output.status.success(), "git {:?} failed", args);
+    ///     Ok(String::from_utf8(output.stdout)?)
+    /// };
+    /// git(&base, &["init", "-q", "--bare", "remote.git"])?;
+    /// git(&base, &["clone", "-q", "remote.git", "home"])?;
+    /// let home = base.join("home");
+    /// git(&home, &["config", "user.name", "Toto"])?;
+    /// git(&home, &["config", "user.email", "toto@example.com"])?;
+    /// git(&home, &["config", "user.signingkey", "toto@example.com"])?;
+    /// // A stand-in for gpg that notes it was asked to sign.
+    /// let stub = base.join("gpg-stub");
+    /// std::fs::write(
+    ///     &stub,
+    ///     "#!/bin/sh\ncat > /dev/null\necho called >> \"$0.log\"\necho '[GNUPG:] SIG_CREATED ' >&2\n\
+    ///      printf -- '-----BEGIN PGP SIGNATURE-----\\nstub\\n-----END PGP SIGNATURE-----\\n'\n",
+    /// )?;
+    /// Command::new("chmod").arg("+x").arg(&stub).status()?;
+    /// git(&home, &["config", "gpg.program", &stub.to_string_lossy()])?;
+    ///
+    /// let sync = |title: &str| -> anyhow::Result<()> {
+    ///     let mut cli = Cli::new(home.to_string_lossy().into_owned());
+    ///     cli.run_from(["toto", "add", "--title", title, "--priority", "1"])?;
+    ///     cli.run_from(["toto", "sync", "--git-only", "--sign"])
+    /// };
+    /// sync("Sign the lease")?;
+    /// assert_eq!(std::fs::read_to_string(base.join("gpg-stub.log"))?, "called\n");
+    /// assert!(git(&home, &["cat-file", "commit", "HEAD"])?.contains("gpgsig -----BEGIN PGP SIGNATURE-----"));
+    ///
+    /// // When signing fails nothing is committed.
+    /// git(&home, &["config", "gpg.program", "false"])?;
+    /// let err = sync("Post the lease").unwrap_err();
+    /// assert_eq!(err.to_string(), "Failed to sign the commit, check git's user.signingkey setting");
+    /// assert_eq!(git(&home, &["rev-list", "--count", "HEAD"])?.trim(), "1");
+    /// # std::fs::remove_dir_all(&base)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// Only `synced_paths` are staged, so the undo journal and the saved
+    /// list view never reach the remote, and neither do other files left
+    /// in the data directory.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use std::process::Command;
+    /// use toto::Cli;
+    /// use toto::output::Output;
+    ///
+    /// # let base = std::env::temp_dir().join(format!("toto-doc-sync-paths-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&base);
+    /// # std::fs::create_dir_all(&base)?;
+    /// let git = |dir: &Path, args: &[&str]| -> anyhow::Result<String> {
+    ///     let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    ///     anyhow::ensure!(output.status.success(), "git {:?} failed", args);
+    ///     Ok(String::from_utf8(output.stdout)?)
+    /// };
+    /// git(&base, &["init", "-q", "--bare", "remote.git"])?;
+    /// git(&base, &["clone", "-q", "remote.git", "home"])?;
+    /// let home = base.join("home");
+    /// git(&home, &["config", "user.name", "Toto"])?;
+    /// git(&home, &["config", "user.email", "toto@example.com"])?;
+    /// let run = |args: &[&str]| {
+    ///     Cli::new(home.to_string_lossy().into_owned())
+    ///         .with_output(Output::new(std::io::sink(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))
+    /// };
+    ///
+    /// run(&["add", "Pack the boxes"])?;
+    /// run(&["add", "Book the van"])?;
+    /// run(&["complete", "--id", "0"])?;
+    /// run(&["list", "--sort", "title"])?;
+    /// std::fs::write(home.join("scratch.txt"), "not a todo")?;
+    /// assert!(home.join("journal.json").exists() && home.join("view.json").exists());
+    ///
+    /// run(&["sync"])?;
+    /// let committed = git(&home, &["ls-files"])?;
+    /// let mut committed: Vec<&str> = committed.lines().collect();
+    /// assert_eq!(committed.pop(), Some("todos.json.sha256"));
+    /// assert_eq!(committed.pop(), Some("todos.json"));
+    /// assert_eq!(committed.len(), 1);
+    /// assert!(committed[0].starts_with("completed_"));
+    /// # std::fs::remove_dir_all(&base)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn git_commit_and_push(&self, message: &str, sign: bool) -> Result<()> {
+        let mut args = vec!["add", "--all", "--"];
+        let paths = self.synced_paths()?;
+        args.extend(paths.iter().map(String::as_str));
+        self.git(&args)?;
+        let staged = self.git(&["diff", "--cached", "--name-only"])?;
+        if staged.trim().is_empty() {
+            writeln!(self.output.out(), "Nothing to commit.")?;
+        } else if sign {
+            self.git(&["commit", "-S", "-m", message])
+                .context("Failed to sign the commit, check git's user.signingkey setting")?;
+            writeln!(self.output.out(), "Committed and signed \"{}\".", message)?;
+        } else {
+            self.git(&["commit", "-m", message])?;
+            writeln!(self.output.out(), "Committed \"{}\".", message)?;
+        }
+        self.git(&["push"])?;
+        writeln!(self.output.out(), "Pushed.")?;
+        Ok(())
+    }
 
-        let write_file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .open(write_file_path)
-            .context("Failed to open or create todo file")?;
+    /// The files `sync` commits: the todo list, its checksum and the
+    /// archives, both those on disk and tracked ones that were removed.
+    /// Everything else in the data directory, such as the undo journal and
+    /// the saved list view, stays local.
+    fn synced_paths(&self) -> Result<Vec<String>> {
+        let mut paths: Vec<String> = ["todos.json", "todos.json.sha256"]
+            .into_iter()
+            .filter(|name| PathBuf::from(&self.file_path).join(name).exists())
+            .map(String::from)
+            .collect();
+        for path in self.archive_paths()? {
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                paths.push(name.to_string());
+            }
+        }
+        let tracked = self.git(&[
+            "ls-files",
+            "--",
+            "todos.json",
+            "todos.json.sha256",
+            "completed_*.json",
+        ])?;
+        paths.extend(tracked.lines().map(String::from));
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
 
-        // Serialize the combined vector to pretty JSON.
-        let writer = BufWriter::new(write_file);
+    // The commit `refs/stash` points at, if there is a stash.
+    fn stash_head(&self) -> Option<String> {
+        self.git(&["rev-parse", "--quiet", "--verify", "refs/stash"])
+            .ok()
+    }
+
+    // Runs git in the data directory, returning its stdout.
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = ProcessCommand::new("git")
+            .arg("-C")
+            .arg(&self.file_path)
+            .args(args)
+            .output()
+            .context("Failed to run git, is it installed?")?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn config_command(&self, config_matches: &ArgMatches) -> Result<()> {
+        match config_matches.subcommand() {
+            Some(("show", _)) => write!(self.output.out(), "{}", self.config.render())?,
+            Some(("path", _)) => writeln!(
+                self.output.out(),
+                "{}",
+                Config::path(&self.file_path).display()
+            )?,
+            _ => {}
+        }
+        Ok(())
+    }
 
-        serde_json::to_writer_pretty(writer, &archive).context("Failed to serialize todo list")?;
+    fn validate_config(&self) -> Result<()> {
+        let problems = config::validate(&self.file_path)?;
+        if problems.is_empty() {
+            writeln!(self.output.out(), "Config is valid.")?;
+            return Ok(());
+        }
+        for problem in &problems {
+            writeln!(self.output.err(), "- {}", problem)?;
+        }
+        bail!("Found {} config problem(s)", problems.len());
+    }
 
-        // Change to a specific directory and run git commands.
-        // Replace the following path with your target directory.
-        let target_dir = self.file_path.clone();
+    /// Reports problems in the todo file, and with `fix` repairs those it
+    /// can. A file changed outside toto, so that it no longer matches its
+    /// checksum, is one of them: other commands keep warning about it, even
+    /// after saving, until `--fix` accepts the file as it is.
+    ///
+    /// ```
+    /// use toto::Cli;
+    /// use toto::output::{Buffer, Output};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-doctor-checksum-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| -> anyhow::Result<(String, String)> {
+    ///     let (out, err) = (Buffer::default(), Buffer::default());
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), err.clone()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok((out.contents(), err.contents()))
+    /// };
+    /// run(&["add", "Pay rent"])?;
+    /// let file = dir.join("todos.json");
+    /// let edited = std::fs::read_to_string(&file)?.replace("Pay rent", "Pay the rent");
+    /// std::fs::write(&file, edited)?;
+    ///
+    /// let warned = |err: &str| err.contains("doesn't match its recorded checksum");
+    /// assert!(warned(&run(&["list"])?.1));
+    /// // Saving other changes doesn't silently accept the edit.
+    /// run(&["add", "Water the plants"])?;
+    /// assert!(warned(&run(&["list"])?.1));
+    ///
+    /// let (out, _) = run(&["doctor"])?;
+    /// assert!(out.contains("doesn't match its recorded checksum"), "{}", out);
+    /// assert_eq!(run(&["doctor", "--fix"])?.0, out.replace("Run `toto doctor --fix` to repair 1 issue(s).", "Repaired 1 issue(s)."));
+    /// assert!(!warned(&run(&["list"])?.1));
+    /// assert_eq!(run(&["doctor"])?.0, "No issues found.\n");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn doctor(&self, fix: bool) -> Result<()> {
+        let file_path = format!("{}/todos.json", self.file_path);
+        let contents = match fs::read_to_string(&file_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                writeln!(self.output.out(), "No todo file found at {}", file_path)?;
+                return Ok(());
+            }
+            Err(err) => return Err(err).context("Failed to read todo file"),
+        };
+        let checksum_matches = integrity::verify(Path::new(&file_path), contents.as_bytes())
+            .context("Failed to read the todo file checksum")?;
+        let entries: Vec<Value> = if contents.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&contents).context("Todo file is not a JSON list")?
+        };
+        let issues = doctor::diagnose(&entries);
+        if issues.is_empty() && checksum_matches {
+            writeln!(self.output.out(), "No issues found.")?;
+            return Ok(());
+        }
 
-        // Run "git add ."
-        let status = ProcessCommand::new("cd ")
-            .arg(target_dir)
-            .arg(" \\ git add .".to_string())
-            .arg(format!("\\ git commit -m \"archive {}\"", date_str).to_string())
-            .output();
-        if status.is_ok() {
-            println!("Git add and commit executed successfully.");
+        if !checksum_matches {
+            writeln!(
+                self.output.out(),
+                "- {} doesn't match its recorded checksum, fixing accepts it as it is",
+                file_path
+            )?;
+        }
+        for issue in &issues {
+            let note = if issue.is_fixable() {
+                ""
+            } else {
+                " (manual fix needed)"
+            };
+            writeln!(self.output.out(), "- {}{}", issue, note)?;
         }
 
-        let status = ProcessCommand::new("git push").output();
-        if status.is_ok() {
-            println!("Git push executed successfully.");
+        let fixable = issues.iter().filter(|issue| issue.is_fixable()).count()
+            + usize::from(!checksum_matches);
+        if !fix {
+            if fixable > 0 {
+                writeln!(
+                    self.output.out(),
+                    "Run `toto doctor --fix` to repair {} issue(s).",
+                    fixable
+                )?;
+            }
+            return Ok(());
         }
 
+        // With only the checksum off, the file is accepted byte for byte.
+        let contents = if issues.is_empty() {
+            contents.into_bytes()
+        } else {
+            let repaired = doctor::repair(&entries);
+            let contents =
+                serde_json::to_vec_pretty(&repaired).context("Failed to serialize todo list")?;
+            fs::write(&file_path, &contents).context("Failed to write todo file")?;
+            contents
+        };
+        integrity::record(Path::new(&file_path), &contents)
+            .context("Failed to write the todo file checksum")?;
+        writeln!(self.output.out(), "Repaired {} issue(s).", fixable)?;
         Ok(())
     }
 
-    fn parse_todo_id(&self, id: &String) -> Result<Uuid> {
-        let todos = self.ordered_todos();
+    /// Resolves an id argument to a todo's UUID. Accepts a full UUID, a list
+    /// row number (in relative mode), or a unique prefix of a UUID. With
+    /// `--raw-id` only a full UUID is accepted.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    /// use uuid::Uuid;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-raw-id-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let id = Uuid::parse_str("5e1ec7ed-0000-4000-8000-000000000000")?;
+    /// Cli::new(path.clone())
+    ///     .with_id_generator(move || id)
+    ///     .run_from(["toto", "add", "--title", "Renew passport", "--priority", "1"])?;
+    /// let rename = |id: &str, title: &str| -> anyhow::Result<String> {
+    ///     Cli::new(path.clone()).run_from(["toto", "--raw-id", "update", "--id", id, "--title", title])?;
+    ///     let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    ///     Ok(todos[0].data.title.clone())
+    /// };
+    /// assert!(rename("0", "By row").is_err());
+    /// assert!(rename("5e1ec7ed", "By prefix").is_err());
+    /// assert_eq!(rename(&id.to_string(), "By UUID")?, "By UUID");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn parse_todo_id(&self, id: &str) -> Result<Uuid> {
+        // Anything shaped like a UUID is one, even if it could also be read
+        // as a number.
+        if let Ok(uuid) = Uuid::parse_str(id) {
+            return Ok(uuid);
+        }
+        if self.config.raw_id {
+            bail!("Expected a full UUID but got: {}", id);
+        }
+        if self.config.id_mode == IdMode::Relative {
+            let todos = self.ordered_todos();
+            if let Ok(human_id) = id.parse::<usize>()
+                && let Some(index) = human_id.checked_sub(self.config.id_base)
+                && index < todos.len()
+            {
+                return Ok(todos[index].id);
+            }
+        }
+
+        let prefix = id.to_lowercase();
+        let mut matching = self
+            .todo_map
+            .keys()
+            .filter(|uuid| uuid.to_string().starts_with(&prefix));
+        match (matching.next(), matching.next()) {
+            (Some(uuid), None) => Ok(*uuid),
+            (Some(_), Some(_)) => bail!("Todo id {} is ambiguous", id),
+            _ => bail!("Failed to parse todo id: {}", id),
+        }
+    }
+
+    // The human id of the todo at `index` in list order.
+    fn human_id(&self, index: usize) -> usize {
+        index + self.config.id_base
+    }
+
+    /// Explains, once per data directory, that rows are numbered from 0
+    /// unless `id_base` says otherwise. Only shown while `id_base` is unset,
+    /// so scripts that pin it never see it.
+    ///
+    /// ```
+    /// use toto::{Cli, TodoStatus};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-id-base-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+    /// let first_status = || -> anyhow::Result<TodoStatus> {
+    ///     let mut cli = Cli::new(path.clone());
+    ///     cli.load()?;
+    ///     Ok(cli.todos()[0].data.status.clone())
+    /// };
+    /// // Explicit ids keep the order fixed should both land on the same millisecond.
+    /// for (id, title) in [("a1000000-0000-4000-8000-000000000000", "First"), ("b2000000-0000-4000-8000-000000000000", "Second")] {
+    ///     let id = uuid::Uuid::parse_str(id)?;
+    ///     Cli::new(path.clone())
+    ///         .with_id_generator(move || id)
+    ///         .run_from(["toto", "add", "--title", title, "--priority", "1"])?;
+    /// }
+    ///
+    /// // The first list shows the note and records that it did.
+    /// let marker = dir.join(".id_base_notice");
+    /// assert!(!marker.exists());
+    /// run(&["list"])?;
+    /// assert!(marker.exists());
+    /// run(&["start", "--id", "0"])?;
+    /// assert_eq!(first_status()?, TodoStatus::InProgress);
+    ///
+    /// std::fs::write(dir.join("config.json"), r#"{"id_base": 1}"#)?;
+    /// run(&["complete", "--id", "1"])?;
+    /// assert_eq!(first_status()?, TodoStatus::Completed);
+    /// assert!(run(&["complete", "--id", "0"]).is_err());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn id_base_notice(&self) -> Result<()> {
+        if self.config.source("id_base") != config::Source::Default {
+            return Ok(());
+        }
+        let marker = PathBuf::from(&self.file_path).join(ID_BASE_NOTICE_FILE);
+        if marker.exists() {
+            return Ok(());
+        }
+        writeln!(
+            self.output.err(),
+            "Note: list rows are numbered from 0. Set \"id_base\": 1 in {} to start at 1 instead, or \"id_base\": 0 to keep this and hide this note.",
+            config::CONFIG_FILE
+        )?;
+        // Failing to record it only means the note shows again.
+        let _ = fs::write(marker, "");
+        Ok(())
+    }
+
+    /// Reads a todo file, such as one written by another tool, and reports
+    /// every problem `doctor` knows of. Nothing is written; problems make it
+    /// fail so scripts can rely on the exit status.
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-validate-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// let data = dir.to_string_lossy().into_owned();
+    /// let mut cli = Cli::new(data.clone());
+    /// cli.add(NewTodo::new("Write report"))?;
+    /// cli.save()?;
+    /// let validate = |file: &std::path::Path| -> (anyhow::Result<()>, String) {
+    ///     let out = Buffer::default();
+    ///     let file = file.to_string_lossy();
+    ///     let result = Cli::new(data.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from(["toto", "validate", &file]);
+    ///     (result, out.contents())
+    /// };
+    ///
+    /// let (result, out) = validate(&dir.join("todos.json"));
+    /// assert!(result.is_ok());
+    /// assert_eq!(out, "No issues found in 1 todo(s).\n");
+    ///
+    /// let id = "a1000000-0000-4000-8000-000000000000";
+    /// let todo = |status: &str, completed: &str| {
+    ///     format!(
+    ///         r#"{{"id": "{}", "data": {{"title": "Fixture", "description": null, "priority": 255,
+    ///         "status": "{}", "created_at": 2000, "in_progress_at": null,
+    ///         "completed_at": {}, "deleted_at": null, "parent": null}}}}"#,
+    ///         id, status, completed
+    ///     )
+    /// };
+    /// let broken = [
+    ///     (format!("[{}, {}]", todo("Pending", "null"), todo("Pending", "null")),
+    ///         format!("- id {} is used by 2 entries\n", id)),
+    ///     (format!("[{}]", todo("Completed", "null")),
+    ///         format!("- todo {} is Completed but has no completed_at\n", id)),
+    ///     (format!("[{}]", todo("Completed", "1000")),
+    ///         format!("- todo {} has completed_at earlier than created_at\n", id)),
+    ///     (format!("[{}]", todo("Lost", "null")), String::from("- entry 0 is malformed: ")),
+    /// ];
+    /// for (contents, report) in broken {
+    ///     let file = dir.join("broken.json");
+    ///     std::fs::write(&file, &contents)?;
+    ///     let (result, out) = validate(&file);
+    ///     assert!(result.unwrap_err().to_string().starts_with("Found 1 issue(s) in "));
+    ///     assert!(out.starts_with(&report), "{}", out);
+    ///     assert_eq!(std::fs::read_to_string(&file)?, contents);
+    /// }
+    /// std::fs::write(dir.join("broken.json"), "{")?;
+    /// let error = validate(&dir.join("broken.json")).0.unwrap_err();
+    /// assert!(error.to_string().ends_with("is not a JSON list"));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn validate_file(&self, path: &PathBuf) -> Result<()> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let entries: Vec<Value> = if contents.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&contents)
+                .with_context(|| format!("{} is not a JSON list", path.display()))?
+        };
+        let issues = doctor::diagnose(&entries);
+        if issues.is_empty() {
+            writeln!(
+                self.output.out(),
+                "No issues found in {} todo(s).",
+                entries.len()
+            )?;
+            return Ok(());
+        }
+        for issue in &issues {
+            writeln!(self.output.out(), "- {}", issue)?;
+        }
+        bail!("Found {} issue(s) in {}", issues.len(), path.display());
+    }
+
+    /// Adds todos from elsewhere. One with the id of a todo already in the
+    /// list replaces it or is dropped, as `strategy` decides, unless the
+    /// two are identical.
+    pub fn import(&mut self, todos: Vec<Todo>, strategy: Strategy) -> Result<Outcome> {
+        let (mut added, mut replaced, mut kept) = (0, 0, 0);
+        for todo in todos {
+            let Some(local) = self.todo_map.get(&todo.id) else {
+                self.todo_map.insert(todo.id, todo.data);
+                added += 1;
+                continue;
+            };
+            if *local == todo.data {
+                continue;
+            }
+            let ask = || self.ask_side(todo.id, local, &todo.data);
+            match merge::resolve(strategy, local, &todo.data, ask)? {
+                Side::Local => kept += 1,
+                Side::Incoming => {
+                    self.todo_map.insert(todo.id, todo.data);
+                    replaced += 1;
+                }
+            }
+        }
+        Ok(Outcome::Imported {
+            added,
+            replaced,
+            kept,
+        })
+    }
+
+    /// Shows both copies of a conflicting todo and asks which to keep.
+    ///
+    /// ```
+    /// use toto::merge::Strategy;
+    /// use toto::output::{Buffer, Output};
+    /// use toto::{Cli, NewTodo, Outcome};
+    ///
+    /// let err = Buffer::default();
+    /// let mut cli = Cli::new(String::from("."))
+    ///     .with_output(Output::new(std::io::sink(), err.clone()).with_input(&b"x\ni\n"[..]));
+    /// let id = cli.add(NewTodo::new("Call the bank"))?;
+    /// let mut incoming = cli.todo(id).unwrap();
+    /// incoming.data.title = String::from("Call the bank before noon");
+    ///
+    /// let outcome = cli.import(vec![incoming], Strategy::Interactive)?;
+    /// assert_eq!(outcome, Outcome::Imported { added: 0, replaced: 1, kept: 0 });
+    /// assert_eq!(cli.todo(id).unwrap().data.title, "Call the bank before noon");
+    /// let err = err.contents();
+    /// assert!(err.starts_with(&format!("Todo {} differs:\n  local    Call the bank [", id)));
+    /// assert!(err.contains("Keep (l)ocal or (i)ncoming: 'x' is not a valid answer.\n"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn ask_side(&self, id: Uuid, local: &TodoData, incoming: &TodoData) -> Result<Side> {
+        let mut err = self.output.err();
+        writeln!(err, "Todo {} differs:", id)?;
+        for (name, data) in [("local", local), ("incoming", incoming)] {
+            writeln!(
+                err,
+                "  {:<9}{} [{}], last changed {}",
+                name,
+                data.title,
+                data.status.name(),
+                doctor::latest_timestamp(data)
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+            )?;
+        }
+        drop(err);
+        loop {
+            match prompt::ask(&self.output, "Keep (l)ocal or (i)ncoming")?.as_str() {
+                "l" | "local" => return Ok(Side::Local),
+                "i" | "incoming" => return Ok(Side::Incoming),
+                answer => writeln!(self.output.err(), "'{}' is not a valid answer.", answer)?,
+            }
+        }
+    }
 
-        if let Ok(human_id) = id.parse::<usize>() {
-            if human_id < todos.len() {
-                return Ok(todos[human_id].id);
+    /// Writes the message for a handler's outcome, if it has one, to
+    /// stderr for the ones that are errors.
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-report-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| -> anyhow::Result<(String, String)> {
+    ///     let (out, err) = (Buffer::default(), Buffer::default());
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), err.clone()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok((out.contents(), err.contents()))
+    /// };
+    /// let (out, _) = run(&["add", "--title", "Milk", "--priority", "1"])?;
+    /// assert_eq!(out, "");
+    /// run(&["add", "--title", "Eggs", "--priority", "1"])?;
+    /// assert_eq!(run(&["complete", "--id", "0"])?, (String::from("1 todo left.\n"), String::new()));
+    /// assert_eq!(run(&["complete", "--id", "0"])?.0, "Todo is already completed\n");
+    ///
+    /// // A well-formed id no todo has is reported, not an error.
+    /// let missing = uuid::Uuid::from_u128(7).to_string();
+    /// assert_eq!(run(&["complete", "--id", &missing])?, (String::new(), String::from("Todo not found\n")));
+    /// // An id that can't be resolved at all fails the command.
+    /// let before = std::fs::read(dir.join("todos.json"))?;
+    /// let err = run(&["complete", "--id", "99"]).unwrap_err();
+    /// assert_eq!(err.to_string(), "Failed to parse todo id: 99");
+    /// assert_eq!(std::fs::read(dir.join("todos.json"))?, before);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn report(&self, outcome: &Outcome) -> Result<()> {
+        if let Some(message) = outcome.message(&self.config.date_style()) {
+            if outcome.is_error() {
+                writeln!(self.output.err(), "{}", message)?;
+            } else {
+                writeln!(self.output.out(), "{}", message)?;
             }
         }
-        let uuid =
-            Uuid::parse_str(&id).with_context(|| format!("Failed to parse todo id: {}", id))?;
-        Ok(uuid)
+        Ok(())
     }
 
+    /// Every todo, in list order: by priority, then creation time, then id
+    /// for todos created at the same moment.
+    ///
+    /// ```
+    /// use toto::cli::{Cli, Todo};
+    /// use uuid::Uuid;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-ties-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let mut ids = [3, 1, 2].into_iter();
+    /// let mut cli = Cli::new(path.clone()).with_id_generator(move || Uuid::from_u128(ids.next().unwrap()));
+    /// for title in ["Three", "One", "Two"] {
+    ///     cli.run_from(["toto", "add", "--title", title, "--priority", "1", "--at", "2024-03-01T09:00:00Z"])?;
+    /// }
+    /// // Row 0 is the lowest id, whatever order the todos were added in.
+    /// Cli::new(path).run_from(["toto", "update", "--id", "0", "--title", "First"])?;
+    /// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+    /// let first = todos.iter().find(|todo| todo.data.title == "First").unwrap();
+    /// assert_eq!(first.id, Uuid::from_u128(1));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
     fn ordered_todos(&self) -> Vec<Todo> {
-        let mut todos: Vec<Todo> = self
-            .todo_map
-            .iter()
-            .map(|(&id, data)| Todo {
+        self.ordered()
+            .into_iter()
+            .map(|(id, data)| Todo {
                 id,
                 data: data.clone(),
             })
-            .collect();
+            .collect()
+    }
 
-        todos.sort_by(|a, b| {
-            let priority_cmp = a.data.priority.cmp(&b.data.priority);
+    // Every todo in list order, borrowed so sorting copies nothing.
+    fn ordered(&self) -> Vec<(Uuid, &TodoData)> {
+        let mut todos: Vec<(Uuid, &TodoData)> =
+            self.todo_map.iter().map(|(&id, data)| (id, data)).collect();
 
-            if priority_cmp == Ordering::Equal {
-                // If priority is equal, sort by created_at
-                a.data.created_at.cmp(&b.data.created_at)
-            } else {
-                priority_cmp
+        todos.sort_by_key(|(id, data)| list_order(*id, data));
+        todos
+    }
+}
+
+/// Counts completed and total children for every parent in `todos`.
+/// Deleted children are left out of both counts.
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-progress-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> anyhow::Result<String> {
+///     let out = Buffer::default();
+///     Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))?;
+///     Ok(out.contents())
+/// };
+/// run(&["add", "--title", "Move", "--priority", "0"])?;
+/// for title in ["Pack", "Unpack", "Clean", "Sell the sofa", "Fix the shelf", "Paint"] {
+///     run(&["add", "--title", title, "--priority", "1", "--parent", "0"])?;
+/// }
+/// for id in ["1", "2", "3"] {
+///     run(&["complete", "--id", id, "--quiet"])?;
+/// }
+/// run(&["delete", "--id", "4"])?;
+///
+/// let listed = run(&["list", "--limit-width", "200"])?;
+/// let parent = listed.lines().find(|line| line.contains("Move")).unwrap();
+/// assert!(parent.contains("| Move (3/5) "), "{parent}");
+/// // Only parents get a count.
+/// assert!(!listed.lines().filter(|line| !line.contains("Move")).any(|line| line.contains('/')));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+fn count_children<'a>(
+    todos: impl IntoIterator<Item = &'a TodoData>,
+) -> HashMap<Uuid, (usize, usize)> {
+    let mut progress: HashMap<Uuid, (usize, usize)> = HashMap::new();
+    for child in todos {
+        if let Some(parent) = child.parent
+            && child.status != TodoStatus::Deleted
+        {
+            let (done, total) = progress.entry(parent).or_insert((0, 0));
+            if child.status == TodoStatus::Completed {
+                *done += 1;
             }
-        });
+            *total += 1;
+        }
+    }
+    progress
+}
 
-        todos
+/// Sorts the entries `keep` accepts by `key`, each with the position it
+/// would have if all entries were sorted. Only the kept entries are sorted;
+/// each of the others costs one binary search among them, so a few kept
+/// entries among many are cheap.
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::cmp::Ordering;
+/// use toto::cli;
+///
+/// // A key that counts how often it is compared.
+/// struct Counted<'a>(u32, &'a Cell<usize>);
+/// impl PartialEq for Counted<'_> {
+///     fn eq(&self, other: &Self) -> bool { self.cmp(other).is_eq() }
+/// }
+/// impl Eq for Counted<'_> {}
+/// impl PartialOrd for Counted<'_> {
+///     fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+/// }
+/// impl Ord for Counted<'_> {
+///     fn cmp(&self, other: &Self) -> Ordering {
+///         self.1.set(self.1.get() + 1);
+///         self.0.cmp(&other.0)
+///     }
+/// }
+///
+/// // 10,000 entries in no particular order, every 2,000th one still open.
+/// let comparisons = Cell::new(0);
+/// let entries: Vec<u32> = (0..10_000).map(|n| n * 7_919 % 10_000).collect();
+/// let open = |n: &u32| n % 2_000 == 0;
+/// let selected = cli::select_sorted(entries.iter().copied(), |&n| Counted(n, &comparisons), open);
+/// assert_eq!(selected, [(0, 0), (2_000, 2_000), (4_000, 4_000), (6_000, 6_000), (8_000, 8_000)]);
+/// let selecting = comparisons.replace(0);
+///
+/// // Sorting everything first, as `Cli::todos` does, compares far more.
+/// let mut all: Vec<Counted> = entries.iter().map(|&n| Counted(n, &comparisons)).collect();
+/// all.sort();
+/// let sorting = comparisons.get();
+/// assert!(selecting <= 5 * entries.len(), "{} comparisons", selecting);
+/// assert!(selecting * 2 < sorting, "{} against {}", selecting, sorting);
+/// ```
+pub fn select_sorted<T, K: Ord>(
+    entries: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> K,
+    keep: impl Fn(&T) -> bool,
+) -> Vec<(usize, T)> {
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in entries {
+        if keep(&entry) {
+            kept.push((key(&entry), entry));
+        } else {
+            skipped.push(key(&entry));
+        }
+    }
+    kept.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    // How many skipped entries sort right before each kept one.
+    let mut before = vec![0; kept.len() + 1];
+    for skipped in skipped {
+        before[kept.partition_point(|(key, _)| *key < skipped)] += 1;
+    }
+    let mut skipped_before = 0;
+    kept.into_iter()
+        .enumerate()
+        .map(|(index, (_, entry))| {
+            skipped_before += before[index];
+            (index + skipped_before, entry)
+        })
+        .collect()
+}
+
+// The key todos are listed by: priority, then created_at. Todos added in a
+// tight loop, or loaded from files that only stored whole seconds, can tie;
+// the id breaks those so the order never depends on the HashMap's
+// iteration order.
+fn list_order(id: Uuid, data: &TodoData) -> (u8, DateTime<Utc>, Uuid) {
+    (data.priority, data.created_at, id)
+}
+
+// Refuses to finish a todo at `at` if that's in the future or earlier than
+// it was created or started, which would make its durations negative.
+fn check_backdate(todo: &TodoData, at: DateTime<Utc>) -> Result<()> {
+    if at > Utc::now() {
+        bail!("{} is in the future", at);
+    }
+    if at < todo.created_at {
+        bail!(
+            "{} is before the todo was created, at {}",
+            at,
+            todo.created_at
+        );
+    }
+    if let Some(started) = todo.in_progress_at
+        && at < started
+    {
+        bail!("{} is before the todo was started, at {}", at, started);
     }
+    Ok(())
+}
+
+/// Flags that make `list` and `count` fail depending on whether anything
+/// matched, for gating scripts and CI jobs on the result.
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-gate-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> (anyhow::Result<()>, String) {
+///     let out = Buffer::default();
+///     let result = Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args));
+///     (result, out.contents())
+/// };
+///
+/// // Nothing matches yet.
+/// for command in ["count", "list"] {
+///     assert!(run(&[command, "--fail-if-any"]).0.is_ok());
+///     let error = run(&[command, "--fail-if-none"]).0.unwrap_err();
+///     assert_eq!(error.to_string(), "No todos matched, failing because of --fail-if-none");
+/// }
+///
+/// run(&["add", "--title", "Fix the flaky test", "--priority", "1", "--tag", "ci"]).0?;
+/// for command in ["count", "list"] {
+///     assert!(run(&[command, "--fail-if-none"]).0.is_ok());
+///     assert!(run(&[command, "--tag", "release", "--fail-if-any"]).0.is_ok());
+///     let error = run(&[command, "--tag", "ci", "--fail-if-any"]).0.unwrap_err();
+///     assert_eq!(error.to_string(), "1 todo(s) matched, failing because of --fail-if-any");
+/// }
+/// // The result is still written before failing.
+/// assert_eq!(run(&["count", "--fail-if-any"]).1, "1\n");
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+fn gate_args() -> Vec<Arg> {
+    vec![
+        Arg::new("fail-if-any")
+            .required(false)
+            .long("fail-if-any")
+            .conflicts_with("fail-if-none")
+            .help("Exit with an error if any todo matches, e.g. with --overdue")
+            .action(ArgAction::SetTrue),
+        Arg::new("fail-if-none")
+            .required(false)
+            .long("fail-if-none")
+            .help("Exit with an error if no todo matches")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+// Asks for a priority by name, by its first letter or as a number.
+fn ask_priority(output: &Output, default: u8) -> Result<u8> {
+    prompt::ask_parsed_with(
+        output,
+        &format!(
+            "Priority: (h)igh, (m)edium, (l)ow, (n)one or 0-255 (default {})",
+            default
+        ),
+        default,
+        |answer| match answer.to_lowercase().as_str() {
+            "h" => Ok(0),
+            "m" => parse_priority("medium"),
+            "l" => parse_priority("low"),
+            "n" => Ok(u8::MAX),
+            _ => parse_priority(answer),
+        },
+    )
+}
+
+// The title given to `add` with -t or as positional words.
+fn add_title(add_matches: &ArgMatches) -> Option<String> {
+    if let Some(title) = add_matches.get_one::<String>("title") {
+        return Some(title.clone());
+    }
+    let words: Vec<&str> = add_matches
+        .get_many::<String>("words")?
+        .map(|word| word.as_str())
+        .collect();
+    Some(words.join(" "))
+}
+
+/// The filters and confirmation shared by the `tag` subcommands.
+fn tag_batch_args() -> Vec<Arg> {
+    let mut args = filter_args();
+    args.push(
+        Arg::new("yes")
+            .required(false)
+            .long("yes")
+            .short('y')
+            .help(format!(
+                "Confirm changing more than {} todos at once",
+                TAG_BATCH_CONFIRM
+            ))
+            .action(ArgAction::SetTrue),
+    );
+    args
+}
+
+// Fails as asked by `gate_args`, after the output has been written.
+fn check_gate(matches: &ArgMatches, matched: usize) -> Result<()> {
+    if matches.get_flag("fail-if-any") && matched > 0 {
+        bail!(
+            "{} todo(s) matched, failing because of --fail-if-any",
+            matched
+        );
+    }
+    if matches.get_flag("fail-if-none") && matched == 0 {
+        bail!("No todos matched, failing because of --fail-if-none");
+    }
+    Ok(())
+}
+
+/// The filter and sort options shared by `list` and `export`.
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-tags-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> anyhow::Result<String> {
+///     let out = Buffer::default();
+///     Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))?;
+///     Ok(out.contents())
+/// };
+/// run(&["add", "--title", "Fix prod", "--priority", "1", "--tag", "work", "--tag", "urgent"])?;
+/// run(&["add", "--title", "Write docs", "--priority", "1", "--tag", "work"])?;
+/// run(&["add", "--title", "Fix the sink", "--priority", "1", "--tag", "home", "--tag", "urgent"])?;
+/// run(&["add", "--title", "Paint the fence", "--priority", "1", "--tag", "home", "--in-progress"])?;
+/// run(&["add", "--title", "Read a book", "--priority", "1"])?;
+/// let count = |args: &[&str]| -> anyhow::Result<String> {
+///     Ok(run(&[&["count"], args].concat())?.trim().to_string())
+/// };
+///
+/// assert_eq!(count(&["--tag-all", "work", "--tag-all", "urgent"])?, "1");
+/// assert_eq!(count(&["--tag-any", "work", "--tag-any", "urgent"])?, "3");
+/// assert_eq!(count(&["--tag", "work", "--tag", "home"])?, "4");
+/// // Any of work or home, and urgent as well.
+/// assert_eq!(count(&["--tag", "work", "--tag", "home", "--tag-all", "urgent"])?, "2");
+/// // Tag filters combine with the others.
+/// assert_eq!(count(&["--tag-any", "home", "--status", "pending"])?, "1");
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+fn filter_args() -> Vec<Arg> {
+    vec![
+        Arg::new("title-contains")
+            .required(false)
+            .long("title-contains")
+            .help("Only include todos whose title contains this text")
+            .value_parser(value_parser!(String)),
+        Arg::new("desc-contains")
+            .required(false)
+            .long("desc-contains")
+            .help("Only include todos whose description contains this text")
+            .value_parser(value_parser!(String)),
+        Arg::new("case-sensitive")
+            .required(false)
+            .long("case-sensitive")
+            .help("Match the contains filters case-sensitively")
+            .action(ArgAction::SetTrue),
+        Arg::new("hide-completed-after")
+            .required(false)
+            .long("hide-completed-after")
+            .help("Hide todos completed longer ago than this, e.g. 7d")
+            .value_parser(time::parse_duration),
+        Arg::new("stale")
+            .required(false)
+            .long("stale")
+            .help("Only include open todos not created or touched within this long, e.g. 30d")
+            .value_parser(time::parse_duration),
+        Arg::new("changed-since")
+            .required(false)
+            .long("changed-since")
+            .help("Only include todos created, started, completed, deleted or touched within this long, e.g. 2d")
+            .value_parser(time::parse_duration),
+        Arg::new("due-today")
+            .required(false)
+            .long("due-today")
+            .help("Only include open todos due today (see --tz)")
+            .action(ArgAction::SetTrue),
+        Arg::new("overdue")
+            .required(false)
+            .long("overdue")
+            .help("Only include open todos that are past due")
+            .action(ArgAction::SetTrue),
+        Arg::new("actionable")
+            .required(false)
+            .long("actionable")
+            .visible_alias("only-actionable")
+            .help("Only include open todos that are neither blocked nor snoozed")
+            .action(ArgAction::SetTrue),
+        Arg::new("sort")
+            .required(false)
+            .long("sort")
+            .help("Order rows by this field")
+            .value_parser(value_parser!(SortKey)),
+        Arg::new("sort-nulls")
+            .required(false)
+            .long("sort-nulls")
+            .help("Where todos without the sorted field go, e.g. no due date with --sort due [default: last]")
+            .value_parser(value_parser!(Nulls)),
+        Arg::new("status")
+            .required(false)
+            .long("status")
+            .help("Only include todos with this status, may be repeated")
+            .value_parser(value_parser!(TodoStatus))
+            .action(ArgAction::Append),
+        Arg::new("tag")
+            .required(false)
+            .long("tag")
+            .help("Only include todos with this tag, may be repeated to allow any of several")
+            .value_parser(value_parser!(String))
+            .action(ArgAction::Append),
+        Arg::new("tag-any")
+            .required(false)
+            .long("tag-any")
+            .help("Same as --tag: todos need at least one of the tags given")
+            .value_parser(value_parser!(String))
+            .action(ArgAction::Append),
+        Arg::new("tag-all")
+            .required(false)
+            .long("tag-all")
+            .help(
+                "Only include todos with this tag, may be repeated to require all of them. \
+                 Applies together with --tag and every other filter",
+            )
+            .value_parser(value_parser!(String))
+            .action(ArgAction::Append),
+    ]
 }
@@ -0,0 +1,447 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use chrono::TimeDelta;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::cli::{DEFAULT_MAX_TITLE_BYTES, IdMode, parse_tag_priorities};
+use crate::sweep::{self, Rule};
+use crate::term::{self, Color, TINY_TERM};
+use crate::time::{self, DateStyle, Tz};
+use crate::urgency::Weights;
+
+/// Name of the config file inside the data directory.
+pub const CONFIG_FILE: &str = "config.json";
+
+/// Every key the config file understands. Each one can also be set with an
+/// environment variable named `TOTO_` plus the key in upper case.
+pub const KEYS: [&str; 21] = [
+    "id_mode",
+    "id_base",
+    "raw_id",
+    "strict_ids",
+    "max_title_bytes",
+    "tz",
+    "date_format",
+    "compact_dates_within",
+    "ellipsis",
+    "tag_colors",
+    "tag_priorities",
+    "priority_symbols",
+    "tiny_width",
+    "count_deleted_in_stats",
+    "auto_start_unblocked",
+    "auto_complete_parents",
+    "urgency_priority_weight",
+    "urgency_due_weight",
+    "urgency_horizon_days",
+    "notify_due_within",
+    "sweep_rules",
+];
+
+/// Where the effective value of a setting came from, lowest precedence first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::Default => "default",
+            Source::File => "config file",
+            Source::Env => "environment",
+            Source::CommandLine => "command line",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Settings shared by every command. Command line flags override the
+/// environment, which overrides the config file, which overrides the
+/// built-in defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub id_mode: IdMode,
+    /// Number of the first row in `list`, 0 or 1.
+    pub id_base: usize,
+    pub raw_id: bool,
+    /// Refuse to load a todo file with duplicate ids instead of warning.
+    pub strict_ids: bool,
+    pub max_title_bytes: usize,
+    pub tz: Tz,
+    /// strftime-style format dates are shown in, e.g. `%d/%m/%Y`.
+    pub date_format: String,
+    /// How close to now a date has to be for `--compact-dates` to show it
+    /// as e.g. `2d ago`.
+    pub compact_dates_within: TimeDelta,
+    /// Marker `list` appends to truncated values.
+    pub ellipsis: String,
+    /// Colors `list` shows tags in, e.g. `urgent=red`. Tags not listed get
+    /// a color picked from their name.
+    pub tag_colors: BTreeMap<String, Color>,
+    /// Priorities `add` gives unprioritised todos with these tags, e.g.
+    /// `urgent=high`. The most important one wins.
+    pub tag_priorities: BTreeMap<String, u8>,
+    /// Symbols `list --priority-symbols` shows priorities as, e.g.
+    /// `high=!!!, low=!`.
+    pub priority_symbols: BTreeMap<u8, String>,
+    /// Terminal width below which `list` prints one compact line per todo.
+    pub tiny_width: usize,
+    /// Whether deleted todos count towards the percentages in `stats`.
+    pub count_deleted_in_stats: bool,
+    /// Start every blocked todo once its blockers are done, not just the
+    /// ones added with `--auto-start`.
+    pub auto_start_unblocked: bool,
+    /// Complete a parent once its last open subtask is completed.
+    pub auto_complete_parents: bool,
+    /// Weights `next`, `top` and `list --sort urgency` rank todos by.
+    pub urgency: Weights,
+    /// How soon a todo has to be due for `watch --notify` to remind of it.
+    pub notify_due_within: TimeDelta,
+    /// What `sweep` deletes.
+    pub sweep_rules: Vec<Rule>,
+    sources: HashMap<&'static str, Source>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            id_mode: IdMode::default(),
+            id_base: 0,
+            raw_id: false,
+            strict_ids: false,
+            max_title_bytes: DEFAULT_MAX_TITLE_BYTES,
+            tz: Tz::default(),
+            date_format: String::from(time::DEFAULT_DATE_FORMAT),
+            compact_dates_within: TimeDelta::weeks(1),
+            ellipsis: String::from("..."),
+            tag_colors: BTreeMap::new(),
+            tag_priorities: BTreeMap::new(),
+            priority_symbols: term::parse_priority_symbols(term::DEFAULT_PRIORITY_SYMBOLS)
+                .unwrap_or_default(),
+            tiny_width: TINY_TERM,
+            count_deleted_in_stats: false,
+            auto_start_unblocked: false,
+            auto_complete_parents: false,
+            urgency: Weights::default(),
+            notify_due_within: TimeDelta::hours(1),
+            sweep_rules: Vec::new(),
+            sources: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// How dates are shown, from `date_format` and `tz`.
+    pub fn date_style(&self) -> DateStyle {
+        DateStyle {
+            format: self.date_format.clone(),
+            tz: self.tz,
+            relative_within: None,
+        }
+    }
+
+    pub fn path(data_dir: &str) -> PathBuf {
+        PathBuf::from(data_dir).join(CONFIG_FILE)
+    }
+
+    /// Reads the config file in `data_dir` and applies `TOTO_*` variables
+    /// on top. Unknown keys are ignored here and reported by [`validate`];
+    /// invalid values are an error.
+    pub fn load(data_dir: &str) -> Result<Config> {
+        let mut config = Config::default();
+        for (key, value) in read_file(data_dir)? {
+            if let Some(key) = known_key(&key) {
+                config
+                    .set(key, &value_to_string(&value), Source::File)
+                    .with_context(|| format!("Invalid value for {} in {}", key, CONFIG_FILE))?;
+            }
+        }
+        for key in KEYS {
+            if let Ok(value) = std::env::var(env_var(key)) {
+                config
+                    .set(key, &value, Source::Env)
+                    .with_context(|| format!("Invalid value for {}", env_var(key)))?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Sets `key` from its textual form, recording where it came from.
+    pub fn set(&mut self, key: &'static str, value: &str, source: Source) -> Result<()> {
+        match key {
+            "id_mode" => {
+                self.id_mode = IdMode::from_str(value, true).map_err(anyhow::Error::msg)?;
+            }
+            "id_base" => {
+                self.id_base = match value {
+                    "0" => 0,
+                    "1" => 1,
+                    _ => bail!("expected 0 or 1, got '{}'", value),
+                };
+            }
+            "raw_id" => {
+                self.raw_id = value
+                    .parse()
+                    .with_context(|| format!("expected true or false, got '{}'", value))?;
+            }
+            "strict_ids" => {
+                self.strict_ids = value
+                    .parse()
+                    .with_context(|| format!("expected true or false, got '{}'", value))?;
+            }
+            "max_title_bytes" => {
+                self.max_title_bytes = value
+                    .parse()
+                    .with_context(|| format!("expected a number of bytes, got '{}'", value))?;
+            }
+            "tz" => self.tz = time::parse_tz(value).map_err(anyhow::Error::msg)?,
+            "date_format" => {
+                self.date_format = time::parse_date_format(value).map_err(anyhow::Error::msg)?;
+            }
+            "compact_dates_within" => {
+                self.compact_dates_within =
+                    time::parse_duration(value).map_err(anyhow::Error::msg)?;
+            }
+            "ellipsis" => self.ellipsis = value.to_string(),
+            "tag_colors" => {
+                self.tag_colors = term::parse_tag_colors(value).map_err(anyhow::Error::msg)?;
+            }
+            "tag_priorities" => {
+                self.tag_priorities = parse_tag_priorities(value).map_err(anyhow::Error::msg)?;
+            }
+            "priority_symbols" => {
+                self.priority_symbols =
+                    term::parse_priority_symbols(value).map_err(anyhow::Error::msg)?;
+            }
+            "auto_start_unblocked" => {
+                self.auto_start_unblocked = value
+                    .parse()
+                    .with_context(|| format!("expected true or false, got '{}'", value))?;
+            }
+            "auto_complete_parents" => {
+                self.auto_complete_parents = value
+                    .parse()
+                    .with_context(|| format!("expected true or false, got '{}'", value))?;
+            }
+            "count_deleted_in_stats" => {
+                self.count_deleted_in_stats = value
+                    .parse()
+                    .with_context(|| format!("expected true or false, got '{}'", value))?;
+            }
+            "tiny_width" => {
+                self.tiny_width = value
+                    .parse()
+                    .with_context(|| format!("expected a number of columns, got '{}'", value))?;
+            }
+            "urgency_priority_weight" => self.urgency.priority = parse_number(value)?,
+            "urgency_due_weight" => self.urgency.due = parse_number(value)?,
+            "urgency_horizon_days" => self.urgency.horizon_days = parse_number(value)?,
+            "notify_due_within" => {
+                self.notify_due_within = time::parse_duration(value).map_err(anyhow::Error::msg)?;
+            }
+            "sweep_rules" => {
+                self.sweep_rules = sweep::parse_rules(value).map_err(anyhow::Error::msg)?;
+            }
+            _ => bail!("Unknown config key '{}'", key),
+        }
+        self.sources.insert(key, source);
+        Ok(())
+    }
+
+    /// Records that a command line flag overrode `key`. The caller has
+    /// already stored the value itself.
+    pub fn mark_from_command_line(&mut self, key: &'static str) {
+        self.sources.insert(key, Source::CommandLine);
+    }
+
+    pub fn source(&self, key: &str) -> Source {
+        self.sources.get(key).copied().unwrap_or(Source::Default)
+    }
+
+    /// The effective value of `key` in the form the config file uses.
+    pub fn value(&self, key: &str) -> String {
+        match key {
+            "id_mode" => self
+                .id_mode
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default(),
+            "id_base" => self.id_base.to_string(),
+            "raw_id" => self.raw_id.to_string(),
+            "strict_ids" => self.strict_ids.to_string(),
+            "max_title_bytes" => self.max_title_bytes.to_string(),
+            "tz" => self.tz.to_string(),
+            "date_format" => self.date_format.clone(),
+            "compact_dates_within" => time::format_duration(self.compact_dates_within),
+            "ellipsis" => self.ellipsis.clone(),
+            "tag_colors" => self
+                .tag_colors
+                .iter()
+                .filter_map(|(tag, color)| {
+                    let name = color.to_possible_value()?;
+                    Some(format!("{}={}", tag, name.get_name()))
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            "tag_priorities" => self
+                .tag_priorities
+                .iter()
+                .map(|(tag, priority)| format!("{}={}", tag, priority))
+                .collect::<Vec<_>>()
+                .join(","),
+            "priority_symbols" => self
+                .priority_symbols
+                .iter()
+                .map(|(priority, symbols)| format!("{}={}", priority, symbols))
+                .collect::<Vec<_>>()
+                .join(","),
+            "tiny_width" => self.tiny_width.to_string(),
+            "count_deleted_in_stats" => self.count_deleted_in_stats.to_string(),
+            "auto_start_unblocked" => self.auto_start_unblocked.to_string(),
+            "auto_complete_parents" => self.auto_complete_parents.to_string(),
+            "urgency_priority_weight" => self.urgency.priority.to_string(),
+            "urgency_due_weight" => self.urgency.due.to_string(),
+            "urgency_horizon_days" => self.urgency.horizon_days.to_string(),
+            "notify_due_within" => time::format_duration(self.notify_due_within),
+            "sweep_rules" => serde_json::to_string(&self.sweep_rules).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+
+    /// One `key = value (source)` line per setting.
+    pub fn render(&self) -> String {
+        KEYS.iter()
+            .map(|key| format!("{} = {} ({})\n", key, self.value(key), self.source(key)))
+            .collect()
+    }
+}
+
+/// Checks the config file and `TOTO_*` variables without stopping at the
+/// first problem. Returns one message per unknown key or invalid value.
+///
+/// ```
+/// use toto::cli::Cli;
+/// use toto::config::{self, Config, Source};
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-config-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// std::fs::write(
+///     Config::path(&path),
+///     r#"{"colour": true, "raw_id": "yes", "max_title_bytes": 80, "ellipsis": "~"}"#,
+/// )?;
+/// // Doctests run in their own process, so this can't leak into others.
+/// unsafe {
+///     std::env::set_var("TOTO_MAX_TITLE_BYTES", "lots");
+/// }
+/// assert_eq!(
+///     config::validate(&path)?,
+///     [
+///         "config.json: unknown key 'colour'",
+///         "config.json: invalid value for raw_id: expected true or false, got 'yes'",
+///         "TOTO_MAX_TITLE_BYTES: expected a number of bytes, got 'lots'",
+///     ]
+/// );
+/// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+/// let err = run(&["config", "validate"]).unwrap_err();
+/// assert_eq!(err.to_string(), "Found 3 config problem(s)");
+///
+/// // The environment beats the file, and a flag beats both.
+/// std::fs::write(Config::path(&path), r#"{"max_title_bytes": 80, "ellipsis": "~"}"#)?;
+/// unsafe {
+///     std::env::set_var("TOTO_MAX_TITLE_BYTES", "100");
+/// }
+/// run(&["config", "validate"])?;
+/// let loaded = Config::load(&path)?;
+/// assert_eq!((loaded.max_title_bytes, loaded.source("max_title_bytes")), (100, Source::Env));
+/// assert_eq!((loaded.ellipsis.as_str(), loaded.source("ellipsis")), ("~", Source::File));
+/// assert!(loaded.render().contains("tz = local (default)\n"));
+/// let title = "x".repeat(120);
+/// let add = ["add", "--title", title.as_str(), "--priority", "1"];
+/// assert!(run(&add).is_err());
+/// run(&[&["--max-title-bytes", "150"], &add[..]].concat())?;
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn validate(data_dir: &str) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let mut config = Config::default();
+    for (key, value) in read_file(data_dir)? {
+        match known_key(&key) {
+            Some(key) => {
+                if let Err(err) = config.set(key, &value_to_string(&value), Source::File) {
+                    problems.push(format!(
+                        "{}: invalid value for {}: {}",
+                        CONFIG_FILE, key, err
+                    ));
+                }
+            }
+            None => problems.push(format!("{}: unknown key '{}'", CONFIG_FILE, key)),
+        }
+    }
+    for key in KEYS {
+        if let Ok(value) = std::env::var(env_var(key))
+            && let Err(err) = config.set(key, &value, Source::Env)
+        {
+            problems.push(format!("{}: {}", env_var(key), err));
+        }
+    }
+    Ok(problems)
+}
+
+fn parse_number(value: &str) -> Result<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .with_context(|| format!("expected a number, got '{}'", value))
+}
+
+fn env_var(key: &str) -> String {
+    format!("TOTO_{}", key.to_uppercase())
+}
+
+fn known_key(key: &str) -> Option<&'static str> {
+    KEYS.into_iter().find(|known| *known == key)
+}
+
+// Strings are taken as is so `"tz": "+02:00"` and `"max_title_bytes": 80`
+// both work.
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// A missing config file is the same as an empty one.
+fn read_file(data_dir: &str) -> Result<Map<String, Value>> {
+    let path = Config::path(data_dir);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Map::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    if contents.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse {}", path.display()))?
+    {
+        Value::Object(map) => Ok(map),
+        _ => bail!("{} must contain a JSON object", path.display()),
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::cli::Todo;
+
+/// How one todo differs between two versions of the list.
+#[derive(Debug, Clone)]
+pub enum Change {
+    Added(Todo),
+    Removed(Todo),
+    Modified {
+        id: Uuid,
+        title: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// A single field whose stored value changed, shown as JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Compares two versions of the list by id. Changes come in the order of
+/// `new`, followed by the todos only `old` has.
+pub fn diff(old: &[Todo], new: &[Todo]) -> Vec<Change> {
+    let old_by_id: HashMap<Uuid, &Todo> = old.iter().map(|todo| (todo.id, todo)).collect();
+    let mut changes = Vec::new();
+    for todo in new {
+        match old_by_id.get(&todo.id) {
+            None => changes.push(Change::Added(todo.clone())),
+            Some(before) => {
+                let fields = field_changes(before, todo);
+                if !fields.is_empty() {
+                    changes.push(Change::Modified {
+                        id: todo.id,
+                        title: todo.data.title.clone(),
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+    for todo in old {
+        if !new.iter().any(|other| other.id == todo.id) {
+            changes.push(Change::Removed(todo.clone()));
+        }
+    }
+    changes
+}
+
+// Compares the serialized form so every stored field is covered, including
+// ones added later, without listing them here.
+fn field_changes(old: &Todo, new: &Todo) -> Vec<FieldChange> {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) = (
+        serde_json::to_value(&old.data),
+        serde_json::to_value(&new.data),
+    ) else {
+        return Vec::new();
+    };
+    let mut keys: Vec<&String> = new.keys().chain(old.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let before = old.get(key).unwrap_or(&Value::Null);
+            let after = new.get(key).unwrap_or(&Value::Null);
+            (before != after).then(|| FieldChange {
+                field: key.clone(),
+                old: before.to_string(),
+                new: after.to_string(),
+            })
+        })
+        .collect()
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(todo) => write!(f, "+ {} {}", short_id(todo.id), todo.data.title),
+            Change::Removed(todo) => write!(f, "- {} {}", short_id(todo.id), todo.data.title),
+            Change::Modified { id, title, fields } => {
+                write!(f, "~ {} {}", short_id(*id), title)?;
+                for change in fields {
+                    write!(
+                        f,
+                        "\n    {}: {} -> {}",
+                        change.field, change.old, change.new
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn short_id(id: Uuid) -> String {
+    id.to_string()[..8].to_string()
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::cli::{Todo, TodoData, TodoStatus};
+
+/// A problem found in a stored todo list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// The entry at `index` could not be read as a todo.
+    Malformed { index: usize, error: String },
+    /// More than one entry shares the same id.
+    DuplicateId { id: Uuid, count: usize },
+    /// The todo names a parent that does not exist.
+    MissingParent { id: Uuid, parent: Uuid },
+    /// The todo is blocked by a todo that does not exist. `Todo::is_blocked`
+    /// already treats it as done, so the link only hides that.
+    MissingBlocker { id: Uuid, blocker: Uuid },
+    /// The status claims a stage whose timestamp is missing.
+    MissingTimestamp {
+        id: Uuid,
+        status: TodoStatus,
+        field: &'static str,
+    },
+    /// A lifecycle timestamp lies before one that must come first, such as
+    /// a todo completed before it was created.
+    OutOfOrder {
+        id: Uuid,
+        field: &'static str,
+        before: &'static str,
+    },
+}
+
+impl Issue {
+    /// Whether `repair` knows how to fix this issue.
+    pub fn is_fixable(&self) -> bool {
+        !matches!(self, Issue::Malformed { .. } | Issue::OutOfOrder { .. })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Malformed { index, error } => {
+                write!(f, "entry {} is malformed: {}", index, error)
+            }
+            Issue::DuplicateId { id, count } => {
+                write!(f, "id {} is used by {} entries", id, count)
+            }
+            Issue::MissingParent { id, parent } => {
+                write!(f, "todo {} has missing parent {}", id, parent)
+            }
+            Issue::MissingBlocker { id, blocker } => {
+                write!(f, "todo {} is blocked by missing {}", id, blocker)
+            }
+            Issue::MissingTimestamp { id, status, field } => {
+                write!(f, "todo {} is {:?} but has no {}", id, status, field)
+            }
+            Issue::OutOfOrder { id, field, before } => {
+                write!(f, "todo {} has {} earlier than {}", id, field, before)
+            }
+        }
+    }
+}
+
+/// Checks raw entries of a todo file for inconsistencies.
+///
+/// ```
+/// use serde_json::{Value, json};
+/// use toto::cli::TodoStatus;
+/// use toto::doctor::{self, Issue};
+/// use uuid::Uuid;
+///
+/// let (a, b, gone) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(9));
+/// let todo = |id: Uuid, status: &str, created: i64, completed: Value, parent: Value| {
+///     json!({
+///         "id": id,
+///         "data": {
+///             "title": "Fixture", "description": null, "priority": 255,
+///             "status": status, "created_at": created, "in_progress_at": null,
+///             "completed_at": completed, "deleted_at": null, "parent": parent,
+///         }
+///     })
+/// };
+/// let mut blocked = todo(b, "Completed", 2_000, json!(3_000), json!(gone));
+/// blocked["data"]["blocked_by"] = json!([a, gone]);
+/// let entries = [
+///     todo(a, "Completed", 2_000, Value::Null, Value::Null),
+///     blocked,
+///     todo(a, "Pending", 3_000, Value::Null, Value::Null),
+///     json!({ "id": "not a uuid" }),
+/// ];
+/// let issues = doctor::diagnose(&entries);
+/// assert_eq!(issues.len(), 5);
+/// assert_eq!(
+///     issues[0],
+///     Issue::MissingTimestamp { id: a, status: TodoStatus::Completed, field: "completed_at" }
+/// );
+/// assert!(matches!(issues[1], Issue::Malformed { index: 3, .. }));
+/// assert_eq!(issues[2], Issue::DuplicateId { id: a, count: 2 });
+/// assert_eq!(issues[3], Issue::MissingParent { id: b, parent: gone });
+/// assert_eq!(issues[4], Issue::MissingBlocker { id: b, blocker: gone });
+/// assert!(issues.iter().filter(|issue| !issue.is_fixable()).count() == 1);
+///
+/// // Repairing keeps the newer copy of `a`, unlinks `b` from its missing
+/// // parent and blocker and leaves the malformed entry.
+/// let repaired = doctor::repair(&entries);
+/// assert_eq!(repaired.len(), 3);
+/// assert_eq!(repaired[0]["data"]["blocked_by"], json!([a]));
+/// assert_eq!(doctor::diagnose(&repaired).len(), 1);
+/// ```
+pub fn diagnose(entries: &[Value]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut counts: HashMap<Uuid, usize> = HashMap::new();
+    let mut order = Vec::new();
+    let mut parents = Vec::new();
+    let mut blockers = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        match serde_json::from_value::<Todo>(entry.clone()) {
+            Ok(todo) => {
+                if let Some(field) = missing_timestamp(&todo.data) {
+                    issues.push(Issue::MissingTimestamp {
+                        id: todo.id,
+                        status: todo.data.status.clone(),
+                        field,
+                    });
+                }
+                if let Some((field, before)) = out_of_order(&todo.data) {
+                    issues.push(Issue::OutOfOrder {
+                        id: todo.id,
+                        field,
+                        before,
+                    });
+                }
+                if let Some(parent) = todo.data.parent {
+                    parents.push((todo.id, parent));
+                }
+                blockers.extend(
+                    todo.data
+                        .blocked_by
+                        .iter()
+                        .map(|&blocker| (todo.id, blocker)),
+                );
+                let count = counts.entry(todo.id).or_insert(0);
+                if *count == 0 {
+                    order.push(todo.id);
+                }
+                *count += 1;
+            }
+            Err(err) => issues.push(Issue::Malformed {
+                index,
+                error: err.to_string(),
+            }),
+        }
+    }
+
+    for id in order {
+        let count = counts[&id];
+        if count > 1 {
+            issues.push(Issue::DuplicateId { id, count });
+        }
+    }
+
+    for (id, parent) in parents {
+        if !counts.contains_key(&parent) {
+            issues.push(Issue::MissingParent { id, parent });
+        }
+    }
+
+    for (id, blocker) in blockers {
+        if !counts.contains_key(&blocker) {
+            issues.push(Issue::MissingBlocker { id, blocker });
+        }
+    }
+
+    issues
+}
+
+/// Fixes every fixable issue, returning the repaired entries.
+///
+/// Missing timestamps are filled with the latest timestamp the todo already
+/// has, links to missing parents and blockers are cleared, and of duplicated
+/// ids only the most recently changed entry is kept. Malformed entries are
+/// left untouched so no data is lost.
+pub fn repair(entries: &[Value]) -> Vec<Value> {
+    let todos: Vec<Result<Todo, Value>> = entries
+        .iter()
+        .map(
+            |entry| match serde_json::from_value::<Todo>(entry.clone()) {
+                Ok(mut todo) => {
+                    fill_missing_timestamp(&mut todo.data);
+                    Ok(todo)
+                }
+                Err(_) => Err(entry.clone()),
+            },
+        )
+        .collect();
+
+    let ids: HashSet<Uuid> = todos.iter().flatten().map(|todo| todo.id).collect();
+
+    // Remember which entry wins for each id.
+    let mut keep: HashMap<Uuid, usize> = HashMap::new();
+    for (index, todo) in todos.iter().enumerate() {
+        if let Ok(todo) = todo {
+            let newer = match keep.get(&todo.id) {
+                Some(&kept) => matches!(&todos[kept], Ok(other)
+                    if latest_timestamp(&todo.data) > latest_timestamp(&other.data)),
+                None => true,
+            };
+            if newer {
+                keep.insert(todo.id, index);
+            }
+        }
+    }
+
+    todos
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, todo)| match todo {
+            Ok(mut todo) if keep.get(&todo.id) == Some(&index) => {
+                if todo
+                    .data
+                    .parent
+                    .is_some_and(|parent| !ids.contains(&parent))
+                {
+                    todo.data.parent = None;
+                }
+                todo.data.blocked_by.retain(|blocker| ids.contains(blocker));
+                serde_json::to_value(todo).ok()
+            }
+            Ok(_) => None,
+            Err(raw) => Some(raw),
+        })
+        .collect()
+}
+
+/// The most recent lifecycle timestamp of a todo, counting `touch`.
+pub fn latest_timestamp(todo: &TodoData) -> DateTime<Utc> {
+    [
+        todo.in_progress_at,
+        todo.completed_at,
+        todo.deleted_at,
+        todo.last_touched_at,
+    ]
+    .into_iter()
+    .flatten()
+    .fold(todo.created_at, DateTime::max)
+}
+
+fn missing_timestamp(todo: &TodoData) -> Option<&'static str> {
+    match todo.status {
+        TodoStatus::InProgress if todo.in_progress_at.is_none() => Some("in_progress_at"),
+        TodoStatus::Completed if todo.completed_at.is_none() => Some("completed_at"),
+        TodoStatus::Deleted if todo.deleted_at.is_none() => Some("deleted_at"),
+        _ => None,
+    }
+}
+
+// The first lifecycle timestamp that lies before the one preceding it.
+// Starting is optional, so completion and deletion are checked against the
+// start when there is one and the creation otherwise.
+fn out_of_order(todo: &TodoData) -> Option<(&'static str, &'static str)> {
+    let mut previous = ("created_at", todo.created_at);
+    let stages = [
+        ("in_progress_at", todo.in_progress_at),
+        ("completed_at", todo.completed_at),
+    ];
+    for (field, timestamp) in stages {
+        if let Some(timestamp) = timestamp {
+            if timestamp < previous.1 {
+                return Some((field, previous.0));
+            }
+            previous = (field, timestamp);
+        }
+    }
+    match todo.deleted_at {
+        Some(deleted_at) if deleted_at < todo.created_at => Some(("deleted_at", "created_at")),
+        _ => None,
+    }
+}
+
+fn fill_missing_timestamp(todo: &mut TodoData) {
+    let latest = latest_timestamp(todo);
+    match todo.status {
+        TodoStatus::InProgress => {
+            todo.in_progress_at.get_or_insert(latest);
+        }
+        TodoStatus::Completed => {
+            todo.completed_at.get_or_insert(latest);
+        }
+        TodoStatus::Deleted => {
+            todo.deleted_at.get_or_insert(latest);
+        }
+        TodoStatus::Pending => {}
+    }
+}
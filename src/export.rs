@@ -0,0 +1,470 @@
+use chrono::{DateTime, Local, Utc};
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use crate::cli::{Todo, TodoStatus};
+use crate::time::DateStyle;
+
+/// Output formats supported by `export`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Emacs org-mode headlines.
+    Org,
+    /// A standalone HTML page with a table of the todos.
+    Html,
+    /// The todos as stored in todos.json, or flat with `--flatten`.
+    Json,
+}
+
+/// Renders todos in the requested format. `dates` applies where the format
+/// leaves it open; org timestamps always use org's own.
+pub fn render(format: ExportFormat, todos: &[Todo], dates: &DateStyle) -> String {
+    match format {
+        ExportFormat::Org => to_org(todos),
+        ExportFormat::Html => to_html(todos, dates, Utc::now()),
+        ExportFormat::Json => {
+            let mut json = to_json(todos, false).unwrap_or_default();
+            json.push('\n');
+            json
+        }
+    }
+}
+
+/// Renders todos as a JSON list of flat objects for analytics tools such as
+/// pandas or DuckDB: RFC 3339 timestamps, the status by name, tags as an
+/// array, and derived fields measured at `now`. `age_days` counts whole
+/// days since the todo was created and `duration_minutes` the minutes it
+/// has been, or was, in progress.
+///
+/// ```
+/// use chrono::{TimeDelta, Utc};
+/// use toto::{Cli, NewTodo};
+///
+/// let now = Utc::now();
+/// let mut cli = Cli::new(String::from("."));
+/// cli.add(NewTodo {
+///     created_at: Some(now - TimeDelta::days(3)),
+///     in_progress: true,
+///     started_at: Some(now - TimeDelta::minutes(90)),
+///     tags: vec![String::from("work")],
+///     ..NewTodo::new("Write the report")
+/// })?;
+/// let flat: serde_json::Value = serde_json::from_str(&toto::export::to_flat_json(&cli.todos(), now))?;
+/// let todo = &flat[0];
+/// assert_eq!(todo["status"], "in-progress");
+/// assert_eq!(todo["tags"], serde_json::json!(["work"]));
+/// assert_eq!(todo["age_days"], 3);
+/// assert_eq!(todo["duration_minutes"], 90);
+/// assert!(todo["completed_at"].is_null());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn to_flat_json(todos: &[Todo], now: DateTime<Utc>) -> String {
+    let timestamp = |dt: Option<DateTime<Utc>>| dt.map(|dt| dt.to_rfc3339());
+    let flat: Vec<Value> = todos
+        .iter()
+        .map(|todo| {
+            let data = &todo.data;
+            json!({
+                "id": todo.id,
+                "title": cap_line(&data.title),
+                "description": data.description.as_deref().map(cap_line),
+                "status": data.status.name(),
+                "priority": data.priority,
+                "tags": data.tags,
+                "parent": data.parent,
+                "blocked_by": data.blocked_by,
+                "notes": data.notes.len(),
+                "created_at": data.created_at.to_rfc3339(),
+                "started_at": timestamp(data.in_progress_at),
+                "completed_at": timestamp(data.completed_at),
+                "deleted_at": timestamp(data.deleted_at),
+                "due_at": timestamp(data.due_at),
+                "snoozed_until": timestamp(data.snoozed_until),
+                "last_touched_at": timestamp(data.last_touched_at),
+                "age_days": (now - data.created_at).num_days(),
+                "duration_minutes": data.in_progress_for(now).map(|took| took.num_minutes()),
+            })
+        })
+        .collect();
+    let mut json = serde_json::to_string_pretty(&flat).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+/// Renders todos as org-mode headlines. Pending and in-progress todos use the
+/// `TODO` keyword, completed ones `DONE` with a `CLOSED` timestamp, and
+/// deleted todos are left out.
+///
+/// ```
+/// use toto::cli::Cli;
+/// use uuid::Uuid;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-org-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let mut ids = (1..).map(Uuid::from_u128);
+/// let mut cli = Cli::new(path.clone()).with_id_generator(move || ids.next().unwrap());
+/// cli.run_from([
+///     "toto", "add", "--title", "Clean the gutters", "--priority", "0",
+///     "--description", "Both windows", "--tag", "home", "--at", "2024-03-01 09:30",
+///     "--done", "--completed-at", "2024-03-01 09:30",
+/// ])?;
+/// cli.run_from(["toto", "add", "--title", "Never mind", "--priority", "1"])?;
+/// cli.run_from(["toto", "delete", "--id", "1"])?;
+///
+/// let org = dir.join("todos.org");
+/// cli.run_from(["toto", "export", "--format", "org", "--output", &org.to_string_lossy()])?;
+/// assert_eq!(
+///     std::fs::read_to_string(&org)?,
+///     format!(
+///         "* DONE [#A] Clean the gutters :home:\n  \
+///          CLOSED: [2024-03-01 Fri 09:30]\n  \
+///          :PROPERTIES:\n  \
+///          :ID: {}\n  \
+///          :CREATED: [2024-03-01 Fri 09:30]\n  \
+///          :END:\n  \
+///          Both windows\n",
+///         Uuid::from_u128(1)
+///     )
+/// );
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn to_org(todos: &[Todo]) -> String {
+    let mut out = String::new();
+    for todo in todos {
+        let keyword = match todo.data.status {
+            TodoStatus::Pending | TodoStatus::InProgress => "TODO",
+            TodoStatus::Completed => "DONE",
+            TodoStatus::Deleted => continue,
+        };
+
+        out.push_str("* ");
+        out.push_str(keyword);
+        if let Some(cookie) = org_priority(todo.data.priority) {
+            out.push_str(&format!(" [#{}]", cookie));
+        }
+        out.push(' ');
+        out.push_str(cap_line(&todo.data.title));
+        if !todo.data.tags.is_empty() {
+            out.push_str(&format!(" :{}:", todo.data.tags.join(":")));
+        }
+        out.push('\n');
+
+        // Org keeps CLOSED and DEADLINE together on one planning line.
+        let mut planning = Vec::new();
+        if let Some(completed_at) = todo.data.completed_at
+            && todo.data.status == TodoStatus::Completed
+        {
+            planning.push(format!("CLOSED: {}", org_timestamp(completed_at)));
+        }
+        if let Some(due_at) = todo.data.due_at {
+            planning.push(format!("DEADLINE: {}", org_active_timestamp(due_at)));
+        }
+        if !planning.is_empty() {
+            out.push_str(&format!("  {}\n", planning.join(" ")));
+        }
+
+        out.push_str("  :PROPERTIES:\n");
+        out.push_str(&format!("  :ID: {}\n", todo.id));
+        out.push_str(&format!(
+            "  :CREATED: {}\n",
+            org_timestamp(todo.data.created_at)
+        ));
+        out.push_str("  :END:\n");
+
+        if let Some(description) = &todo.data.description {
+            for line in description.lines() {
+                out.push_str("  ");
+                out.push_str(cap_line(line));
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Renders todos as a JSON list in the format of `todos.json`, indented
+/// for reading or `compact` on a single line for piping. Titles and
+/// descriptions are cut to `MAX_LINE_BYTES`.
+///
+/// ```
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// cli.add(NewTodo::new("Book flights"))?;
+/// cli.add(NewTodo::new("Pack"))?;
+/// let todos = cli.todos();
+/// assert_eq!(toto::export::to_json(&todos, true)?.lines().count(), 1);
+/// assert!(toto::export::to_json(&todos, false)?.lines().count() > 1);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn to_json(todos: &[Todo], compact: bool) -> serde_json::Result<String> {
+    let capped: Vec<Todo> = todos.iter().map(capped).collect();
+    if compact {
+        serde_json::to_string(&capped)
+    } else {
+        serde_json::to_string_pretty(&capped)
+    }
+}
+
+// `todo` with its title and description cut to `MAX_LINE_BYTES`.
+fn capped(todo: &Todo) -> Todo {
+    let mut todo = todo.clone();
+    todo.data.title = cap_line(&todo.data.title).to_string();
+    if let Some(description) = &mut todo.data.description {
+        *description = cap_line(description).to_string();
+    }
+    todo
+}
+
+/// Renders one `ID UUID STATUS PRIORITY TITLE` record per todo for
+/// `list --plain`, the fields joined by `separator`. Each pair is a todo
+/// with its human id. Records end in a newline, or in a NUL when that is
+/// the separator, so `xargs -0` splits them too. A backslash, newline, NUL
+/// or `separator` in a title is escaped with a backslash, as
+/// `--field-separator` takes them, so every record splits the same way.
+///
+/// ```
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let id = cli.add(NewTodo { priority: 1, ..NewTodo::new("Eggs, milk\nand C:\\bread") })?;
+/// let todos = cli.todos();
+/// let plain = toto::export::to_plain(&[(0, &todos[0])], ",");
+/// assert_eq!(plain, format!("0,{},pending,1,Eggs\\, milk\\nand C:\\\\bread\n", id));
+/// assert_eq!(plain.lines().count(), 1);
+/// let tabbed = toto::export::to_plain(&[(0, &todos[0])], "\t");
+/// assert!(tabbed.ends_with("\tEggs, milk\\nand C:\\\\bread\n"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn to_plain(rows: &[(usize, &Todo)], separator: &str) -> String {
+    rows.iter()
+        .map(|(id, todo)| {
+            let fields = [
+                id.to_string(),
+                todo.id.to_string(),
+                todo.data.status.name().to_string(),
+                todo.data.priority.to_string(),
+                escape_field(cap_line(&todo.data.title), separator),
+            ];
+            format!("{}{}", fields.join(separator), record_end(separator))
+        })
+        .collect()
+}
+
+/// The ids of `todos` joined by `separator`, for `list --ids-only`, as one
+/// record ended the way `to_plain` ends them.
+///
+/// ```
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let first = cli.add(NewTodo { priority: 0, ..NewTodo::new("Pack") })?;
+/// let second = cli.add(NewTodo::new("Leave"))?;
+/// let ids = toto::export::to_ids(&cli.todos(), "\0");
+/// assert_eq!(ids.into_bytes(), format!("{}\0{}\0", first, second).into_bytes());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn to_ids(todos: &[Todo], separator: &str) -> String {
+    let ids: Vec<String> = todos.iter().map(|todo| todo.id.to_string()).collect();
+    if ids.is_empty() {
+        return String::new();
+    }
+    format!("{}{}", ids.join(separator), record_end(separator))
+}
+
+fn record_end(separator: &str) -> &'static str {
+    if separator == "\0" { "\0" } else { "\n" }
+}
+
+// `field` with backslashes, record ends and `separator` escaped.
+fn escape_field(field: &str, separator: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\0' => out.push_str("\\0"),
+            '\t' if separator == "\t" => out.push_str("\\t"),
+            c if separator.starts_with(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Renders todos as a standalone HTML page, styled inline so it still looks
+/// right when mailed, with one table row per todo colored by status and
+/// the time it was `generated_at`. Deleted todos are left out.
+///
+/// ```
+/// use chrono::Utc;
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// cli.add(NewTodo::new("Fix <br> & \"quotes\""))?;
+/// let html = toto::export::to_html(&cli.todos(), &Default::default(), Utc::now());
+/// assert!(html.contains("Fix &lt;br&gt; &amp; &quot;quotes&quot;"));
+/// assert!(!html.contains("<br>"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn to_html(todos: &[Todo], dates: &DateStyle, generated_at: DateTime<Utc>) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Todos</title>\n</head>\n\
+         <body style=\"font-family: sans-serif; margin: 2em;\">\n<h1>Todos</h1>\n",
+    );
+    out.push_str(&format!(
+        "<p style=\"color: #666;\">Generated {}</p>\n",
+        dates.date_time(generated_at)
+    ));
+    out.push_str("<table style=\"border-collapse: collapse;\">\n<tr>");
+    for header in ["Title", "Status", "Priority", "Tags", "Due", "Created"] {
+        out.push_str(&format!(
+            "<th style=\"{}text-align: left; border-bottom: 2px solid #999;\">{}</th>",
+            HTML_CELL_STYLE, header
+        ));
+    }
+    out.push_str("</tr>\n");
+
+    let date = |dt: DateTime<Utc>| dates.date(dt);
+    for todo in todos {
+        let data = &todo.data;
+        let color = match data.status {
+            TodoStatus::Pending => "#1f6feb",
+            TodoStatus::InProgress => "#bf8700",
+            TodoStatus::Completed => "#1a7f37",
+            TodoStatus::Deleted => continue,
+        };
+        let mut title = escape_html(cap_line(&data.title));
+        if let Some(description) = &data.description {
+            title.push_str(&format!(
+                "<br><small style=\"color: #666;\">{}</small>",
+                escape_html(cap_line(description)).replace('\n', "<br>")
+            ));
+        }
+        let cells = [
+            title,
+            format!(
+                "<span style=\"color: {}; font-weight: bold;\">{}</span>",
+                color,
+                data.status.name()
+            ),
+            data.priority.to_string(),
+            escape_html(&data.tags.join(", ")),
+            data.due_at.map(date).unwrap_or_default(),
+            date(data.created_at),
+        ];
+        out.push_str("<tr>");
+        for cell in cells {
+            out.push_str(&format!(
+                "<td style=\"{}border-bottom: 1px solid #ddd;\">{}</td>",
+                HTML_CELL_STYLE, cell
+            ));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+const HTML_CELL_STYLE: &str = "padding: 4px 12px; vertical-align: top; ";
+
+/// Escapes the characters that are markup in HTML text and attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Longest line a renderer writes for any single field. Titles are capped
+/// on input, but data written before the cap (or with a raised one) and
+/// descriptions are not, and some readers choke on enormous lines. The
+/// exports, `list --plain`, `--json` and `--no-truncate` all cut fields to it.
+///
+/// ```
+/// use toto::export::MAX_LINE_BYTES;
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-line-bytes-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> anyhow::Result<String> {
+///     let out = Buffer::default();
+///     Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))?;
+///     Ok(out.contents())
+/// };
+/// let title = "x".repeat(MAX_LINE_BYTES + 100);
+/// run(&["--max-title-bytes", "10000", "add", "--title", &title, "--priority", "1"])?;
+///
+/// let (capped, over) = ("x".repeat(MAX_LINE_BYTES), "x".repeat(MAX_LINE_BYTES + 1));
+/// for args in [
+///     &["list", "--plain"][..],
+///     &["list", "--json"],
+///     &["list", "--no-truncate"],
+///     &["export", "--format", "json"],
+///     &["export", "--format", "json", "--flatten"],
+///     &["export", "--format", "org"],
+/// ] {
+///     let out = run(args)?;
+///     assert!(out.contains(&capped) && !out.contains(&over), "{:?}", args);
+/// }
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub const MAX_LINE_BYTES: usize = 4096;
+
+// Cuts `line` down to `MAX_LINE_BYTES`, backing off to a char boundary.
+pub(crate) fn cap_line(line: &str) -> &str {
+    if line.len() <= MAX_LINE_BYTES {
+        return line;
+    }
+    let mut end = MAX_LINE_BYTES;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Maps a numeric priority onto org's `A`/`B`/`C` cookies by splitting the
+/// range into thirds, lower numbers being more important. The default
+/// priority of 255 means "unprioritised" and gets no cookie.
+pub fn org_priority(priority: u8) -> Option<char> {
+    match priority {
+        0..=84 => Some('A'),
+        85..=169 => Some('B'),
+        170..=254 => Some('C'),
+        255 => None,
+    }
+}
+
+// Org inactive timestamps look like `[2024-03-01 Fri 09:30]`.
+fn org_timestamp(dt: DateTime<Utc>) -> String {
+    dt.with_timezone(&Local)
+        .format("[%Y-%m-%d %a %H:%M]")
+        .to_string()
+}
+
+// Active timestamps use angle brackets and show up in the org agenda.
+fn org_active_timestamp(dt: DateTime<Utc>) -> String {
+    dt.with_timezone(&Local)
+        .format("<%Y-%m-%d %a %H:%M>")
+        .to_string()
+}
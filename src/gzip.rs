@@ -0,0 +1,144 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+use anyhow::Result;
+
+/// Whether this build can read and write gzip files, which needs the
+/// `gzip` feature. Its doctests only run in full with
+/// `cargo test --features gzip`.
+pub const AVAILABLE: bool = cfg!(feature = "gzip");
+
+/// The first two bytes of every gzip stream.
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether `contents`, read from `path`, are gzip-compressed, going by the
+/// magic bytes or else a `.gz` extension.
+pub fn is_gzip(path: &Path, contents: &[u8]) -> bool {
+    contents.starts_with(&MAGIC) || path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// `contents` as read from `path`, decompressed if they are gzip.
+///
+/// ```
+/// use toto::output::Output;
+/// use toto::{Cli, NewTodo};
+///
+/// # if !toto::gzip::AVAILABLE {
+/// #     return Ok(());
+/// # }
+/// let dir = std::env::temp_dir().join(format!("toto-doc-gzip-{}", std::process::id()));
+/// std::fs::create_dir_all(dir.join("here"))?;
+/// std::fs::create_dir_all(dir.join("there"))?;
+/// let here = dir.join("here").to_string_lossy().into_owned();
+/// let there = dir.join("there").to_string_lossy().into_owned();
+/// let backup = dir.join("backup.json.gz");
+///
+/// let mut cli = Cli::new(here.clone());
+/// cli.add(NewTodo::new("Renew passport"))?;
+/// cli.add(NewTodo { priority: 0, ..NewTodo::new("Book flights") })?;
+/// cli.save()?;
+/// let run = |path: &str, args: &[&str]| {
+///     Cli::new(path.to_string())
+///         .with_output(Output::new(std::io::sink(), std::io::sink()))
+///         .run_from(args.iter().copied())
+/// };
+/// let backup_arg = backup.to_string_lossy();
+/// run(&here, &["toto", "export", "-f", "json", "--gzip", "-o", &backup_arg])?;
+/// assert!(std::fs::read(&backup)?.starts_with(&[0x1f, 0x8b]));
+/// run(&there, &["toto", "import", &backup_arg])?;
+///
+/// let (mut exported, mut imported) = (Cli::new(here), Cli::new(there));
+/// exported.load()?;
+/// imported.load()?;
+/// let all = |cli: &Cli| -> Vec<_> { cli.todos().into_iter().map(|t| (t.id, t.data)).collect() };
+/// assert_eq!(all(&imported), all(&exported));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// Without the feature, exporting with `--gzip` and importing a gzip file
+/// both fail without writing anything:
+///
+/// ```
+/// use toto::output::Output;
+/// use toto::{Cli, NewTodo};
+///
+/// # if toto::gzip::AVAILABLE {
+/// #     return Ok(());
+/// # }
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-no-gzip-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let mut cli = Cli::new(path.clone());
+/// cli.add(NewTodo::new("Renew passport"))?;
+/// cli.save()?;
+/// let before = std::fs::read(dir.join("todos.json"))?;
+/// let run = |args: &[&str]| {
+///     Cli::new(path.clone())
+///         .with_output(Output::new(std::io::sink(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))
+/// };
+///
+/// let backup = dir.join("backup.json.gz");
+/// let backup_arg = backup.to_string_lossy();
+/// let error = run(&["export", "-f", "json", "--gzip", "-o", &backup_arg]).unwrap_err();
+/// assert!(error.to_string().contains("--features gzip"));
+/// assert!(!backup.exists());
+///
+/// std::fs::write(&backup, [0x1f, 0x8b, 0x08, 0x00])?;
+/// let error = run(&["import", &backup_arg]).unwrap_err();
+/// assert!(format!("{error:#}").contains("Decompressing needs the gzip feature"));
+/// assert_eq!(std::fs::read(dir.join("todos.json"))?, before);
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn decode<'a>(path: &Path, contents: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+    if is_gzip(path, contents) {
+        decompress(contents).map(Cow::Owned)
+    } else {
+        Ok(Cow::Borrowed(contents))
+    }
+}
+
+/// Compresses `data` with gzip.
+#[cfg(feature = "gzip")]
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Write;
+
+    use anyhow::Context;
+    use flate2::{Compression, write::GzEncoder};
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|()| encoder.finish())
+        .context("Failed to compress")
+}
+
+/// Compresses `data` with gzip.
+#[cfg(not(feature = "gzip"))]
+pub fn compress(_data: &[u8]) -> Result<Vec<u8>> {
+    anyhow::bail!("Compressing needs the gzip feature")
+}
+
+/// Decompresses the gzip stream `data`.
+#[cfg(feature = "gzip")]
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    use anyhow::Context;
+    use flate2::read::GzDecoder;
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut decompressed)
+        .context("Failed to decompress")?;
+    Ok(decompressed)
+}
+
+/// Decompresses the gzip stream `data`.
+#[cfg(not(feature = "gzip"))]
+pub fn decompress(_data: &[u8]) -> Result<Vec<u8>> {
+    anyhow::bail!("Decompressing needs the gzip feature")
+}
@@ -0,0 +1,178 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The sidecar holding the checksum of `file`, e.g. `todos.json.sha256`.
+pub fn sidecar(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// Writes the checksum of `contents`, just written to `file`, next to it in
+/// the format `sha256sum` reads, so `sha256sum -c todos.json.sha256` works
+/// from the data directory too.
+pub fn record(file: &Path, contents: &[u8]) -> io::Result<()> {
+    let name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    fs::write(
+        sidecar(file),
+        format!("{}  {}\n", sha256_hex(contents), name),
+    )
+}
+
+/// Whether `contents`, just read from `file`, match the checksum recorded
+/// when it was last saved. A file without a sidecar, e.g. one written by an
+/// older version, passes.
+pub fn verify(file: &Path, contents: &[u8]) -> io::Result<bool> {
+    let recorded = match fs::read_to_string(sidecar(file)) {
+        Ok(recorded) => recorded,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(true),
+        Err(err) => return Err(err),
+    };
+    let expected = recorded.split_whitespace().next().unwrap_or_default();
+    Ok(expected.eq_ignore_ascii_case(&sha256_hex(contents)))
+}
+
+/// The SHA-256 digest of `data` as lowercase hex.
+///
+/// The FIPS 180-4 examples, which between them cover an empty message, one
+/// block, and padding that spills into a second block:
+///
+/// ```
+/// use toto::integrity::sha256_hex;
+///
+/// assert_eq!(
+///     sha256_hex(b""),
+///     "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+/// );
+/// assert_eq!(
+///     sha256_hex(b"abc"),
+///     "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+/// );
+/// // 56 bytes, so the length no longer fits in the first block.
+/// assert_eq!(
+///     sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+///     "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+/// );
+/// // 112 bytes, two full blocks of message.
+/// assert_eq!(
+///     sha256_hex(
+///         b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmno\
+///           ijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu"
+///     ),
+///     "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1"
+/// );
+/// // Exactly one block, padded into a second.
+/// assert_eq!(
+///     sha256_hex(&[b'a'; 64]),
+///     "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"
+/// );
+/// assert_eq!(
+///     sha256_hex(&vec![b'a'; 1_000_000]),
+///     "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+/// );
+/// ```
+///
+/// Every length around the block and padding boundaries agrees with
+/// `sha256sum`, which is what `sha256sum -c` checks the sidecar with:
+///
+/// ```
+/// use std::io::Write;
+/// use std::process::{Command, Stdio};
+/// use toto::integrity::sha256_hex;
+///
+/// for len in (0..=130).chain([255, 256, 257, 1023, 1024, 1025]) {
+///     let data: Vec<u8> = (0..len).map(|i| (i * 7 + 3) as u8).collect();
+///     let mut child = Command::new("sha256sum")
+///         .stdin(Stdio::piped())
+///         .stdout(Stdio::piped())
+///         .spawn()?;
+///     child.stdin.take().unwrap().write_all(&data)?;
+///     let output = child.wait_with_output()?;
+///     let expected = String::from_utf8(output.stdout)?;
+///     assert_eq!(sha256_hex(&data), expected.split_whitespace().next().unwrap(), "length {}", len);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn sha256_hex(data: &[u8]) -> String {
+    sha256(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// A plain FIPS 180-4 implementation. The file is small and read once per
+// run, so speed doesn't matter and a dependency isn't worth it.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for (k, w) in K.iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (chunk, state) in digest.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
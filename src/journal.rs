@@ -0,0 +1,224 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::cli::Todo;
+use crate::diff::{self, Change};
+use crate::time::DateStyle;
+
+/// Name of the journal file inside the data directory.
+pub const JOURNAL_FILE: &str = "journal.json";
+
+/// How many operations `undo` can go back.
+pub const JOURNAL_LIMIT: usize = 20;
+
+/// One operation that changed the list, with what the list looked like
+/// before it so `undo` can put it back.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    /// The command that made the change, e.g. `complete`.
+    pub operation: String,
+    /// Titles of the todos it added, changed or removed.
+    pub titles: Vec<String>,
+    pub before: Vec<Todo>,
+}
+
+impl Entry {
+    /// The entry for `operation` turning `before` into `after`, or `None`
+    /// when it changed nothing.
+    pub fn new(
+        operation: &str,
+        at: DateTime<Utc>,
+        before: Vec<Todo>,
+        after: &[Todo],
+    ) -> Option<Entry> {
+        let titles: Vec<String> = diff::diff(&before, after)
+            .into_iter()
+            .map(|change| match change {
+                Change::Added(todo) | Change::Removed(todo) => todo.data.title,
+                Change::Modified { title, .. } => title,
+            })
+            .collect();
+        if titles.is_empty() {
+            return None;
+        }
+        Some(Entry {
+            at,
+            operation: operation.to_string(),
+            titles,
+            before,
+        })
+    }
+}
+
+pub fn path(data_dir: &str) -> PathBuf {
+    PathBuf::from(data_dir).join(JOURNAL_FILE)
+}
+
+/// Reads the journal in `data_dir`, oldest entry first. A missing file is
+/// an empty journal.
+pub fn load(data_dir: &str) -> Result<Vec<Entry>> {
+    let path = path(data_dir);
+    match fs::read(&path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+pub fn save(data_dir: &str, entries: &[Entry]) -> Result<()> {
+    let path = path(data_dir);
+    let contents = serde_json::to_vec(entries).context("Failed to serialize the journal")?;
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Appends `entry`, dropping the oldest ones past `JOURNAL_LIMIT`.
+pub fn record(entries: &mut Vec<Entry>, entry: Entry) {
+    entries.push(entry);
+    let excess = entries.len().saturating_sub(JOURNAL_LIMIT);
+    entries.drain(..excess);
+}
+
+/// One line per entry, most recent first and numbered from 1 the way
+/// `undo --to` counts them.
+///
+/// ```
+/// use chrono::{FixedOffset, TimeZone, Utc};
+/// use toto::journal::{self, Entry};
+/// use toto::time::{DateStyle, Tz};
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let at = Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+/// let mut entries = Vec::new();
+/// let before = cli.todos();
+/// let id = cli.add(NewTodo::new("Water the plants"))?;
+/// journal::record(&mut entries, Entry::new("add", at, before, &cli.todos()).unwrap());
+/// let before = cli.todos();
+/// cli.complete(id, None)?;
+/// let later = at + chrono::TimeDelta::hours(1);
+/// journal::record(&mut entries, Entry::new("complete", later, before, &cli.todos()).unwrap());
+///
+/// let utc = DateStyle {
+///     tz: Tz::Fixed(FixedOffset::east_opt(0).unwrap()),
+///     ..DateStyle::default()
+/// };
+/// assert_eq!(
+///     journal::render_list(&entries, &utc),
+///     "1  2024-05-01 10:30  complete  Water the plants\n\
+///      2  2024-05-01 09:30  add  Water the plants\n"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn render_list(entries: &[Entry], dates: &DateStyle) -> String {
+    entries
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, entry)| {
+            format!(
+                "{}  {}  {}  {}\n",
+                index + 1,
+                dates.date_time(entry.at),
+                entry.operation,
+                entry.titles.join(", ")
+            )
+        })
+        .collect()
+}
+
+/// One journaled change to a single todo.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub at: DateTime<Utc>,
+    pub operation: String,
+    pub change: Change,
+}
+
+/// The journaled changes to the todo `id`, oldest first. `current` is the
+/// list as it is now, which the most recent entry led to.
+pub fn history(entries: &[Entry], current: &[Todo], id: Uuid) -> Vec<Step> {
+    let only = |todos: &[Todo]| -> Vec<Todo> {
+        todos.iter().filter(|todo| todo.id == id).cloned().collect()
+    };
+    let afters = entries
+        .iter()
+        .skip(1)
+        .map(|entry| entry.before.as_slice())
+        .chain([current]);
+    entries
+        .iter()
+        .zip(afters)
+        .flat_map(|(entry, after)| {
+            diff::diff(&only(&entry.before), &only(after))
+                .into_iter()
+                .map(|change| Step {
+                    at: entry.at,
+                    operation: entry.operation.clone(),
+                    change,
+                })
+        })
+        .collect()
+}
+
+/// One line per step, oldest first, e.g.
+/// `2024-05-01 10:30  set-priority  priority 255 -> 0`.
+///
+/// ```
+/// use chrono::{FixedOffset, TimeDelta, TimeZone, Utc};
+/// use toto::journal::{self, Entry};
+/// use toto::time::{DateStyle, Tz};
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let at = Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+/// let mut entries = Vec::new();
+/// let before = cli.todos();
+/// let id = cli.add(NewTodo::new("Water the plants"))?;
+/// journal::record(&mut entries, Entry::new("add", at, before, &cli.todos()).unwrap());
+/// let before = cli.todos();
+/// cli.set_priority(id, 0)?;
+/// let later = at + TimeDelta::hours(1);
+/// journal::record(&mut entries, Entry::new("set-priority", later, before, &cli.todos()).unwrap());
+///
+/// let utc = DateStyle {
+///     tz: Tz::Fixed(FixedOffset::east_opt(0).unwrap()),
+///     ..DateStyle::default()
+/// };
+/// let steps = journal::history(&entries, &cli.todos(), id);
+/// assert_eq!(
+///     journal::render_history(&steps, &utc),
+///     "2024-05-01 09:30  add  added \"Water the plants\"\n\
+///      2024-05-01 10:30  set-priority  priority 255 -> 0\n"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn render_history(steps: &[Step], dates: &DateStyle) -> String {
+    steps
+        .iter()
+        .map(|step| {
+            let what = match &step.change {
+                Change::Added(todo) => format!("added \"{}\"", todo.data.title),
+                Change::Removed(_) => String::from("removed"),
+                Change::Modified { fields, .. } => fields
+                    .iter()
+                    .map(|field| format!("{} {} -> {}", field.field, field.old, field.new))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+            format!(
+                "{}  {}  {}\n",
+                dates.date_time(step.at),
+                step.operation,
+                what
+            )
+        })
+        .collect()
+}
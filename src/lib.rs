@@ -1,3 +1,36 @@
+//! A small todo list kept in a JSON file, usable as a command line tool or
+//! as a library.
+//!
+//! ```
+//! use toto::{Cli, NewTodo, Outcome};
+//!
+//! let mut cli = Cli::new(String::from("."));
+//! let id = cli.add(NewTodo::new("Write the report"))?;
+//! cli.start(id)?;
+//! assert!(matches!(cli.complete(id, None)?, Outcome::Completed { remaining: 0, .. }));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
 #[allow(clippy::too_many_arguments)]
 pub mod cli;
+pub mod config;
+pub mod diff;
+pub mod doctor;
+pub mod export;
+pub mod gzip;
+pub mod integrity;
+pub mod journal;
+pub mod merge;
+pub mod outcome;
+pub mod output;
+pub mod prompt;
+pub mod remind;
+pub mod stats;
+pub mod sweep;
 pub mod term;
+pub mod time;
+pub mod urgency;
+
+pub use cli::{Cli, ListFilter, NewTodo, Selection, SortKey, Todo, TodoData, TodoStatus};
+pub use config::Config;
+pub use outcome::Outcome;
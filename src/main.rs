@@ -2,6 +2,12 @@ use toto::cli::Cli;
 
 fn main() {
     let file_path = "/Users/tydelargy/.toto".to_string();
-    let res = Cli::new(file_path).run();
-    print!("{:?}", res);
+    if let Err(err) = Cli::new(file_path).run() {
+        // Usage errors, --help and --version are printed the way clap does.
+        if let Some(err) = err.downcast_ref::<clap::Error>() {
+            err.exit();
+        }
+        eprintln!("Error: {:?}", err);
+        std::process::exit(1);
+    }
 }
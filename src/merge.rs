@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use uuid::Uuid;
+
+use crate::cli::{Todo, TodoData};
+use crate::doctor;
+
+/// How a todo that exists locally and in incoming data, with different
+/// contents, is settled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Keep the copy with the latest lifecycle timestamp, the local one on
+    /// a tie.
+    #[default]
+    NewestWins,
+    KeepLocal,
+    KeepIncoming,
+    /// Ask which copy to keep for every conflict.
+    Interactive,
+}
+
+/// One of the two copies of a conflicting todo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Incoming,
+}
+
+/// Picks the copy to keep. `ask` is only called by `Strategy::Interactive`.
+///
+/// ```
+/// use toto::merge::{self, Side, Strategy};
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let id = cli.add(NewTodo::new("Renew passport"))?;
+/// let local = cli.todo(id).unwrap().data;
+/// cli.start(id)?;
+/// let incoming = cli.todo(id).unwrap().data;
+///
+/// let pick = |strategy| merge::resolve(strategy, &local, &incoming, || Ok(Side::Local));
+/// assert_eq!(pick(Strategy::NewestWins)?, Side::Incoming);
+/// assert_eq!(pick(Strategy::KeepLocal)?, Side::Local);
+/// assert_eq!(pick(Strategy::KeepIncoming)?, Side::Incoming);
+/// assert_eq!(pick(Strategy::Interactive)?, Side::Local);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn resolve(
+    strategy: Strategy,
+    local: &TodoData,
+    incoming: &TodoData,
+    ask: impl FnOnce() -> Result<Side>,
+) -> Result<Side> {
+    Ok(match strategy {
+        Strategy::NewestWins => {
+            if doctor::latest_timestamp(incoming) > doctor::latest_timestamp(local) {
+                Side::Incoming
+            } else {
+                Side::Local
+            }
+        }
+        Strategy::KeepLocal => Side::Local,
+        Strategy::KeepIncoming => Side::Incoming,
+        Strategy::Interactive => ask()?,
+    })
+}
+
+/// The active and archived todos as one list ordered by creation date, for
+/// `list --flat-archived`. Each id shows up once: a todo that is both active
+/// and archived, e.g. one restored after it was archived, keeps its active
+/// copy, and of several archived copies the one in the latest archive wins.
+/// Active todos come with their position in `active`.
+///
+/// ```
+/// use toto::merge;
+/// use toto::{Cli, NewTodo, TodoStatus};
+///
+/// // Archived once completed, then reopened.
+/// let mut cli = Cli::new(String::from("."));
+/// let restored = cli.add(NewTodo::new("Renew passport"))?;
+/// cli.complete(restored, None)?;
+/// let mut archived = vec![cli.todo(restored).unwrap()];
+/// cli.reopen(restored)?;
+///
+/// let mut old = Cli::new(String::from("."));
+/// let done = old.add(NewTodo { done: true, ..NewTodo::new("Book flights") })?;
+/// archived.push(old.todo(done).unwrap());
+///
+/// let merged = merge::with_archived(cli.todos(), archived);
+/// let rows: Vec<_> = merged
+///     .iter()
+///     .map(|(position, todo)| (*position, todo.id, todo.data.status.clone()))
+///     .collect();
+/// assert_eq!(
+///     rows,
+///     [
+///         (Some(0), restored, TodoStatus::InProgress),
+///         (None, done, TodoStatus::Completed),
+///     ]
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// It is also still `cli::with_archived`. Rows are ordered by creation
+/// date whichever list they come from:
+///
+/// ```
+/// use toto::cli::{self, Todo, TodoStatus};
+///
+/// let todo = |id: &str, title: &str, created_at: i64, status: &str| -> Todo {
+///     serde_json::from_value(serde_json::json!({
+///         "id": id,
+///         "data": {
+///             "title": title,
+///             "description": null,
+///             "priority": 2,
+///             "status": status,
+///             "created_at": created_at,
+///             "in_progress_at": null,
+///             "completed_at": null,
+///             "deleted_at": null,
+///         },
+///     }))
+///     .unwrap()
+/// };
+/// let alpha = "a1000000-0000-4000-8000-000000000000";
+/// let active = vec![
+///     todo(alpha, "Alpha", 300, "Pending"),
+///     todo("b2000000-0000-4000-8000-000000000000", "Bravo", 400, "Pending"),
+/// ];
+/// // Alpha was archived once and then restored, Zulu is only archived.
+/// let archived = vec![
+///     todo(alpha, "Alpha", 300, "Completed"),
+///     todo("f9000000-0000-4000-8000-000000000000", "Zulu", 100, "Completed"),
+/// ];
+///
+/// let rows: Vec<_> = cli::with_archived(active, archived)
+///     .into_iter()
+///     .map(|(position, todo)| (position, todo.data.title, todo.data.status))
+///     .collect();
+/// assert_eq!(
+///     rows,
+///     [
+///         (None, String::from("Zulu"), TodoStatus::Completed),
+///         (Some(0), String::from("Alpha"), TodoStatus::Pending),
+///         (Some(1), String::from("Bravo"), TodoStatus::Pending),
+///     ]
+/// );
+/// ```
+pub fn with_archived(active: Vec<Todo>, archived: Vec<Todo>) -> Vec<(Option<usize>, Todo)> {
+    let mut merged: HashMap<Uuid, (Option<usize>, Todo)> = HashMap::new();
+    for todo in archived {
+        merged.insert(todo.id, (None, todo));
+    }
+    for (position, todo) in active.into_iter().enumerate() {
+        merged.insert(todo.id, (Some(position), todo));
+    }
+    let mut rows: Vec<(Option<usize>, Todo)> = merged.into_values().collect();
+    rows.sort_by(|(_, a), (_, b)| {
+        a.data
+            .created_at
+            .cmp(&b.data.created_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    rows
+}
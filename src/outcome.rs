@@ -0,0 +1,224 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::time::DateStyle;
+
+/// What a command handler did, returned instead of printed so callers can
+/// inspect it. `Cli::run` turns it into the message the user sees.
+///
+/// ```
+/// use toto::outcome::Outcome;
+/// use toto::time::DateStyle;
+///
+/// let id = uuid::Uuid::from_u128(1);
+/// let message = |outcome: Outcome| outcome.message(&DateStyle::default());
+/// assert_eq!(message(Outcome::Added(id)), None);
+/// assert_eq!(message(Outcome::BulkAdded(3)).as_deref(), Some("Created 3 todo(s)."));
+/// assert_eq!(message(Outcome::AlreadyDeleted(id)).as_deref(), Some("Todo is already deleted"));
+/// let normalized = Outcome::PrioritiesNormalized(vec![(4, 1), (9, 2)]);
+/// assert_eq!(message(normalized).as_deref(), Some("4 -> 1\n9 -> 2"));
+/// assert!(Outcome::NotFound.is_error() && !Outcome::Deleted(id).is_error());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Added(Uuid),
+    /// `add --dedupe` folded the new todo into this existing one.
+    Merged(Uuid),
+    BulkAdded(usize),
+    Updated(Uuid),
+    Started(Uuid),
+    /// `remaining` counts the pending and in-progress todos left afterwards.
+    /// `parents` are the ancestors completed along with it, nearest first.
+    Completed {
+        id: Uuid,
+        remaining: usize,
+        parents: Vec<Uuid>,
+    },
+    AlreadyCompleted(Uuid),
+    Reopened(Uuid),
+    Snoozed {
+        id: Uuid,
+        until: DateTime<Utc>,
+    },
+    /// Not snoozed: it would have come back after it is due.
+    SnoozedPastDue {
+        id: Uuid,
+        due_at: DateTime<Utc>,
+    },
+    Deleted(Uuid),
+    AlreadyDeleted(Uuid),
+    /// `import` added `added` todos and, of those it already had, replaced
+    /// `replaced` with the incoming copy and kept `kept` as they were.
+    Imported {
+        added: usize,
+        replaced: usize,
+        kept: usize,
+    },
+    PriorityChanged {
+        id: Uuid,
+        from: u8,
+        to: u8,
+    },
+    /// `set-due` or `clear-due`, `None` being no due date.
+    DueChanged {
+        id: Uuid,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    },
+    /// Old and new value of every priority that changed.
+    PrioritiesNormalized(Vec<(u8, u8)>),
+    /// `recover` restored the todo from this commit.
+    Recovered {
+        id: Uuid,
+        commit: String,
+        /// It was finished in that commit and is open again.
+        reopened: bool,
+    },
+    /// The id was valid but no active todo has it.
+    NotFound,
+}
+
+impl Outcome {
+    /// The message to show for this outcome, if any, with times shown in
+    /// `dates`. Adding, updating and starting are silent like most unix
+    /// tools.
+    ///
+    /// ```
+    /// use toto::outcome::Outcome;
+    /// use toto::time::DateStyle;
+    ///
+    /// let message = |remaining| {
+    ///     let id = uuid::Uuid::from_u128(1);
+    ///     Outcome::Completed { id, remaining, parents: vec![] }.message(&DateStyle::default()).unwrap()
+    /// };
+    /// assert_eq!(message(2), "2 todos left.");
+    /// assert_eq!(message(1), "1 todo left.");
+    /// assert_eq!(message(0), "Inbox zero! Nothing left to do.");
+    /// ```
+    ///
+    /// `complete` reports it once the last open todo is done:
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-inbox-zero-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let out = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok(out.contents())
+    /// };
+    /// run(&["add", "--title", "Milk", "--priority", "1"])?;
+    /// run(&["add", "--title", "Eggs", "--priority", "1"])?;
+    /// run(&["add", "--title", "Bread", "--priority", "1"])?;
+    /// run(&["delete", "--id", "2"])?;
+    /// assert_eq!(run(&["complete", "--id", "0"])?, "1 todo left.\n");
+    /// assert_eq!(run(&["complete", "--id", "1"])?, "Inbox zero! Nothing left to do.\n");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// Due dates and snoozes are shown in the configured `date_format` and
+    /// `tz`:
+    ///
+    /// ```
+    /// use toto::output::{Buffer, Output};
+    /// use toto::Cli;
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("toto-doc-due-style-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.to_string_lossy().into_owned();
+    /// std::fs::write(dir.join("config.json"), r#"{"date_format": "%d/%m/%Y", "tz": "+02:00"}"#)?;
+    /// let run = |args: &[&str]| -> anyhow::Result<String> {
+    ///     let out = Buffer::default();
+    ///     Cli::new(path.clone())
+    ///         .with_output(Output::new(out.clone(), std::io::sink()))
+    ///         .run_from(["toto"].iter().chain(args))?;
+    ///     Ok(out.contents())
+    /// };
+    /// run(&["add", "--title", "Renew passport"])?;
+    /// assert_eq!(
+    ///     run(&["set-due", "0", "2024-03-01T09:30:00Z"])?,
+    ///     "Due none -> 01/03/2024 11:30\n"
+    /// );
+    /// assert_eq!(run(&["clear-due", "0"])?, "Due 01/03/2024 11:30 -> none\n");
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn message(&self, dates: &DateStyle) -> Option<String> {
+        match self {
+            Outcome::Added(_) | Outcome::Updated(_) | Outcome::Started(_) => None,
+            Outcome::Merged(id) => Some(format!("Merged into existing todo {}", id)),
+            Outcome::BulkAdded(count) => Some(format!("Created {} todo(s).", count)),
+            Outcome::Imported {
+                added,
+                replaced,
+                kept,
+            } => Some(format!(
+                "Imported {} new todo(s), replaced {} and kept {} local one(s).",
+                added, replaced, kept
+            )),
+            Outcome::Completed { remaining, .. } => Some(match remaining {
+                0 => String::from("Inbox zero! Nothing left to do."),
+                1 => String::from("1 todo left."),
+                n => format!("{} todos left.", n),
+            }),
+            Outcome::Reopened(_) => Some(String::from("Todo reopened and back in progress")),
+            Outcome::Snoozed { until, .. } => {
+                Some(format!("Snoozed until {}.", dates.date_time(*until)))
+            }
+            Outcome::SnoozedPastDue { due_at, .. } => Some(format!(
+                "Warning: the todo is due {} and would come back already overdue. Pass --force to snooze it anyway.",
+                dates.date_time(*due_at)
+            )),
+            Outcome::AlreadyCompleted(_) => Some(String::from("Todo is already completed")),
+            Outcome::Deleted(_) => None,
+            Outcome::AlreadyDeleted(_) => Some(String::from("Todo is already deleted")),
+            Outcome::PrioritiesNormalized(changed) if changed.is_empty() => {
+                Some(String::from("Priorities are already normalized."))
+            }
+            Outcome::PrioritiesNormalized(changed) => Some(
+                changed
+                    .iter()
+                    .map(|(old, new)| format!("{} -> {}", old, new))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Outcome::PriorityChanged { from, to, .. } => {
+                Some(format!("Priority {} -> {}", from, to))
+            }
+            Outcome::DueChanged { from, to, .. } => {
+                let due = |at: &Option<DateTime<Utc>>| match at {
+                    Some(at) => dates.date_time(*at),
+                    None => String::from("none"),
+                };
+                Some(format!("Due {} -> {}", due(from), due(to)))
+            }
+            Outcome::Recovered {
+                id,
+                commit,
+                reopened: false,
+            } => Some(format!("Recovered todo {} from commit {}.", id, commit)),
+            Outcome::Recovered {
+                id,
+                commit,
+                reopened: true,
+            } => Some(format!(
+                "Recovered todo {} from commit {} and reopened it.",
+                id, commit
+            )),
+            Outcome::NotFound => Some(String::from("Todo not found")),
+        }
+    }
+
+    /// Whether the message belongs on stderr rather than stdout.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Outcome::NotFound | Outcome::SnoozedPastDue { .. })
+    }
+}
@@ -0,0 +1,110 @@
+use std::cell::{RefCell, RefMut};
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+/// The streams command handlers write to: `out` for results and `err` for
+/// warnings and problems, plus `input` that prompts read answers from.
+/// `Cli` writes everything through one of these instead of printing, so
+/// output can be captured or redirected.
+///
+/// ```
+/// use toto::Cli;
+/// use toto::output::{Buffer, Output};
+///
+/// let out = Buffer::default();
+/// let mut cli = Cli::new(String::from(".")).with_output(Output::new(out.clone(), std::io::sink()));
+/// cli.report(&toto::Outcome::BulkAdded(3))?;
+/// assert_eq!(out.contents(), "Created 3 todo(s).\n");
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-output-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let (out, err) = (Buffer::default(), Buffer::default());
+/// Cli::new(path.clone())
+///     .with_output(Output::new(out.clone(), err.clone()))
+///     .run_from(["toto", "add", "--title", "Milk", "--priority", "1"])?;
+/// Cli::new(path)
+///     .with_output(Output::new(out.clone(), err.clone()))
+///     .run_from(["toto", "list"])?;
+/// assert_eq!(out.contents(), "   ID    | TITLE      | STATUS\n   0     | Milk       | P\n");
+/// assert!(err.contents().starts_with("Note: list rows are numbered from 0."));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct Output {
+    out: RefCell<Box<dyn Write>>,
+    err: RefCell<Box<dyn Write>>,
+    /// `None` reads standard input, locking it only while reading.
+    input: RefCell<Option<Box<dyn BufRead>>>,
+}
+
+impl Output {
+    pub fn new(out: impl Write + 'static, err: impl Write + 'static) -> Self {
+        Self {
+            out: RefCell::new(Box::new(out)),
+            err: RefCell::new(Box::new(err)),
+            input: RefCell::new(None),
+        }
+    }
+
+    /// Reads prompt answers from `input` instead of standard input.
+    pub fn with_input(mut self, input: impl BufRead + 'static) -> Self {
+        self.input = RefCell::new(Some(Box::new(input)));
+        self
+    }
+
+    /// Standard output and standard error.
+    pub fn stdio() -> Self {
+        Self::new(io::stdout(), io::stderr())
+    }
+
+    /// The stream for results. Don't hold on to it across calls that may
+    /// write themselves.
+    pub fn out(&self) -> RefMut<'_, Box<dyn Write>> {
+        self.out.borrow_mut()
+    }
+
+    /// The stream for warnings and problems.
+    pub fn err(&self) -> RefMut<'_, Box<dyn Write>> {
+        self.err.borrow_mut()
+    }
+
+    /// Reads one line of input for a prompt into `line`, returning 0 at
+    /// the end of the input.
+    pub fn read_line(&self, line: &mut String) -> io::Result<usize> {
+        match self.input.borrow_mut().as_mut() {
+            Some(input) => input.read_line(line),
+            None => io::stdin().read_line(line),
+        }
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::stdio()
+    }
+}
+
+/// An in-memory stream. Clones share the same contents, so one can be
+/// handed to an `Output` and another kept to read what was written.
+#[derive(Clone, Debug, Default)]
+pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    /// Everything written so far, with invalid UTF-8 replaced.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use std::io::Write;
+
+use anyhow::{Context, Result, bail};
+
+use crate::output::Output;
+
+/// Asks `question` on `output`'s error stream and returns the trimmed
+/// answer read from its input, which may be empty. Fails if the input ends
+/// before a line is entered.
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::prompt;
+///
+/// let err = Buffer::default();
+/// let output = Output::new(std::io::sink(), err.clone()).with_input(&b"\n  Buy stamps \n"[..]);
+/// assert_eq!(prompt::ask_required(&output, "Title")?, "Buy stamps");
+/// assert_eq!(err.contents(), "Title: An answer is required.\nTitle: ");
+/// assert!(prompt::ask(&output, "Description").is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn ask(output: &Output, question: &str) -> Result<String> {
+    {
+        let mut err = output.err();
+        write!(err, "{}: ", question).context("Failed to write prompt")?;
+        err.flush().context("Failed to write prompt")?;
+    }
+    let mut line = String::new();
+    let read = output
+        .read_line(&mut line)
+        .context("Failed to read answer")?;
+    if read == 0 {
+        bail!("No answer given for: {}", question);
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Asks until a non-empty answer is given.
+pub fn ask_required(output: &Output, question: &str) -> Result<String> {
+    loop {
+        let answer = ask(output, question)?;
+        if !answer.is_empty() {
+            return Ok(answer);
+        }
+        writeln!(output.err(), "An answer is required.")?;
+    }
+}
+
+/// Asks until `parse` accepts the answer, using `default` for an empty
+/// answer and showing the error for one it rejects.
+pub fn ask_parsed_with<T>(
+    output: &Output,
+    question: &str,
+    default: T,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<T> {
+    loop {
+        let answer = ask(output, question)?;
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        match parse(&answer) {
+            Ok(value) => return Ok(value),
+            Err(err) => writeln!(output.err(), "'{}' is not a valid answer: {}.", answer, err)?,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use uuid::Uuid;
+
+use crate::cli::{Todo, TodoData, TodoStatus};
+
+/// Why `watch --notify` reminds about a todo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Reminder {
+    /// Due within the `notify_due_within` window.
+    DueSoon,
+    Overdue,
+}
+
+impl Reminder {
+    /// The reminder an open todo with a due date deserves at `now`, if any.
+    pub fn for_todo(todo: &TodoData, now: DateTime<Utc>, within: TimeDelta) -> Option<Reminder> {
+        if !matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress) {
+            return None;
+        }
+        let due_at = todo.due_at?;
+        if due_at <= now {
+            Some(Reminder::Overdue)
+        } else if due_at - now <= within {
+            Some(Reminder::DueSoon)
+        } else {
+            None
+        }
+    }
+
+    pub fn message(self, title: &str) -> String {
+        match self {
+            Reminder::DueSoon => format!("\"{}\" is due soon", title),
+            Reminder::Overdue => format!("\"{}\" is overdue", title),
+        }
+    }
+}
+
+/// The reminders sent so far, so each todo is reminded of once when it
+/// comes due and once more when it becomes overdue rather than on every
+/// tick. Moving the due date makes it eligible again.
+#[derive(Debug, Default)]
+pub struct Reminders {
+    sent: HashSet<(Uuid, DateTime<Utc>, Reminder)>,
+}
+
+impl Reminders {
+    /// The reminders `todos` call for at `now` that weren't sent before,
+    /// which from now on count as sent.
+    ///
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::remind::{Reminder, Reminders};
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let now = Utc::now();
+    /// let due = |title, hours| NewTodo {
+    ///     due_at: Some(now + TimeDelta::hours(hours)),
+    ///     ..NewTodo::new(title)
+    /// };
+    /// let late = cli.add(due("File taxes", -2))?;
+    /// let soon = cli.add(due("Call the bank", 1))?;
+    /// cli.add(due("Renew passport", 48))?;
+    /// let done = cli.add(due("Pay rent", -1))?;
+    /// cli.complete(done, None)?;
+    ///
+    /// let mut reminders = Reminders::default();
+    /// let within = TimeDelta::hours(2);
+    /// let todos = cli.todos();
+    /// let mut sent: Vec<_> = reminders
+    ///     .due(&todos, now, within)
+    ///     .into_iter()
+    ///     .map(|(reminder, todo)| (reminder, todo.id))
+    ///     .collect();
+    /// sent.sort_by_key(|(reminder, _)| *reminder == Reminder::DueSoon);
+    /// assert_eq!(sent, [(Reminder::Overdue, late), (Reminder::DueSoon, soon)]);
+    ///
+    /// // Nothing is sent twice, but a todo coming due is reminded of again.
+    /// assert!(reminders.due(&todos, now, within).is_empty());
+    /// let later = now + TimeDelta::hours(1);
+    /// let sent = reminders.due(&todos, later, within);
+    /// assert!(sent.len() == 1 && sent[0].0 == Reminder::Overdue && sent[0].1.id == soon);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn due<'a>(
+        &mut self,
+        todos: &'a [Todo],
+        now: DateTime<Utc>,
+        within: TimeDelta,
+    ) -> Vec<(Reminder, &'a Todo)> {
+        todos
+            .iter()
+            .filter_map(|todo| {
+                let reminder = Reminder::for_todo(&todo.data, now, within)?;
+                let due_at = todo.data.due_at?;
+                self.sent
+                    .insert((todo.id, due_at, reminder))
+                    .then_some((reminder, todo))
+            })
+            .collect()
+    }
+}
+
+/// Whether this build can show desktop notifications, which needs the
+/// `notify` feature.
+pub const AVAILABLE: bool = cfg!(feature = "notify");
+
+/// Shows `message` as a desktop notification.
+#[cfg(feature = "notify")]
+pub fn notify(message: &str) -> Result<()> {
+    use anyhow::Context;
+
+    notify_rust::Notification::new()
+        .summary("toto")
+        .body(message)
+        .show()
+        .context("Failed to show a desktop notification")?;
+    Ok(())
+}
+
+/// Shows `message` as a desktop notification.
+#[cfg(not(feature = "notify"))]
+pub fn notify(_message: &str) -> Result<()> {
+    anyhow::bail!("Desktop notifications need the notify feature")
+}
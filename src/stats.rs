@@ -0,0 +1,465 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::cli::{Todo, TodoStatus};
+
+/// Period that completions are grouped into for throughput.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BucketSize {
+    Day,
+    Week,
+    Month,
+}
+
+impl BucketSize {
+    fn label(self, dt: DateTime<Utc>) -> String {
+        match self {
+            BucketSize::Day => dt.format("%Y-%m-%d").to_string(),
+            BucketSize::Week => {
+                let week = dt.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            BucketSize::Month => dt.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// Versions of the `--porcelain` format. A version's output never changes
+/// once released; new fields or layouts get a new version that scripts opt
+/// into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PorcelainVersion {
+    /// Space separated `<kind> [<key>] <value>` lines, preceded by a
+    /// `porcelain v1` header line.
+    V1,
+}
+
+/// Field that `count --by` groups todos on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Status,
+    Priority,
+    Tag,
+}
+
+/// Number of todos per group, in a fixed order: statuses in lifecycle
+/// order (all of them, even when empty), priorities ascending and tags
+/// alphabetically with untagged todos last as `(none)`. A todo with several
+/// tags counts towards each of them.
+///
+/// ```
+/// use serde_json::json;
+/// use toto::cli::Todo;
+/// use toto::stats::{self, GroupBy};
+///
+/// let todo = |id: u128, priority: u8, status: &str, tags: &[&str]| {
+///     json!({
+///         "id": uuid::Uuid::from_u128(id),
+///         "data": {
+///             "title": "Chore", "description": null, "priority": priority, "status": status,
+///             "created_at": 0, "in_progress_at": null, "completed_at": null, "deleted_at": null,
+///             "tags": tags,
+///         },
+///     })
+/// };
+/// let todos: Vec<Todo> = serde_json::from_value(json!([
+///     todo(1, 1, "Pending", &["home", "garden"]),
+///     todo(2, 1, "Completed", &["home"]),
+///     todo(3, 255, "InProgress", &[]),
+///     todo(4, 255, "Pending", &[]),
+/// ]))?;
+/// let todos: Vec<&Todo> = todos.iter().collect();
+/// let counts = |by| -> Vec<String> {
+///     stats::group_counts(&todos, by).into_iter().map(|(group, count)| format!("{group}: {count}")).collect()
+/// };
+///
+/// assert_eq!(counts(GroupBy::Status), ["pending: 2", "in-progress: 1", "completed: 1", "deleted: 0"]);
+/// assert_eq!(counts(GroupBy::Priority), ["1: 2", "255: 2"]);
+/// assert_eq!(counts(GroupBy::Tag), ["garden: 1", "home: 2", "(none): 2"]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn group_counts(todos: &[&Todo], by: GroupBy) -> Vec<(String, usize)> {
+    match by {
+        GroupBy::Status => TodoStatus::value_variants()
+            .iter()
+            .map(|status| {
+                let count = todos
+                    .iter()
+                    .filter(|todo| todo.data.status == *status)
+                    .count();
+                let name = status
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string())
+                    .unwrap_or_default();
+                (name, count)
+            })
+            .collect(),
+        GroupBy::Priority => {
+            let mut counts: BTreeMap<u8, usize> = BTreeMap::new();
+            for todo in todos {
+                *counts.entry(todo.data.priority).or_insert(0) += 1;
+            }
+            counts
+                .into_iter()
+                .map(|(priority, count)| (priority.to_string(), count))
+                .collect()
+        }
+        GroupBy::Tag => {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            let mut untagged = 0;
+            for todo in todos {
+                if todo.data.tags.is_empty() {
+                    untagged += 1;
+                }
+                for tag in &todo.data.tags {
+                    *counts.entry(tag.clone()).or_insert(0) += 1;
+                }
+            }
+            let mut groups: Vec<(String, usize)> = counts.into_iter().collect();
+            if untagged > 0 {
+                groups.push((String::from("(none)"), untagged));
+            }
+            groups
+        }
+    }
+}
+
+/// `count --porcelain`: the `porcelain v1` header, `total <n>`, then with
+/// `--by` one `<by> <group> <n>` line per group in `group_counts` order.
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-count-porcelain-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> anyhow::Result<String> {
+///     let out = Buffer::default();
+///     Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))?;
+///     Ok(out.contents())
+/// };
+/// run(&["add", "--title", "Weed", "--priority", "1", "--tag", "home", "--tag", "garden"])?;
+/// run(&["add", "--title", "Mop", "--priority", "1", "--tag", "home"])?;
+/// run(&["add", "--title", "Taxes", "--priority", "2"])?;
+///
+/// assert_eq!(run(&["count", "--porcelain"])?, "porcelain v1\ntotal 3\n");
+/// let by_tag = "porcelain v1\ntotal 3\ntag garden 1\ntag home 2\ntag (none) 1\n";
+/// assert_eq!(run(&["count", "--porcelain=v1", "--by", "tag"])?, by_tag);
+///
+/// // Settings that color and size the table don't reach it, nor stats'.
+/// let stats = run(&["stats", "--porcelain"])?;
+/// std::fs::write(dir.join("config.json"), r#"{"tag_colors": "home=red", "tiny_width": 500}"#)?;
+/// assert_eq!(run(&["count", "--porcelain", "--by", "tag"])?, by_tag);
+/// assert_eq!(run(&["stats", "--porcelain"])?, stats);
+/// assert!(!stats.contains('\x1b'));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn render_count_porcelain(
+    total: usize,
+    groups: Option<(GroupBy, &[(String, usize)])>,
+    version: PorcelainVersion,
+) -> String {
+    match version {
+        PorcelainVersion::V1 => {
+            let mut out = String::from("porcelain v1\n");
+            out.push_str(&format!("total {}\n", total));
+            if let Some((by, groups)) = groups {
+                let kind = by
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string())
+                    .unwrap_or_default();
+                for (group, count) in groups {
+                    out.push_str(&format!("{} {} {}\n", kind, group, count));
+                }
+            }
+            out
+        }
+    }
+}
+
+/// Metrics computed over a todo list.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub total: usize,
+    pub pending: usize,
+    pub in_progress: usize,
+    pub completed: usize,
+    pub deleted: usize,
+    /// The todos percentages are taken of: all of them when deleted todos
+    /// count (`count_deleted_in_stats`), otherwise all but the deleted.
+    pub counted: usize,
+    /// Share of `counted` per status, rounded to one decimal place. Deleted
+    /// todos only have one when they count.
+    pub percentages: BTreeMap<&'static str, f64>,
+    /// Todos completed per bucket, oldest first.
+    pub throughput: Vec<Throughput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Throughput {
+    pub bucket: String,
+    pub completed: usize,
+}
+
+/// Counts `todos` by status and completions per `bucket`. Deleted todos
+/// only count towards the percentages with `count_deleted`.
+///
+/// ```
+/// use serde_json::json;
+/// use toto::cli::Todo;
+/// use toto::config::Config;
+/// use toto::stats::{self, BucketSize};
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-count-deleted-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let todo = |id: u128, status: &str| {
+///     let at = |wanted: &str| if status == wanted { json!(1_000) } else { json!(null) };
+///     json!({
+///         "id": uuid::Uuid::from_u128(id),
+///         "data": {
+///             "title": "Chore", "description": null, "priority": 1, "status": status,
+///             "created_at": 1_000, "in_progress_at": null,
+///             "completed_at": at("Completed"), "deleted_at": at("Deleted"),
+///         },
+///     })
+/// };
+/// let todos: Vec<Todo> = serde_json::from_value(json!([
+///     todo(1, "Pending"),
+///     todo(2, "Completed"),
+///     todo(3, "Deleted"),
+///     todo(4, "Deleted"),
+/// ]))?;
+///
+/// let without = stats::compute(&todos, BucketSize::Week, false);
+/// assert_eq!((without.total, without.counted), (4, 2));
+/// assert_eq!((without.percentages["pending"], without.percentages.get("deleted")), (50.0, None));
+/// let with = stats::compute(&todos, BucketSize::Week, true);
+/// assert_eq!((with.total, with.counted), (4, 4));
+/// assert_eq!((with.percentages["pending"], with.percentages["deleted"]), (25.0, 50.0));
+///
+/// // `stats` takes the setting from the config.
+/// assert!(!Config::load(&path)?.count_deleted_in_stats);
+/// std::fs::write(Config::path(&path), r#"{"count_deleted_in_stats": true}"#)?;
+/// assert!(Config::load(&path)?.count_deleted_in_stats);
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn compute(todos: &[Todo], bucket: BucketSize, count_deleted: bool) -> Stats {
+    let mut stats = Stats {
+        total: todos.len(),
+        pending: 0,
+        in_progress: 0,
+        completed: 0,
+        deleted: 0,
+        counted: 0,
+        percentages: BTreeMap::new(),
+        throughput: Vec::new(),
+    };
+    let mut buckets: BTreeMap<String, usize> = BTreeMap::new();
+
+    for todo in todos {
+        match todo.data.status {
+            TodoStatus::Pending => stats.pending += 1,
+            TodoStatus::InProgress => stats.in_progress += 1,
+            TodoStatus::Completed => stats.completed += 1,
+            TodoStatus::Deleted => stats.deleted += 1,
+        }
+        if todo.data.status == TodoStatus::Completed
+            && let Some(completed_at) = todo.data.completed_at
+        {
+            *buckets.entry(bucket.label(completed_at)).or_insert(0) += 1;
+        }
+    }
+
+    stats.counted = if count_deleted {
+        stats.total
+    } else {
+        stats.total - stats.deleted
+    };
+    for (status, count) in stats.status_counts() {
+        if (status != "deleted" || count_deleted) && stats.counted > 0 {
+            let percent = count as f64 * 100.0 / stats.counted as f64;
+            stats
+                .percentages
+                .insert(status, (percent * 10.0).round() / 10.0);
+        }
+    }
+
+    // Bucket labels are zero padded, so lexical order is chronological.
+    stats.throughput = buckets
+        .into_iter()
+        .map(|(bucket, completed)| Throughput { bucket, completed })
+        .collect();
+    stats
+}
+
+impl Stats {
+    fn status_counts(&self) -> [(&'static str, usize); 4] {
+        [
+            ("pending", self.pending),
+            ("in_progress", self.in_progress),
+            ("completed", self.completed),
+            ("deleted", self.deleted),
+        ]
+    }
+
+    pub fn render_human(&self) -> String {
+        let mut out = format!("Total: {}\n", self.total);
+        for (status, count) in self.status_counts() {
+            match self.percentages.get(status) {
+                Some(percent) => out.push_str(&format!(
+                    "  {:<12} {:<5} ({:.1}%)\n",
+                    status, count, percent
+                )),
+                None => out.push_str(&format!("  {:<12} {}\n", status, count)),
+            }
+        }
+        if !self.throughput.is_empty() {
+            out.push_str("Completed per period:\n");
+            for throughput in &self.throughput {
+                out.push_str(&format!(
+                    "  {:<12} {}\n",
+                    throughput.bucket, throughput.completed
+                ));
+            }
+        }
+        out
+    }
+
+    /// One `metric,key,value` record per line, with a header row.
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// use toto::cli::Todo;
+    /// use toto::stats::{self, BucketSize};
+    ///
+    /// // 2024-05-01 09:30 UTC, in milliseconds.
+    /// let at = 1_714_555_800_000_i64;
+    /// let todo = |id: u128, title: &str, status: &str, completed_at: Option<i64>| {
+    ///     json!({
+    ///         "id": uuid::Uuid::from_u128(id),
+    ///         "data": {
+    ///             "title": title, "description": "", "priority": 1, "status": status,
+    ///             "created_at": at, "in_progress_at": null,
+    ///             "completed_at": completed_at, "deleted_at": null,
+    ///         },
+    ///     })
+    /// };
+    /// let todos: Vec<Todo> = serde_json::from_value(json!([
+    ///     todo(1, "Done", "Completed", Some(at)),
+    ///     todo(2, "Open", "Pending", None),
+    /// ]))?;
+    ///
+    /// let stats = stats::compute(&todos, BucketSize::Day, false);
+    /// assert_eq!(
+    ///     stats.render_csv(),
+    ///     "metric,key,value\n\
+    ///      total,,2\n\
+    ///      counted,,2\n\
+    ///      status,pending,1\n\
+    ///      status,in_progress,0\n\
+    ///      status,completed,1\n\
+    ///      status,deleted,0\n\
+    ///      percent,pending,50.0\n\
+    ///      percent,in_progress,0.0\n\
+    ///      percent,completed,50.0\n\
+    ///      throughput,2024-05-01,1\n"
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn render_csv(&self) -> String {
+        let mut out = String::from("metric,key,value\n");
+        out.push_str(&format!("total,,{}\n", self.total));
+        out.push_str(&format!("counted,,{}\n", self.counted));
+        for (status, count) in self.status_counts() {
+            out.push_str(&format!("status,{},{}\n", status, count));
+        }
+        for (status, _) in self.status_counts() {
+            if let Some(percent) = self.percentages.get(status) {
+                out.push_str(&format!("percent,{},{:.1}\n", status, percent));
+            }
+        }
+        for throughput in &self.throughput {
+            out.push_str(&format!(
+                "throughput,{},{}\n",
+                throughput.bucket, throughput.completed
+            ));
+        }
+        out
+    }
+
+    /// Line oriented output for scripts. Never colored or sized to the
+    /// terminal, so it is safe to diff between runs.
+    ///
+    /// v1 prints `porcelain v1`, then `total <n>`, then
+    /// `status <name> <n>` for every status in a fixed order, then
+    /// `throughput <bucket> <n>` oldest first.
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// use toto::cli::Todo;
+    /// use toto::stats::{self, BucketSize, PorcelainVersion};
+    ///
+    /// // 2024-05-01 09:30 UTC, in milliseconds.
+    /// let at = 1_714_555_800_000_i64;
+    /// let todo = |id: u128, status: &str, in_progress_at: Option<i64>, completed_at: Option<i64>| {
+    ///     json!({
+    ///         "id": uuid::Uuid::from_u128(id),
+    ///         "data": {
+    ///             "title": "Chore", "description": null, "priority": 1, "status": status,
+    ///             "created_at": at, "in_progress_at": in_progress_at,
+    ///             "completed_at": completed_at, "deleted_at": null,
+    ///         },
+    ///     })
+    /// };
+    /// let todos: Vec<Todo> = serde_json::from_value(json!([
+    ///     todo(1, "Completed", None, Some(at)),
+    ///     todo(2, "InProgress", Some(at), None),
+    /// ]))?;
+    ///
+    /// let stats = stats::compute(&todos, BucketSize::Day, false);
+    /// assert_eq!(
+    ///     stats.render_porcelain(PorcelainVersion::V1),
+    ///     "porcelain v1\n\
+    ///      total 2\n\
+    ///      status pending 0\n\
+    ///      status in_progress 1\n\
+    ///      status completed 1\n\
+    ///      status deleted 0\n\
+    ///      throughput 2024-05-01 1\n"
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn render_porcelain(&self, version: PorcelainVersion) -> String {
+        match version {
+            PorcelainVersion::V1 => {
+                let mut out = String::from("porcelain v1\n");
+                out.push_str(&format!("total {}\n", self.total));
+                for (status, count) in self.status_counts() {
+                    out.push_str(&format!("status {} {}\n", status, count));
+                }
+                for throughput in &self.throughput {
+                    out.push_str(&format!(
+                        "throughput {} {}\n",
+                        throughput.bucket, throughput.completed
+                    ));
+                }
+                out
+            }
+        }
+    }
+
+    pub fn render_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
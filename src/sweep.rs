@@ -0,0 +1,96 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::cli::{TodoData, TodoStatus};
+
+/// A condition under which `sweep` deletes a todo, set in the `sweep_rules`
+/// config key as a JSON list such as
+/// `[{"statuses": ["pending"], "tags": ["maybe"], "older_than_days": 90}]`.
+///
+/// Every criterion that is set must match. A rule needs at least one of
+/// `tags`, `title_contains` or `older_than_days`, so a typo can't sweep the
+/// whole list.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Only todos in one of these states, pending and in progress if empty.
+    /// Deleted todos are never swept again.
+    #[serde(default, with = "status_names", skip_serializing_if = "Vec::is_empty")]
+    pub statuses: Vec<TodoStatus>,
+    /// Only todos carrying every one of these tags.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Only todos whose title contains this, ignoring case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_contains: Option<String>,
+    /// Only todos created more than this many days ago.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than_days: Option<u32>,
+}
+
+impl Rule {
+    pub fn matches(&self, todo: &TodoData, now: DateTime<Utc>) -> bool {
+        let status_matches = if self.statuses.is_empty() {
+            matches!(todo.status, TodoStatus::Pending | TodoStatus::InProgress)
+        } else {
+            self.statuses.contains(&todo.status)
+        };
+        if !status_matches || todo.status == TodoStatus::Deleted {
+            return false;
+        }
+        if !self.tags.iter().all(|tag| todo.tags.contains(tag)) {
+            return false;
+        }
+        if let Some(needle) = &self.title_contains
+            && !todo.title.to_lowercase().contains(&needle.to_lowercase())
+        {
+            return false;
+        }
+        if let Some(days) = self.older_than_days
+            && now - todo.created_at <= TimeDelta::days(i64::from(days))
+        {
+            return false;
+        }
+        true
+    }
+
+    fn is_narrowed(&self) -> bool {
+        !self.tags.is_empty() || self.title_contains.is_some() || self.older_than_days.is_some()
+    }
+}
+
+/// Parses the `sweep_rules` config value.
+pub fn parse_rules(value: &str) -> Result<Vec<Rule>, String> {
+    let rules: Vec<Rule> = serde_json::from_str(value)
+        .map_err(|err| format!("expected a JSON list of rules: {}", err))?;
+    if let Some(index) = rules.iter().position(|rule| !rule.is_narrowed()) {
+        return Err(format!(
+            "rule {} needs tags, title_contains or older_than_days",
+            index + 1
+        ));
+    }
+    Ok(rules)
+}
+
+// Statuses are written the way the command line spells them, e.g.
+// `in-progress`.
+mod status_names {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(statuses: &[TodoStatus], s: S) -> Result<S::Ok, S::Error> {
+        let names: Vec<String> = statuses
+            .iter()
+            .filter_map(|status| status.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        names.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<TodoStatus>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|name| TodoStatus::from_str(name, true).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
@@ -1,55 +1,933 @@
-use crate::cli::{Todo, TodoStatus};
+use crate::cli::{Todo, parse_priority};
+use crate::export;
+use crate::time::{self, DateStyle};
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, IsTerminal, Write, stdout},
+    str::FromStr,
+};
 use terminal_size::{Height, Width, terminal_size};
 
-const SMALL_TERM: u16 = 80;
+const SMALL_TERM: usize = 80;
+
+/// One of the two fixed table layouts, for forcing it with `--wide` or
+/// `--narrow`.
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-size-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> anyhow::Result<String> {
+///     let out = Buffer::default();
+///     Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))?;
+///     Ok(out.contents())
+/// };
+/// run(&["add", "--title", "Milk", "--priority", "1"])?;
+/// // Without a terminal the narrow layout is the default.
+/// let narrow = "   ID    | TITLE      | STATUS\n   0     | Milk       | P\n";
+/// assert_eq!(run(&["list"])?, narrow);
+/// let wide = run(&["list", "--wide"])?;
+/// assert!(wide.contains("| TITLE                          | DESCRIPTION "), "{}", wide);
+///
+/// // 200 columns would pick the wide layout, unless told otherwise.
+/// let roomy = run(&["list", "--limit-width", "200"])?;
+/// assert!(roomy.contains("| DESCRIPTION "), "{}", roomy);
+/// assert_eq!(run(&["list", "--limit-width", "200", "--narrow"])?, narrow);
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Size {
+    Small,
+    Large,
+}
+
+/// The lines `list --borders` draws its table with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Borders {
+    /// Box-drawing characters such as `┌─┬─┐`.
+    Unicode,
+    /// `+`, `-` and `|`, for terminals without box-drawing characters.
+    Ascii,
+}
+
+impl Borders {
+    /// Unicode lines unless `ascii` is set or the locale isn't UTF-8.
+    pub fn detect(ascii: bool) -> Borders {
+        // The first of these that is set decides, as for the C library.
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        if ascii || !(locale.contains("utf-8") || locale.contains("utf8")) {
+            Borders::Ascii
+        } else {
+            Borders::Unicode
+        }
+    }
+
+    // The left corner, junction and right corner of the top, header and
+    // bottom rules, then the horizontal and vertical lines.
+    fn glyphs(self) -> ([[char; 3]; 3], char, char) {
+        match self {
+            Borders::Unicode => (
+                [['┌', '┬', '┐'], ['├', '┼', '┤'], ['└', '┴', '┘']],
+                '─',
+                '│',
+            ),
+            Borders::Ascii => ([['+'; 3]; 3], '-', '|'),
+        }
+    }
+}
+
+/// Horizontal alignment of a value within its column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl FromStr for Align {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "left" => Ok(Align::Left),
+            "center" => Ok(Align::Center),
+            "right" => Ok(Align::Right),
+            _ => Err(format!(
+                "unknown alignment '{}', expected left, center or right",
+                s
+            )),
+        }
+    }
+}
+
+/// Colors a todo's row can be pinned to.
+///
+/// ```
+/// use toto::cli::{Cli, Todo};
+/// use toto::term::Color;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-color-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+/// run(&["add", "--title", "Taxes", "--priority", "1", "--color", "red", "--icon", "!"])?;
+/// let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+/// assert_eq!(todos[0].data.color, Some(Color::Red));
+/// assert_eq!(todos[0].data.icon.as_deref(), Some("!"));
+/// assert_eq!(Color::Red.ansi(), "\x1b[31m");
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// With color on, the todo's row is printed in it. `update --color` picks
+/// another and `update --clear-color` goes back to none:
+///
+/// ```
+/// use toto::cli::{Cli, Todo};
+/// use toto::term::{self, Layout, Row};
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-color-row-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| Cli::new(path.clone()).run_from(["toto"].iter().chain(args));
+/// let row = || -> anyhow::Result<String> {
+///     let todos: Vec<Todo> = serde_json::from_slice(&std::fs::read(dir.join("todos.json"))?)?;
+///     let layout = Layout { color: true, max_width: Some(60), ..Layout::default() };
+///     let mut out = Vec::new();
+///     term::print_todo(&mut out, &layout, false, &Row::new(&todos[0], Some(0)))?;
+///     Ok(String::from_utf8(out)?)
+/// };
+/// run(&["add", "--title", "Taxes", "--priority", "1", "--color", "red"])?;
+/// let red = row()?;
+/// assert!(red.starts_with("\x1b[31m") && red.ends_with("\x1b[0m\n"), "{:?}", red);
+/// assert!(red.contains("Taxes"));
+///
+/// run(&["update", "--id", "0", "--color", "blue"])?;
+/// assert!(row()?.starts_with("\x1b[34m"));
+/// run(&["update", "--id", "0", "--clear-color"])?;
+/// assert!(!row()?.contains('\x1b'));
+/// assert!(run(&["update", "--id", "0", "--color", "red", "--clear-color"]).is_err());
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+}
+
+impl Color {
+    /// The ANSI escape sequence selecting this foreground color.
+    pub fn ansi(self) -> &'static str {
+        match self {
+            Color::Red => "\x1b[31m",
+            Color::Green => "\x1b[32m",
+            Color::Yellow => "\x1b[33m",
+            Color::Blue => "\x1b[34m",
+            Color::Magenta => "\x1b[35m",
+            Color::Cyan => "\x1b[36m",
+        }
+    }
+
+    /// The color for `tag`: the one mapped to it in `tag_colors`, or else
+    /// one picked from the tag's name so it stays the same between runs.
+    pub fn for_tag(tag: &str, tag_colors: &BTreeMap<String, Color>) -> Color {
+        if let Some(color) = tag_colors.get(tag) {
+            return *color;
+        }
+        let colors = Color::value_variants();
+        let sum: usize = tag.bytes().map(usize::from).sum();
+        colors[sum % colors.len()]
+    }
+}
+
+/// Parses the `tag_colors` setting, comma separated `TAG=COLOR` pairs such
+/// as `urgent=red, home=blue`.
+pub fn parse_tag_colors(s: &str) -> Result<BTreeMap<String, Color>, String> {
+    let mut tag_colors = BTreeMap::new();
+    for pair in s.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (tag, color) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("expected TAG=COLOR, got '{}'", pair.trim()))?;
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() {
+            return Err(format!("missing tag in '{}'", pair.trim()));
+        }
+        let color = Color::from_str(color.trim(), true)?;
+        tag_colors.insert(tag, color);
+    }
+    Ok(tag_colors)
+}
+
+/// The `priority_symbols` used unless another mapping is configured.
+pub const DEFAULT_PRIORITY_SYMBOLS: &str = "high=!!!, medium=!!, low=!";
+
+/// Parses the `priority_symbols` setting, comma separated
+/// `PRIORITY=SYMBOLS` pairs such as `high=!!!, low=!`, with priorities as
+/// `parse_priority` takes them. Each applies from its priority down to the
+/// next one listed.
+pub fn parse_priority_symbols(s: &str) -> Result<BTreeMap<u8, String>, String> {
+    let mut symbols = BTreeMap::new();
+    for pair in s.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (priority, symbol) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("expected PRIORITY=SYMBOLS, got '{}'", pair.trim()))?;
+        symbols.insert(parse_priority(priority)?, symbol.trim().to_string());
+    }
+    Ok(symbols)
+}
+
+/// The symbols `priority` is shown as with `--priority-symbols`: those of
+/// the nearest mapped priority at or above it in importance. Unprioritised
+/// todos get none.
+///
+/// ```
+/// use toto::cli::parse_priority;
+/// use toto::term::{self, DEFAULT_PRIORITY_SYMBOLS};
+///
+/// let symbols = term::parse_priority_symbols(DEFAULT_PRIORITY_SYMBOLS).unwrap();
+/// let shown = |name| term::priority_symbols(parse_priority(name).unwrap(), &symbols);
+/// assert_eq!(shown("high"), "!!!");
+/// assert_eq!(shown("medium"), "!!");
+/// assert_eq!(shown("low"), "!");
+/// assert_eq!(shown("none"), "");
+/// assert_eq!(term::priority_symbols(40, &symbols), "!!!");
+/// ```
+pub fn priority_symbols(priority: u8, symbols: &BTreeMap<u8, String>) -> String {
+    if priority == u8::MAX {
+        return String::new();
+    }
+    symbols
+        .range(..=priority)
+        .next_back()
+        .map(|(_, symbol)| symbol.clone())
+        .unwrap_or_default()
+}
+
+const RESET: &str = "\x1b[0m";
+const DEFAULT_FOREGROUND: &str = "\x1b[39m";
+// Bold on and off, leaving the row's color alone.
+const BOLD: &str = "\x1b[1m";
+const BOLD_OFF: &str = "\x1b[22m";
+
+/// The columns rendered by `list`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Column {
+    Id,
+    Title,
+    Description,
+    Priority,
+    Status,
+    Created,
+    Tags,
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "id" => Ok(Column::Id),
+            "title" => Ok(Column::Title),
+            "description" => Ok(Column::Description),
+            "priority" => Ok(Column::Priority),
+            "status" => Ok(Column::Status),
+            "created" => Ok(Column::Created),
+            "tags" => Ok(Column::Tags),
+            _ => Err(format!("unknown column '{}'", s)),
+        }
+    }
+}
+
+/// Parses a `COLUMN=ALIGN` pair such as `title=right`.
+pub fn parse_column_align(s: &str) -> Result<(Column, Align), String> {
+    let (column, align) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected COLUMN=ALIGN, got '{}'", s))?;
+    Ok((column.trim().parse()?, align.trim().parse()?))
+}
+
+/// Controls how truncated values and column alignment are rendered.
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub ellipsis: String,
+    pub align: HashMap<Column, Align>,
+    /// Drop columns one at a time as the terminal narrows instead of
+    /// switching between the fixed small and large layouts.
+    pub auto_columns: bool,
+    /// Terminals narrower than this get one `ID S TITLE` line per todo and
+    /// no header, since the small layout needs this many columns.
+    pub tiny_below: usize,
+    /// Print every field in full on one line, whatever the terminal width.
+    pub no_truncate: bool,
+    /// Use this layout whatever width the terminal reports, for when the
+    /// detection gets it wrong.
+    pub size: Option<Size>,
+    /// Whether to emit color and highlight escapes at all.
+    pub color: bool,
+    /// Text to pick out wherever it appears in titles and descriptions,
+    /// ignoring ASCII case.
+    pub highlight: Option<String>,
+    /// Add a bar of each todo's progress, or of its age if it has no
+    /// subtasks, to the large layout.
+    pub bars: bool,
+    pub dates: DateStyle,
+    /// Leave out the header line, keeping the rows as they are.
+    pub no_header: bool,
+    /// Colors for tags, see `Color::for_tag`. A row without a color of its
+    /// own takes the color of its first tag mapped here.
+    pub tag_colors: BTreeMap<String, Color>,
+    /// Draw `list` as a table with these borders, see `print_table`.
+    pub borders: Option<Borders>,
+    /// Show priorities as symbols such as `!!!` rather than numbers, see
+    /// `priority_symbols`.
+    pub priority_symbols: Option<BTreeMap<u8, String>>,
+    /// Lay rows out for at most this many columns, and cut off any that
+    /// are still longer, whatever the terminal's width.
+    pub max_width: Option<usize>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            ellipsis: String::from("..."),
+            align: HashMap::new(),
+            auto_columns: false,
+            tiny_below: TINY_TERM,
+            no_truncate: false,
+            size: None,
+            color: stdout().is_terminal(),
+            highlight: None,
+            bars: false,
+            dates: DateStyle::default(),
+            no_header: false,
+            tag_colors: BTreeMap::new(),
+            borders: None,
+            priority_symbols: None,
+            max_width: None,
+        }
+    }
+}
+
+/// Width the small layout's rows take up, and so the default below which
+/// the tiny layout is used.
+///
+/// ```
+/// use toto::config::{Config, Source};
+/// use toto::term::TINY_TERM;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-tiny-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// assert_eq!(Config::load(&path)?.tiny_width, TINY_TERM);
+/// std::fs::write(Config::path(&path), r#"{"tiny_width": 40}"#)?;
+/// let config = Config::load(&path)?;
+/// assert_eq!((config.tiny_width, config.source("tiny_width")), (40, Source::File));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub const TINY_TERM: usize = 25;
+
+impl Layout {
+    /// Free text and tags read best left-aligned; everything else stays
+    /// centered.
+    pub fn align(&self, column: Column) -> Align {
+        match self.align.get(&column) {
+            Some(align) => *align,
+            None => match column {
+                Column::Title | Column::Description | Column::Tags => Align::Left,
+                _ => Align::Center,
+            },
+        }
+    }
+
+    /// Pads `value` to `width` using the alignment configured for `column`.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use toto::term::{Column, Layout, parse_column_align};
+    ///
+    /// let layout = Layout {
+    ///     ellipsis: String::from("~"),
+    ///     align: HashMap::from([parse_column_align("title=right").unwrap()]),
+    ///     ..Layout::default()
+    /// };
+    /// let title = |title: &str| layout.pad(Column::Title, &layout.truncate_str(title, 10), 10);
+    /// assert_eq!(title("Milk"), "      Milk");
+    /// assert_eq!(title("Water the plants"), "Water the~");
+    /// assert_eq!(layout.pad(Column::Status, "P", 3), " P ");
+    /// assert_eq!(Layout::default().pad(Column::Title, "Milk", 6), "Milk  ");
+    /// assert!(parse_column_align("title").is_err());
+    /// ```
+    pub fn pad(&self, column: Column, value: &str, width: usize) -> String {
+        match self.align(column) {
+            Align::Left => format!("{:<width$}", value),
+            Align::Center => format!("{:^width$}", value),
+            Align::Right => format!("{:>width$}", value),
+        }
+    }
+
+    /// The width to lay rows out for on a `terminal` this wide, if known:
+    /// `max_width` if that is narrower, and `max_width` too when the width
+    /// is unknown, e.g. when piped.
+    ///
+    /// ```
+    /// use toto::term::Layout;
+    ///
+    /// let layout = Layout { max_width: Some(80), ..Layout::default() };
+    /// assert_eq!(layout.width(Some(200)), Some(80));
+    /// assert_eq!(layout.width(Some(60)), Some(60));
+    /// assert_eq!(layout.width(None), Some(80));
+    /// assert_eq!(Layout::default().width(Some(200)), Some(200));
+    /// ```
+    ///
+    /// Rows of a layout that is still wider, such as a forced `--wide`,
+    /// are cut off at `max_width`:
+    ///
+    /// ```
+    /// use toto::term::{self, Layout, Row, Size};
+    /// use toto::{Cli, NewTodo};
+    ///
+    /// let mut cli = Cli::new(String::from("."));
+    /// let id = cli.add(NewTodo {
+    ///     description: Some(String::from("Two weeks, both kids")),
+    ///     ..NewTodo::new("Book the summer holiday")
+    /// })?;
+    /// let todo = cli.todo(id).unwrap();
+    /// let layout = Layout {
+    ///     max_width: Some(80),
+    ///     size: Some(Size::Large),
+    ///     color: false,
+    ///     ..Layout::default()
+    /// };
+    /// let mut out = Vec::new();
+    /// term::splash(&mut out, &layout)?;
+    /// term::print_todo(&mut out, &layout, true, &Row::new(&todo, Some(0)))?;
+    /// let out = String::from_utf8(out)?;
+    /// assert!(out.lines().all(|line| line.chars().count() <= 80));
+    /// assert!(out.contains("Book the summer holiday"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn width(&self, terminal: Option<usize>) -> Option<usize> {
+        match (terminal, self.max_width) {
+            (Some(terminal), Some(max)) => Some(terminal.min(max)),
+            (terminal, max) => terminal.or(max),
+        }
+    }
+
+    // The width of the terminal stdout is on, capped by `max_width`.
+    fn screen_width(&self) -> Option<usize> {
+        self.width(terminal_width().map(usize::from))
+    }
+
+    // Cuts `line` off after `max_width` characters, not counting color
+    // escapes, resetting the color if it cut any short.
+    fn clip(&self, line: String) -> String {
+        let Some(max) = self.max_width else {
+            return line;
+        };
+        let mut visible = 0;
+        let mut in_escape = false;
+        for (index, c) in line.char_indices() {
+            if in_escape {
+                in_escape = c != 'm';
+                continue;
+            }
+            if c == '\x1b' {
+                in_escape = true;
+                continue;
+            }
+            if visible == max {
+                let mut clipped = line[..index].to_string();
+                if line.contains('\x1b') {
+                    clipped.push_str(RESET);
+                }
+                return clipped;
+            }
+            visible += 1;
+        }
+        line
+    }
+
+    // The terminal width when it is too narrow for the small layout.
+    fn tiny_width(&self) -> Option<usize> {
+        self.screen_width().filter(|width| *width < self.tiny_below)
+    }
+
+    /// Wraps each occurrence of the highlight term in `cell` in bold. Call it
+    /// on the padded cell, since the escapes take up no room on screen.
+    ///
+    /// ```
+    /// use toto::term::Layout;
+    ///
+    /// let layout = |color: bool| Layout {
+    ///     highlight: Some(String::from("MILK")),
+    ///     color,
+    ///     ..Layout::default()
+    /// };
+    /// let cell = String::from("Buy milk, then more milk   ");
+    /// assert_eq!(
+    ///     layout(true).highlight(cell.clone()),
+    ///     "Buy \x1b[1mmilk\x1b[22m, then more \x1b[1mmilk\x1b[22m   "
+    /// );
+    /// assert_eq!(layout(false).highlight(cell.clone()), cell);
+    /// assert_eq!(Layout::default().highlight(cell.clone()), cell);
+    /// ```
+    pub fn highlight(&self, cell: String) -> String {
+        let Some(term) = self.highlight.as_deref().filter(|term| !term.is_empty()) else {
+            return cell;
+        };
+        if !self.color {
+            return cell;
+        }
+        // ASCII lowercasing keeps byte offsets, so they index into `cell`.
+        let haystack = cell.to_ascii_lowercase();
+        let needle = term.to_ascii_lowercase();
+        let mut out = String::with_capacity(cell.len());
+        let mut last = 0;
+        for (start, _) in haystack.match_indices(&needle) {
+            let end = start + needle.len();
+            out.push_str(&cell[last..start]);
+            out.push_str(BOLD);
+            out.push_str(&cell[start..end]);
+            out.push_str(BOLD_OFF);
+            last = end;
+        }
+        out.push_str(&cell[last..]);
+        out
+    }
+
+    /// Shortens `s` to at most `max_len` characters, ending with the ellipsis.
+    pub fn truncate_str(&self, s: &str, max_len: usize) -> String {
+        if s.chars().count() <= max_len {
+            return s.to_string();
+        }
+        // Leave room for the ellipsis.
+        let keep = max_len.saturating_sub(self.ellipsis.chars().count());
+        let mut truncated: String = s.chars().take(keep).collect();
+        truncated.push_str(&self.ellipsis);
+        truncated
+    }
+
+    // The priority as a number, or as symbols when asked for.
+    fn priority(&self, priority: u8) -> String {
+        match &self.priority_symbols {
+            Some(symbols) => priority_symbols(priority, symbols),
+            None => priority.to_string(),
+        }
+    }
+
+    // The color `row` is tinted with, if any.
+    fn row_color(&self, row: &Row) -> Option<Color> {
+        if !self.color {
+            return None;
+        }
+        row.todo.data.color.or_else(|| {
+            row.todo
+                .data
+                .tags
+                .iter()
+                .find_map(|tag| self.tag_colors.get(tag).copied())
+        })
+    }
+
+    /// Renders `tags` as a cell `width` wide, each tag in its color (see
+    /// `Color::for_tag`) when color is on. `row_color` is switched back on
+    /// after each tag so the rest of a tinted row keeps its color.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use toto::term::{Color, Layout};
+    ///
+    /// let layout = Layout {
+    ///     color: true,
+    ///     tag_colors: BTreeMap::from([(String::from("urgent"), Color::Red)]),
+    ///     ..Layout::default()
+    /// };
+    /// let cell = layout.tag_cell(&[String::from("urgent")], 8, None);
+    /// assert_eq!(cell, "\x1b[31murgent\x1b[39m  ");
+    /// ```
+    pub fn tag_cell(&self, tags: &[String], width: usize, row_color: Option<Color>) -> String {
+        let cell = self.pad(
+            Column::Tags,
+            &self.truncate_str(&tags.join(", "), width),
+            width,
+        );
+        if !self.color {
+            return cell;
+        }
+        let restore = row_color.map_or(DEFAULT_FOREGROUND, Color::ansi);
+        let mut out = String::with_capacity(cell.len());
+        let mut rest = cell.as_str();
+        // Tags cut off by truncation are left uncolored.
+        for tag in tags {
+            let Some(start) = rest.find(tag.as_str()) else {
+                break;
+            };
+            let end = start + tag.len();
+            out.push_str(&rest[..start]);
+            out.push_str(Color::for_tag(tag, &self.tag_colors).ansi());
+            out.push_str(&rest[start..end]);
+            out.push_str(restore);
+            rest = &rest[end..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// A todo prepared for rendering.
+pub struct Row<'a> {
+    pub todo: &'a Todo,
+    /// The human readable id, or `None` for archived todos which cannot be
+    /// addressed by one.
+    pub id: Option<usize>,
+    /// Completed and total number of children, for todos that have any.
+    pub progress: Option<(usize, usize)>,
+    /// A short note printed after the row, such as why it is listed.
+    pub flag: Option<&'static str>,
+}
+
+impl<'a> Row<'a> {
+    pub fn new(todo: &'a Todo, id: Option<usize>) -> Self {
+        Self {
+            todo,
+            id,
+            progress: None,
+            flag: None,
+        }
+    }
+}
+
+// Columns in display order, and the order they are dropped in as the
+// terminal narrows. ID, TITLE and STATUS are never dropped.
+const AUTO_COLUMNS: [Column; 7] = [
+    Column::Id,
+    Column::Title,
+    Column::Description,
+    Column::Priority,
+    Column::Status,
+    Column::Created,
+    Column::Tags,
+];
+const AUTO_DROP_ORDER: [Column; 4] = [
+    Column::Tags,
+    Column::Description,
+    Column::Created,
+    Column::Priority,
+];
+const SEPARATOR: &str = " | ";
+
+impl Column {
+    // Width of the column in the auto layout.
+    fn auto_width(self) -> usize {
+        match self {
+            Column::Id => 8,
+            Column::Title => 30,
+            Column::Description => 20,
+            Column::Priority => 8,
+            Column::Status => 10,
+            Column::Created => 10,
+            Column::Tags => 16,
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            Column::Id => "ID",
+            Column::Title => "TITLE",
+            Column::Description => "DESCRIPTION",
+            Column::Priority => "PRIORITY",
+            Column::Status => "STATUS",
+            Column::Created => "CREATED",
+            Column::Tags => "TAGS",
+        }
+    }
+}
+
+/// Picks the columns that fit in `width`, dropping TAGS, then DESCRIPTION,
+/// then CREATED, then PRIORITY. Returns `None` when even ID, TITLE and STATUS
+/// don't fit, in which case the small layout should be used.
+///
+/// ```
+/// use toto::term::{Column::*, auto_columns};
+///
+/// assert_eq!(auto_columns(101), Some(vec![Id, Title, Description, Priority, Status, Created]));
+/// assert_eq!(auto_columns(100), Some(vec![Id, Title, Priority, Status, Created]));
+/// assert_eq!(auto_columns(77), Some(vec![Id, Title, Priority, Status]));
+/// assert_eq!(auto_columns(64), Some(vec![Id, Title, Status]));
+/// assert_eq!(auto_columns(53), None);
+/// ```
+pub fn auto_columns(width: usize) -> Option<Vec<Column>> {
+    let mut columns = AUTO_COLUMNS.to_vec();
+    let line_width = |columns: &[Column]| {
+        columns.iter().map(|c| c.auto_width()).sum::<usize>()
+            + SEPARATOR.len() * columns.len().saturating_sub(1)
+    };
+    for drop in AUTO_DROP_ORDER {
+        if line_width(&columns) <= width {
+            return Some(columns);
+        }
+        columns.retain(|column| *column != drop);
+    }
+    (line_width(&columns) <= width).then_some(columns)
+}
+
+fn terminal_width() -> Option<u16> {
+    // get_winsize() returns an Option with (width, height)
+    terminal_size().map(|(Width(w), Height(_h))| w)
+}
+
+fn splash_columns(out: &mut dyn Write, layout: &Layout, columns: &[Column]) -> io::Result<()> {
+    let cells: Vec<String> = columns
+        .iter()
+        .map(|column| layout.pad(*column, column.header(), column.auto_width()))
+        .collect();
+    writeln!(out, "{}", layout.clip(cells.join(SEPARATOR)))
+}
+
+fn print_todo_columns(
+    out: &mut dyn Write,
+    layout: &Layout,
+    verbose: bool,
+    row: &Row,
+    columns: &[Column],
+) -> io::Result<()> {
+    let cells: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let width = column.auto_width();
+            let value = match column {
+                Column::Id if verbose => row.todo.id.to_string()[..8].to_string(),
+                Column::Id => human_id(row.id),
+                Column::Title => title_cell(layout, row, width),
+                Column::Description => match &row.todo.data.description {
+                    Some(desc) => layout.truncate_str(desc, width),
+                    None => String::new(),
+                },
+                Column::Priority => layout.priority(row.todo.data.priority),
+                Column::Status => status_word(row),
+                Column::Created => layout.dates.date(row.todo.data.created_at),
+                Column::Tags => {
+                    return layout.tag_cell(&row.todo.data.tags, width, layout.row_color(row));
+                }
+            };
+            let cell = layout.pad(*column, &value, width);
+            match column {
+                Column::Title | Column::Description => layout.highlight(cell),
+                _ => cell,
+            }
+        })
+        .collect();
+    print_row(out, layout, row, cells.join(SEPARATOR))
+}
+
+// Prints a rendered row, tinted with the todo's color, or that of its
+// first tag with one configured, when color is on. The flag goes last so it
+// doesn't shift the columns.
+fn print_row(out: &mut dyn Write, layout: &Layout, row: &Row, mut line: String) -> io::Result<()> {
+    if let Some(flag) = row.flag {
+        line.push_str(&format!("  [{}]", flag));
+    }
+    let line = layout.clip(line);
+    match layout.row_color(row) {
+        Some(color) => writeln!(out, "{}{}{}", color.ansi(), line, RESET),
+        None => writeln!(out, "{}", line),
+    }
+}
 
 // Create splash screen based on the terminal size.
 
-fn splash_large() {
-    println!(
-        "{:^36} | {:^30} | {:^20} | {:^2} | {:^10} | CREATED",
-        "ID", "TITLE", "DESCRIPTION", "PRIORITY", "STATUS",
+fn splash_large(out: &mut dyn Write, layout: &Layout) -> io::Result<()> {
+    let bars = if layout.bars {
+        format!("{}PROGRESS/AGE", SEPARATOR)
+    } else {
+        String::new()
+    };
+    let header = format!(
+        "{} | {} | {} | {} | {} | CREATED{}",
+        layout.pad(Column::Id, "ID", 36),
+        layout.pad(Column::Title, "TITLE", 30),
+        layout.pad(Column::Description, "DESCRIPTION", 20),
+        layout.pad(Column::Priority, "PRIORITY", 2),
+        layout.pad(Column::Status, "STATUS", 10),
+        bars,
     );
+    writeln!(out, "{}", layout.clip(header))
 }
 
-fn splash_small() {
-    println!("{:^8} | {:^10} | STATUS", "ID", "TITLE");
+fn splash_small(out: &mut dyn Write, layout: &Layout) -> io::Result<()> {
+    let header = format!(
+        "{} | {} | STATUS",
+        layout.pad(Column::Id, "ID", 8),
+        layout.pad(Column::Title, "TITLE", 10),
+    );
+    writeln!(out, "{}", layout.clip(header))
 }
 
-pub fn splash() {
-    // Open the standard output terminal.
-    let size = terminal_size();
+/// Prints the header line for the layout `print_todo` will use.
+///
+/// ```
+/// use toto::term::{self, Layout, Row, Size};
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let id = cli.add(NewTodo::new("Pack"))?;
+/// let todo = cli.todo(id).unwrap();
+/// let layout = Layout {
+///     no_header: true,
+///     size: Some(Size::Small),
+///     ..Layout::default()
+/// };
+/// let mut out = Vec::new();
+/// term::splash(&mut out, &layout)?;
+/// term::print_todo(&mut out, &layout, false, &Row::new(&todo, Some(0)))?;
+/// let out = String::from_utf8(out)?;
+/// assert_eq!(out.lines().count(), 1);
+/// assert!(out.contains("Pack"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn splash(out: &mut dyn Write, layout: &Layout) -> io::Result<()> {
+    if layout.no_header {
+        return Ok(());
+    }
+    if layout.no_truncate {
+        let headers: Vec<&str> = AUTO_COLUMNS.iter().map(|column| column.header()).collect();
+        return writeln!(out, "{}", headers.join(SEPARATOR));
+    }
+    match layout.size {
+        Some(Size::Large) => return splash_large(out, layout),
+        Some(Size::Small) => return splash_small(out, layout),
+        None => {}
+    }
+    // A header would take as much room as the rows themselves.
+    if layout.tiny_width().is_some() {
+        return Ok(());
+    }
+    if layout.auto_columns
+        && let Some(columns) = layout.screen_width().and_then(auto_columns)
+    {
+        return splash_columns(out, layout, &columns);
+    }
 
-    // get_winsize() returns an Option with (width, height)
-    if let Some((Width(w), Height(_h))) = size {
+    if let Some(w) = layout.screen_width() {
         if w > SMALL_TERM {
-            splash_large();
+            splash_large(out, layout)
         } else {
-            splash_small();
+            splash_small(out, layout)
         }
     } else {
-        splash_small();
+        splash_small(out, layout)
     }
 }
 
-pub fn print_todo(verbose: bool, todo: &Todo, id: usize) {
-    // Open the standard output terminal.
-    let size = terminal_size();
-    // get_winsize() returns an Option with (width, height)
-    if let Some((Width(w), Height(_h))) = size {
+/// Prints a todo using the layout that fits the terminal.
+pub fn print_todo(
+    out: &mut dyn Write,
+    layout: &Layout,
+    verbose: bool,
+    row: &Row,
+) -> io::Result<()> {
+    if layout.no_truncate {
+        return print_todo_full(out, layout, verbose, row);
+    }
+    match layout.size {
+        Some(Size::Large) => return print_todo_large(out, layout, verbose, row),
+        Some(Size::Small) => return print_todo_small(out, layout, verbose, row),
+        None => {}
+    }
+    if let Some(width) = layout.tiny_width() {
+        return print_todo_tiny(out, layout, verbose, row, width);
+    }
+    if layout.auto_columns
+        && let Some(columns) = layout.screen_width().and_then(auto_columns)
+    {
+        return print_todo_columns(out, layout, verbose, row, &columns);
+    }
+
+    if let Some(w) = layout.screen_width() {
         if w > SMALL_TERM {
-            print_todo_large(verbose, todo, id);
+            print_todo_large(out, layout, verbose, row)
         } else {
-            print_todo_small(verbose, todo, id);
+            print_todo_small(out, layout, verbose, row)
         }
     } else {
-        print_todo_small(verbose, todo, id);
+        print_todo_small(out, layout, verbose, row)
     }
 }
 
 /// Prints a compact summary of a todo item suitable for a ~20-column terminal.
 /// It displays a short id, a truncated title, and a one-letter status indicator.
-pub fn print_todo_small(verbose: bool, todo: &Todo, id: usize) {
+pub fn print_todo_small(
+    out: &mut dyn Write,
+    layout: &Layout,
+    verbose: bool,
+    row: &Row,
+) -> io::Result<()> {
+    let (todo, id) = (row.todo, row.id);
     // Use the full UUID if verbose, otherwise the human-readable id.
     // For small output, we truncate the UUID to its first 8 characters.
     let id_str = if verbose {
@@ -61,70 +939,649 @@ pub fn print_todo_small(verbose: bool, todo: &Todo, id: usize) {
         }
     } else {
         // Format the human-readable id as a string.
-        id.to_string()
+        human_id(id)
     };
 
     // For the title, allow a maximum of 10 characters.
-    let max_title_len = 10;
-    let title = if todo.data.title.len() > max_title_len {
-        // Leave room for the ellipsis.
-        format!("{}...", &todo.data.title[..max_title_len.saturating_sub(3)])
-    } else {
-        todo.data.title.clone()
-    };
+    let title = title_cell(layout, row, 10);
 
-    // Use a one-character indicator for the status.
-    let status_initial = match todo.data.status {
-        TodoStatus::Pending => "P",
-        TodoStatus::InProgress => "I",
-        TodoStatus::Completed => "C",
-        TodoStatus::Deleted => "D",
-    };
+    let status_initial = status_initial(row);
 
     // Print in a compact format.
     // We allocate 8 characters for the id, 10 for the title, plus the status.
-    println!("{:^8} | {:^10} | {}", id_str, title, status_initial);
+    print_row(
+        out,
+        layout,
+        row,
+        format!(
+            "{} | {} | {}",
+            layout.pad(Column::Id, &id_str, 8),
+            layout.highlight(layout.pad(Column::Title, &title, 10)),
+            status_initial
+        ),
+    )
 }
 
 /// Prints a detailed summary of a todo item suitable for a ~50-60 column terminal.
 /// It displays a longer id, a longer title, a truncated description if available,
 /// the priority, status, and the creation date.
-pub fn print_todo_large(verbose: bool, todo: &Todo, id: usize) {
+///
+/// With `bars`, a parent's bar after the date shows how many of its
+/// children are done, and any other todo's how close it is to 30 days old:
+///
+/// ```
+/// use chrono::{TimeDelta, Utc};
+/// use toto::term::{self, Layout, Row, Size};
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let created_at = Some(Utc::now() - TimeDelta::days(15) - TimeDelta::hours(1));
+/// let id = cli.add(NewTodo { created_at, ..NewTodo::new("Move house") })?;
+/// let todo = cli.todo(id).unwrap();
+/// let layout = Layout { bars: true, color: false, size: Some(Size::Large), max_width: Some(200), ..Layout::default() };
+/// let render = |row: &Row| -> anyhow::Result<String> {
+///     let mut out = Vec::new();
+///     term::print_todo_large(&mut out, &layout, false, row)?;
+///     Ok(String::from_utf8(out)?)
+/// };
+///
+/// let aged = render(&Row::new(&todo, Some(0)))?;
+/// assert!(aged.ends_with(" | #####.....\n"), "{:?}", aged);
+/// let parent = render(&Row { progress: Some((3, 10)), ..Row::new(&todo, Some(0)) })?;
+/// assert!(parent.ends_with(" | ###.......\n"), "{:?}", parent);
+///
+/// let mut header = Vec::new();
+/// term::splash(&mut header, &layout)?;
+/// assert!(String::from_utf8(header)?.trim_end().ends_with("| CREATED | PROGRESS/AGE"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn print_todo_large(
+    out: &mut dyn Write,
+    layout: &Layout,
+    verbose: bool,
+    row: &Row,
+) -> io::Result<()> {
+    let (todo, id) = (row.todo, row.id);
     // Use the full UUID or human-readable id.
     let id_str = if verbose {
         todo.id.to_string()
     } else {
-        id.to_string()
+        human_id(id)
     };
 
     // For the title, allow up to 30 characters.
-    let max_title_len = 30;
-    let title = if todo.data.title.len() > max_title_len {
-        format!("{}...", &todo.data.title[..max_title_len.saturating_sub(3)])
-    } else {
-        todo.data.title.clone()
-    };
+    let title = title_cell(layout, row, 30);
 
     // For the description, allow up to 20 characters if it exists.
-    let max_desc_len = 20;
     let description = match &todo.data.description {
-        Some(desc) => {
-            if desc.len() > max_desc_len {
-                format!("{}...", &desc[..max_desc_len.saturating_sub(3)])
-            } else {
-                desc.clone()
-            }
-        }
+        Some(desc) => layout.truncate_str(desc, 20),
         None => String::from(""),
     };
 
-    let status = format!("{:?}", todo.data.status);
-    let created_at = todo.data.created_at.format("%Y-%m-%d").to_string();
+    let mut status = status_word(row);
+    // How long it has been, or was, in progress; shown only when asked for
+    // since it makes the column ragged.
+    if verbose && let Some(took) = todo.data.in_progress_for(Utc::now()) {
+        status.push_str(&format!(" ({})", time::format_duration(took)));
+    }
+    let mut created_at = layout.dates.date(todo.data.created_at);
+    if layout.bars {
+        let fraction = match row.progress {
+            Some((done, total)) if total > 0 => done as f64 / total as f64,
+            _ => {
+                let age = Utc::now() - todo.data.last_active_at();
+                age.num_hours() as f64 / (BAR_AGE_DAYS * 24) as f64
+            }
+        };
+        created_at.push_str(SEPARATOR);
+        created_at.push_str(&bar(fraction, BAR_WIDTH, !layout.color));
+    }
 
     // Print the detailed view.
     // Adjust column widths to fit within about 60 characters.
-    println!(
-        "{:^36} | {:^30} | {:^20} | {:^2} | {:^10} | {}",
-        id_str, title, description, todo.data.priority, status, created_at
-    );
+    print_row(
+        out,
+        layout,
+        row,
+        format!(
+            "{} | {} | {} | {} | {} | {}",
+            layout.pad(Column::Id, &id_str, 36),
+            layout.highlight(layout.pad(Column::Title, &title, 30)),
+            layout.highlight(layout.pad(Column::Description, &description, 20)),
+            layout.pad(Column::Priority, &layout.priority(todo.data.priority), 2),
+            layout.pad(Column::Status, &status, 10),
+            created_at
+        ),
+    )
+}
+
+/// Characters in a `--bars` bar.
+const BAR_WIDTH: usize = 10;
+/// Age at which a todo's age bar is full.
+const BAR_AGE_DAYS: i64 = 30;
+
+/// A bar `width` characters wide and filled in proportion to `fraction`,
+/// clamped to 0..=1. Plain bars use ASCII for terminals without block
+/// characters or color.
+///
+/// ```
+/// use toto::term::bar;
+///
+/// assert_eq!(bar(0.3, 10, false), "███░░░░░░░");
+/// assert_eq!(bar(0.6, 10, false), "██████░░░░");
+/// assert_eq!(bar(2.0, 5, true), "#####");
+/// assert_eq!(bar(0.0, 5, true), ".....");
+/// ```
+pub fn bar(fraction: f64, width: usize, plain: bool) -> String {
+    let filled = (fraction.clamp(0.0, 1.0) * width as f64).round() as usize;
+    let (full, empty) = if plain { ("#", ".") } else { ("█", "░") };
+    format!("{}{}", full.repeat(filled), empty.repeat(width - filled))
+}
+
+// Truncates the title behind the todo's icon, keeping room for a parent's
+// `(done/total)` suffix.
+fn title_cell(layout: &Layout, row: &Row, max_len: usize) -> String {
+    let prefix = match &row.todo.data.icon {
+        Some(icon) => format!("{} ", icon),
+        None => String::new(),
+    };
+    let suffix = match row.progress {
+        Some((done, total)) => format!(" ({}/{})", done, total),
+        None => String::new(),
+    };
+    let room = max_len.saturating_sub(prefix.chars().count() + suffix.chars().count());
+    format!(
+        "{}{}{}",
+        prefix,
+        layout.truncate_str(export::cap_line(&row.todo.data.title), room),
+        suffix
+    )
+}
+
+/// Prints every column unpadded and untruncated, for grepping. Line breaks
+/// in the description become spaces so each todo stays on one line.
+///
+/// ```
+/// use toto::cli::Todo;
+/// use toto::term::{self, Layout, Row};
+///
+/// let todo = |id: &str, title: &str, description: Option<&str>, status: &str| -> Todo {
+///     serde_json::from_value(serde_json::json!({
+///         "id": id,
+///         "data": {
+///             "title": title,
+///             "description": description,
+///             "priority": 1,
+///             "status": status,
+///             "created_at": 1_714_521_600_000i64,
+///             "in_progress_at": null,
+///             "completed_at": null,
+///             "deleted_at": null,
+///         },
+///     }))
+///     .unwrap()
+/// };
+/// let title = "Renew the passport before the summer holiday, the old one runs out in July";
+/// let description = "Forms are at the post office\nBring two photos";
+/// let passport = todo("a1000000-0000-4000-8000-000000000000", title, Some(description), "Pending");
+///
+/// let mut out = Vec::new();
+/// term::print_todo_full(&mut out, &Layout::default(), false, &Row::new(&passport, Some(0)))?;
+/// let row = String::from_utf8(out)?;
+/// assert!(row.starts_with(&format!("0 | {} | Forms are at the post office Bring two photos | 1 | ", title)));
+/// assert!(!row.contains("..."));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// Tags come last, under the TAGS heading `splash` prints for this layout:
+///
+/// ```
+/// use toto::cli::Todo;
+/// use toto::term::{self, Layout, Row};
+///
+/// let passport: Todo = serde_json::from_value(serde_json::json!({
+///     "id": "a1000000-0000-4000-8000-000000000000",
+///     "data": {
+///         "title": "Renew the passport", "description": null, "priority": 1,
+///         "status": "Pending", "created_at": 1_714_521_600_000i64, "in_progress_at": null,
+///         "completed_at": null, "deleted_at": null, "tags": ["travel", "paperwork"],
+///     },
+/// }))?;
+/// let layout = Layout { no_truncate: true, color: false, ..Layout::default() };
+/// let mut out = Vec::new();
+/// term::splash(&mut out, &layout)?;
+/// term::print_todo_full(&mut out, &layout, false, &Row::new(&passport, Some(0)))?;
+/// let out = String::from_utf8(out)?;
+/// let (header, row) = (out.lines().next().unwrap(), out.lines().nth(1).unwrap());
+/// // One cell under every heading.
+/// assert_eq!(header.split(" | ").count(), row.split(" | ").count());
+/// assert!(header.ends_with(" | TAGS") && row.ends_with(" | travel, paperwork"), "{}", out);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// `list --no-truncate` prints every row this way, however narrow the
+/// layout would otherwise be:
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-no-truncate-list-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> anyhow::Result<String> {
+///     let out = Buffer::default();
+///     Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))?;
+///     Ok(out.contents())
+/// };
+/// let title = "Find the receipt for the washing machine before the warranty runs out";
+/// run(&["add", "--title", title, "--priority", "2"])?;
+///
+/// for args in [&["list"][..], &["list", "--wide"], &["list", "--limit-width", "200"]] {
+///     assert!(!run(args)?.contains(title));
+/// }
+/// let full = run(&["list", "--no-truncate"])?;
+/// assert_eq!(full.lines().count(), 2);
+/// assert!(full.lines().nth(1).unwrap().starts_with(&format!("0 | {} | ", title)), "{}", full);
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn print_todo_full(
+    out: &mut dyn Write,
+    layout: &Layout,
+    verbose: bool,
+    row: &Row,
+) -> io::Result<()> {
+    // The same columns as the header `splash` prints for this layout.
+    let cells: Vec<String> = AUTO_COLUMNS
+        .iter()
+        .map(|column| {
+            let value = column_value(layout, verbose, row, *column, usize::MAX);
+            match column {
+                Column::Title | Column::Description => layout.highlight(value),
+                _ => value,
+            }
+        })
+        .collect();
+    print_row(out, layout, row, cells.join(SEPARATOR))
+}
+
+/// Prints `rows` as a table with `borders`, each column as wide as its
+/// widest value. Titles, descriptions and tags are cut short as in the auto
+/// layout unless `no_truncate` is set.
+///
+/// ```
+/// use toto::term::{self, Borders, Layout, Row};
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// cli.add(NewTodo::new("Pack"))?;
+/// cli.add(NewTodo { description: Some(String::from("Both bikes")), ..NewTodo::new("Oil the chains") })?;
+/// let todos = cli.todos();
+/// let rows: Vec<Row> = todos.iter().enumerate().map(|(id, todo)| Row::new(todo, Some(id))).collect();
+/// let layout = Layout { color: false, ..Layout::default() };
+///
+/// let mut out = Vec::new();
+/// term::print_table(&mut out, &layout, false, &rows, Borders::Unicode)?;
+/// let out = String::from_utf8(out)?;
+/// let lines: Vec<&str> = out.lines().collect();
+/// assert!(lines[0].starts_with("┌─") && lines[0].ends_with("─┐"));
+/// assert!(lines[2].starts_with("├─") && lines[5].ends_with("─┘"));
+/// assert!(lines[3].starts_with("│ 0  │ Pack           │"));
+/// let width = lines[0].chars().count();
+/// assert!(lines.iter().all(|line| line.chars().count() == width));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn print_table(
+    out: &mut dyn Write,
+    layout: &Layout,
+    verbose: bool,
+    rows: &[Row],
+    borders: Borders,
+) -> io::Result<()> {
+    let values: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            AUTO_COLUMNS
+                .iter()
+                .map(|column| {
+                    let room = if layout.no_truncate {
+                        usize::MAX
+                    } else {
+                        column.auto_width()
+                    };
+                    column_value(layout, verbose, row, *column, room)
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = AUTO_COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            values
+                .iter()
+                .map(|cells| cells[index].chars().count())
+                .fold(column.header().len(), usize::max)
+        })
+        .collect();
+
+    let ([top, middle, bottom], horizontal, vertical) = borders.glyphs();
+    let rule = |[left, junction, right]: [char; 3]| {
+        let segments: Vec<String> = widths
+            .iter()
+            .map(|width| horizontal.to_string().repeat(width + 2))
+            .collect();
+        format!("{}{}{}", left, segments.join(&junction.to_string()), right)
+    };
+    let line = |cells: Vec<String>| {
+        format!(
+            "{} {} {}",
+            vertical,
+            cells.join(&format!(" {} ", vertical)),
+            vertical
+        )
+    };
+
+    writeln!(out, "{}", rule(top))?;
+    if !layout.no_header {
+        let headers = AUTO_COLUMNS
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| layout.pad(*column, column.header(), *width))
+            .collect();
+        writeln!(out, "{}", line(headers))?;
+        writeln!(out, "{}", rule(middle))?;
+    }
+    for (row, cells) in rows.iter().zip(values) {
+        let cells = AUTO_COLUMNS
+            .iter()
+            .zip(cells)
+            .zip(&widths)
+            .map(|((column, value), width)| match column {
+                Column::Title | Column::Description => {
+                    layout.highlight(layout.pad(*column, &value, *width))
+                }
+                Column::Tags => layout.tag_cell(&row.todo.data.tags, *width, layout.row_color(row)),
+                _ => layout.pad(*column, &value, *width),
+            })
+            .collect();
+        print_row(out, layout, row, line(cells))?;
+    }
+    writeln!(out, "{}", rule(bottom))
+}
+
+// The unpadded value of `column`, with free text cut to `room` characters.
+fn column_value(layout: &Layout, verbose: bool, row: &Row, column: Column, room: usize) -> String {
+    let data = &row.todo.data;
+    match column {
+        Column::Id if verbose => row.todo.id.to_string(),
+        Column::Id => human_id(row.id),
+        Column::Title => title_cell(layout, row, room),
+        Column::Description => {
+            let description = data
+                .description
+                .as_deref()
+                .unwrap_or_default()
+                .lines()
+                .collect::<Vec<_>>()
+                .join(" ");
+            layout.truncate_str(export::cap_line(&description), room)
+        }
+        Column::Priority => layout.priority(data.priority),
+        Column::Status => status_word(row),
+        Column::Created => layout.dates.date(data.created_at),
+        Column::Tags => layout.truncate_str(export::cap_line(&data.tags.join(", ")), room),
+    }
+}
+
+/// Prints a todo as `ID S TITLE` in at most `width` characters, for panes
+/// too narrow for the small layout. The title gets whatever room is left.
+///
+/// ```
+/// use toto::cli::Todo;
+/// use toto::term::{self, Layout, Row};
+///
+/// let todo = |id: &str, title: &str, description: Option<&str>, status: &str| -> Todo {
+///     serde_json::from_value(serde_json::json!({
+///         "id": id,
+///         "data": {
+///             "title": title,
+///             "description": description,
+///             "priority": 1,
+///             "status": status,
+///             "created_at": 1_714_521_600_000i64,
+///             "in_progress_at": null,
+///             "completed_at": null,
+///             "deleted_at": null,
+///         },
+///     }))
+///     .unwrap()
+/// };
+/// let milk = todo("a1000000-0000-4000-8000-000000000000", "Milk", None, "Pending");
+/// let plants = todo("b2000000-0000-4000-8000-000000000000", "Water the plants", None, "InProgress");
+///
+/// let mut out = Vec::new();
+/// term::print_todo_tiny(&mut out, &Layout::default(), false, &Row::new(&milk, Some(0)), 15)?;
+/// term::print_todo_tiny(&mut out, &Layout::default(), false, &Row::new(&plants, Some(1)), 15)?;
+/// assert_eq!(String::from_utf8(out)?, "0 P Milk\n1 I Water th...\n");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// `list` switches to it, without a header, when there are fewer than
+/// `tiny_width` columns to lay rows out in:
+///
+/// ```
+/// use toto::output::{Buffer, Output};
+/// use toto::Cli;
+///
+/// # let dir = std::env::temp_dir().join(format!("toto-doc-tiny-list-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir)?;
+/// # let path = dir.to_string_lossy().into_owned();
+/// let run = |args: &[&str]| -> anyhow::Result<String> {
+///     let out = Buffer::default();
+///     Cli::new(path.clone())
+///         .with_output(Output::new(out.clone(), std::io::sink()))
+///         .run_from(["toto"].iter().chain(args))?;
+///     Ok(out.contents())
+/// };
+/// run(&["add", "--title", "Milk", "--priority", "1"])?;
+/// run(&["add", "--title", "Water the plants", "--priority", "2"])?;
+/// assert_eq!(run(&["list", "--limit-width", "15"])?, "0 P Milk\n1 P Water th...\n");
+/// // At the threshold the small layout is back.
+/// assert!(run(&["list", "--limit-width", "25"])?.starts_with("   ID    | TITLE"));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn print_todo_tiny(
+    out: &mut dyn Write,
+    layout: &Layout,
+    verbose: bool,
+    row: &Row,
+    width: usize,
+) -> io::Result<()> {
+    let id_str = if verbose {
+        row.todo.id.to_string()[..8].to_string()
+    } else {
+        human_id(row.id)
+    };
+    let prefix = format!("{} {} ", id_str, status_initial(row));
+    let room = width.saturating_sub(prefix.chars().count());
+    let title = layout.highlight(title_cell(layout, row, room));
+    print_row(out, layout, row, format!("{}{}", prefix, title))
+}
+
+// A one-character indicator for the status.
+fn status_initial(row: &Row) -> &'static str {
+    if row.id.is_none() {
+        "A"
+    } else {
+        row.todo.data.status.letter()
+    }
+}
+
+// The full status name, with archived todos marked as such.
+fn status_word(row: &Row) -> String {
+    if row.id.is_none() {
+        String::from("Archived")
+    } else {
+        format!("{:?}", row.todo.data.status)
+    }
+}
+
+// Archived todos have no human-readable id, so show a placeholder instead.
+fn human_id(id: Option<usize>) -> String {
+    match id {
+        Some(id) => id.to_string(),
+        None => String::from("-"),
+    }
+}
+
+// Width the detail view wraps to when the terminal's is unknown.
+const DETAILS_WIDTH: usize = 80;
+// Indent of description and note lines in the detail view.
+const DETAILS_INDENT: &str = "  ";
+
+/// Prints everything about one todo, one field per line. Unlike the list
+/// layouts nothing is cut short: descriptions and notes keep their line
+/// breaks and long lines wrap at the terminal width.
+///
+/// ```
+/// use toto::term;
+/// use toto::{Cli, NewTodo};
+///
+/// let mut cli = Cli::new(String::from("."));
+/// let description = "Pack:\n  - passport\n  - charger\n\nCheck in online.";
+/// let id = cli.add(NewTodo {
+///     description: Some(description.to_string()),
+///     ..NewTodo::new("Fly to Lisbon")
+/// })?;
+/// let mut out = Vec::new();
+/// term::print_details(&mut out, &Default::default(), &cli.todo(id).unwrap())?;
+/// let out = String::from_utf8(out)?;
+/// let shown = out.split_once("Description:\n").unwrap().1;
+/// assert_eq!(shown, "  Pack:\n    - passport\n    - charger\n\n  Check in online.\n");
+///
+/// // Without a terminal, long lines wrap at 80 columns.
+/// let long = "word ".repeat(30);
+/// let id = cli.add(NewTodo { description: Some(long), ..NewTodo::new("Long") })?;
+/// let mut out = Vec::new();
+/// term::print_details(&mut out, &Default::default(), &cli.todo(id).unwrap())?;
+/// let out = String::from_utf8(out)?;
+/// let shown: Vec<&str> = out.split_once("Description:\n").unwrap().1.lines().collect();
+/// assert_eq!(shown.len(), 2);
+/// assert!(shown.iter().all(|line| line.starts_with("  word") && line.len() <= 80));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn print_details(out: &mut dyn Write, dates: &DateStyle, todo: &Todo) -> io::Result<()> {
+    let data = &todo.data;
+    let width = terminal_width().map_or(DETAILS_WIDTH, usize::from);
+    let time = |dt: DateTime<Utc>| dates.date_time(dt);
+
+    let mut fields = vec![
+        ("Title", data.title.clone()),
+        ("ID", todo.id.to_string()),
+        ("Status", format!("{:?}", data.status)),
+        ("Priority", data.priority.to_string()),
+    ];
+    if !data.tags.is_empty() {
+        fields.push(("Tags", data.tags.join(", ")));
+    }
+    fields.push(("Created", time(data.created_at)));
+    let optional = [
+        ("Started", data.in_progress_at),
+        ("Completed", data.completed_at),
+        ("Deleted", data.deleted_at),
+        ("Due", data.due_at),
+        ("Snoozed until", data.snoozed_until),
+        ("Touched", data.last_touched_at),
+    ];
+    for (name, timestamp) in optional {
+        if let Some(timestamp) = timestamp {
+            fields.push((name, time(timestamp)));
+        }
+    }
+    if let Some(seconds) = data.snooze_interval {
+        fields.push((
+            "Snooze every",
+            time::format_duration(TimeDelta::seconds(seconds)),
+        ));
+    }
+    if let Some(parent) = data.parent {
+        fields.push(("Parent", parent.to_string()));
+    }
+    for blocker in &data.blocked_by {
+        fields.push(("Blocked by", blocker.to_string()));
+    }
+    for (name, value) in fields {
+        writeln!(out, "{:<14}{}", format!("{}:", name), value)?;
+    }
+
+    if let Some(description) = &data.description {
+        writeln!(out, "Description:")?;
+        for line in wrap_lines(description, width.saturating_sub(DETAILS_INDENT.len())) {
+            writeln!(out, "{}", format!("{}{}", DETAILS_INDENT, line).trim_end())?;
+        }
+    }
+    if !data.notes.is_empty() {
+        writeln!(out, "Notes:")?;
+        for note in &data.notes {
+            writeln!(out, "{}{}", DETAILS_INDENT, time(note.created_at))?;
+            let indent = DETAILS_INDENT.repeat(2);
+            for line in wrap_lines(&note.text, width.saturating_sub(indent.len())) {
+                writeln!(out, "{}", format!("{}{}", indent, line).trim_end())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits `text` into lines of at most `width` characters, keeping its own
+/// line breaks (blank lines included) and breaking long lines between words.
+/// Wrapped parts of an indented line keep its indentation. A word longer
+/// than a whole line is split wherever it must be.
+pub fn wrap_lines(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for source in text.lines() {
+        let indent: String = source.chars().take_while(|c| c.is_whitespace()).collect();
+        // Always leave room for at least one character after the indent.
+        let indent = if indent.chars().count() < width {
+            indent
+        } else {
+            String::new()
+        };
+        let room = width.saturating_sub(indent.chars().count()).max(1);
+        let mut line = String::new();
+        let mut used = 0;
+        for word in source.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            if used > 0 && used + 1 + word.len() > room {
+                lines.push(format!("{}{}", indent, std::mem::take(&mut line)));
+                used = 0;
+            }
+            if used > 0 {
+                line.push(' ');
+                used += 1;
+            }
+            while used + word.len() > room {
+                let rest = word.split_off(room - used);
+                line.extend(word);
+                lines.push(format!("{}{}", indent, std::mem::take(&mut line)));
+                used = 0;
+                word = rest;
+            }
+            used += word.len();
+            line.extend(word);
+        }
+        if line.is_empty() {
+            lines.push(line);
+        } else {
+            lines.push(format!("{}{}", indent, line));
+        }
+    }
+    lines
 }
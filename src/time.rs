@@ -0,0 +1,389 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone, Utc, Weekday,
+};
+
+// Formats accepted for a date and time without an offset.
+const NAIVE_FORMATS: [&str; 3] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+
+/// Parses a user supplied timestamp.
+///
+/// Accepts RFC 3339 (`2024-03-01T09:30:00Z`), a local date and time
+/// (`2024-03-01 09:30`) or a bare local date (`2024-03-01`, taken as midnight).
+pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    let naive = NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| format!("unrecognised timestamp '{}'", s))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .single()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| format!("'{}' is ambiguous or invalid in the local timezone", s))
+}
+
+/// Parses a duration such as `90m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(s: &str) -> Result<TimeDelta, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("duration '{}' needs a unit (m, h, d or w)", s))?;
+    let (amount, unit) = s.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+    let delta = match unit {
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        _ => {
+            return Err(format!(
+                "unknown duration unit '{}', expected m, h, d or w",
+                unit
+            ));
+        }
+    };
+    delta.ok_or_else(|| format!("duration '{}' is too large", s))
+}
+
+/// Parses a due date: anything `parse_timestamp` takes, `today`,
+/// `tomorrow`, a weekday such as `friday` or `fri` for the next one after
+/// today, all at local midnight, or a duration from now such as `3d` or
+/// `in 2w`.
+pub fn parse_due(s: &str) -> Result<DateTime<Utc>, String> {
+    parse_due_at(s, Local::now())
+}
+
+/// `parse_due` relative to `now` rather than the current time.
+///
+/// ```
+/// use chrono::{Local, TimeDelta, TimeZone};
+/// use toto::time::parse_due_at;
+///
+/// // A Wednesday.
+/// let now = Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+/// let midnight = |day| Local.with_ymd_and_hms(2024, 5, day, 0, 0, 0).unwrap().to_utc();
+/// assert_eq!(parse_due_at("friday", now), Ok(midnight(3)));
+/// assert_eq!(parse_due_at("Wed", now), Ok(midnight(8)));
+/// assert_eq!(parse_due_at("tomorrow", now), Ok(midnight(2)));
+/// assert_eq!(parse_due_at("2024-05-20", now), Ok(midnight(20)));
+/// assert_eq!(parse_due_at("in 3d", now), Ok(now.to_utc() + TimeDelta::days(3)));
+/// assert!(parse_due_at("someday", now).is_err());
+/// ```
+pub fn parse_due_at(s: &str, now: DateTime<Local>) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    if let Ok(dt) = parse_timestamp(s) {
+        return Ok(dt);
+    }
+    let today = now.date_naive();
+    let lower = s.to_lowercase();
+    let date = match lower.as_str() {
+        "today" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        _ => lower.parse::<Weekday>().ok().map(|weekday| {
+            let ahead =
+                (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday() - 1)
+                    % 7
+                    + 1;
+            today + TimeDelta::days(ahead.into())
+        }),
+    };
+    if let Some(date) = date {
+        return Local
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok_or_else(|| format!("'{}' is invalid in the local timezone", s));
+    }
+    let duration = lower.strip_prefix("in ").unwrap_or(&lower);
+    parse_duration(duration)
+        .map(|delta| now.with_timezone(&Utc) + delta)
+        .map_err(|_| {
+            format!(
+                "unrecognised due date '{}', expected e.g. 2024-03-01, tomorrow, friday or 3d",
+                s
+            )
+        })
+}
+
+/// Formats a duration for display in the two largest units that apply,
+/// e.g. `45m`, `3h 20m` or `2d 4h`, leaving out a zero second unit.
+///
+/// ```
+/// use chrono::TimeDelta;
+/// use toto::time::format_duration;
+///
+/// assert_eq!(format_duration(TimeDelta::minutes(45)), "45m");
+/// assert_eq!(format_duration(TimeDelta::minutes(200)), "3h 20m");
+/// assert_eq!(format_duration(TimeDelta::hours(52)), "2d 4h");
+/// assert_eq!(format_duration(TimeDelta::days(3)), "3d");
+/// ```
+pub fn format_duration(delta: TimeDelta) -> String {
+    let minutes = delta.num_minutes().max(0);
+    let (large, small) = if minutes < 60 {
+        return format!("{}m", minutes);
+    } else if minutes < 24 * 60 {
+        ((minutes / 60, 'h'), (minutes % 60, 'm'))
+    } else {
+        ((minutes / (24 * 60), 'd'), (minutes / 60 % 24, 'h'))
+    };
+    if small.0 == 0 {
+        format!("{}{}", large.0, large.1)
+    } else {
+        format!("{}{} {}{}", large.0, large.1, small.0, small.1)
+    }
+}
+
+/// Timezone that decides which calendar day a moment falls on, e.g. for
+/// `list --due-today`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tz {
+    /// The system timezone.
+    #[default]
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl Tz {
+    /// The calendar date of `dt` in this timezone.
+    pub fn date(self, dt: DateTime<Utc>) -> NaiveDate {
+        match self {
+            Tz::Local => dt.with_timezone(&Local).date_naive(),
+            Tz::Fixed(offset) => dt.with_timezone(&offset).date_naive(),
+        }
+    }
+}
+
+/// The `date_format` used unless another is configured.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Checks a strftime-style format such as `%d/%m/%Y` for `--date-format`.
+/// It has to fit on one short line and use only specifiers chrono knows, so
+/// a typo is caught here rather than when rendering.
+pub fn parse_date_format(s: &str) -> Result<String, String> {
+    if s.trim().is_empty() || s.len() > 64 || s.contains(char::is_control) {
+        return Err(format!(
+            "date format '{}' must be a single line of at most 64 bytes",
+            s
+        ));
+    }
+    if StrftimeItems::new(s).any(|item| item == Item::Error) {
+        return Err(format!("date format '{}' has an unknown % specifier", s));
+    }
+    Ok(s.to_string())
+}
+
+/// How dates are shown: in `format`, on the calendar of `tz`.
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use toto::time::{DateStyle, parse_tz};
+///
+/// let dt = Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap();
+/// let utc = parse_tz("utc").unwrap();
+/// let iso = DateStyle { tz: utc, ..DateStyle::default() };
+/// assert_eq!(iso.date(dt), "2024-03-01");
+/// let european = DateStyle {
+///     format: String::from("%d/%m/%Y"),
+///     tz: utc,
+///     ..DateStyle::default()
+/// };
+/// assert_eq!(european.date(dt), "01/03/2024");
+/// assert_eq!(european.date_time(dt), "01/03/2024 09:30");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DateStyle {
+    pub format: String,
+    pub tz: Tz,
+    /// Show dates closer to now than this as e.g. `2d ago` or `in 3h`
+    /// rather than in `format`, for `list --compact-dates`.
+    pub relative_within: Option<TimeDelta>,
+}
+
+impl Default for DateStyle {
+    fn default() -> Self {
+        Self {
+            format: String::from(DEFAULT_DATE_FORMAT),
+            tz: Tz::default(),
+            relative_within: None,
+        }
+    }
+}
+
+impl DateStyle {
+    /// ```
+    /// use chrono::{TimeDelta, Utc};
+    /// use toto::time::{DateStyle, parse_tz};
+    ///
+    /// let compact = DateStyle {
+    ///     tz: parse_tz("utc").unwrap(),
+    ///     relative_within: Some(TimeDelta::weeks(1)),
+    ///     ..DateStyle::default()
+    /// };
+    /// let now = Utc::now();
+    /// assert_eq!(compact.date(now - TimeDelta::days(2)), "2d ago");
+    /// assert_eq!(compact.date(now + TimeDelta::minutes(190)), "in 3h");
+    /// let two_months_ago = now - TimeDelta::days(61);
+    /// assert_eq!(
+    ///     compact.date(two_months_ago),
+    ///     two_months_ago.format("%Y-%m-%d").to_string()
+    /// );
+    /// ```
+    pub fn date(&self, dt: DateTime<Utc>) -> String {
+        self.relative(dt)
+            .unwrap_or_else(|| self.render(dt, &self.format))
+    }
+
+    /// The date followed by the time of day, for where minutes matter.
+    pub fn date_time(&self, dt: DateTime<Utc>) -> String {
+        self.relative(dt)
+            .unwrap_or_else(|| self.render(dt, &format!("{} %H:%M", self.format)))
+    }
+
+    // How long ago, or how soon, `dt` is in its largest unit, if that's
+    // within `relative_within`.
+    fn relative(&self, dt: DateTime<Utc>) -> Option<String> {
+        let within = self.relative_within?;
+        let delta = Utc::now() - dt;
+        if delta.abs() >= within {
+            return None;
+        }
+        if delta.abs() < TimeDelta::minutes(1) {
+            return Some(String::from("just now"));
+        }
+        let amount = format_duration(delta.abs());
+        let largest = amount.split(' ').next().unwrap_or_default();
+        Some(if delta > TimeDelta::zero() {
+            format!("{} ago", largest)
+        } else {
+            format!("in {}", largest)
+        })
+    }
+
+    fn render(&self, dt: DateTime<Utc>, format: &str) -> String {
+        match self.tz {
+            Tz::Local => dt.with_timezone(&Local).format(format).to_string(),
+            Tz::Fixed(offset) => dt.with_timezone(&offset).format(format).to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Tz {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tz::Local => f.write_str("local"),
+            Tz::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+/// Parses `local`, `utc` or a fixed offset such as `+02:00` or `-0530`.
+pub fn parse_tz(s: &str) -> Result<Tz, String> {
+    let s = s.trim();
+    match s.to_lowercase().as_str() {
+        "local" => return Ok(Tz::Local),
+        "utc" | "z" => return Ok(Tz::Fixed(FixedOffset::east_opt(0).unwrap())),
+        _ => {}
+    }
+    s.parse::<FixedOffset>().map(Tz::Fixed).map_err(|_| {
+        format!(
+            "unrecognised timezone '{}', expected local, utc or an offset like +02:00",
+            s
+        )
+    })
+}
+
+// Stored values below this are whole seconds from files written before
+// timestamps were kept in milliseconds. In milliseconds it is early 1970, in
+// seconds it is the year 2286, so the two ranges never overlap in practice.
+const SECONDS_CUTOFF: i64 = 10_000_000_000;
+
+fn from_stored(value: i64) -> Option<DateTime<Utc>> {
+    if value.abs() < SECONDS_CUTOFF {
+        DateTime::from_timestamp(value, 0)
+    } else {
+        DateTime::from_timestamp_millis(value)
+    }
+}
+
+/// Serializes timestamps as Unix milliseconds, still reading the whole
+/// seconds written by older versions.
+///
+/// ```
+/// use chrono::{DateTime, TimeZone, Utc};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Stamp(#[serde(with = "toto::time::ts_milliseconds")] DateTime<Utc>);
+///
+/// // Milliseconds survive a round trip.
+/// let at = Utc.timestamp_millis_opt(1_714_555_800_123).unwrap();
+/// let json = serde_json::to_string(&Stamp(at))?;
+/// assert_eq!(json, "1714555800123");
+/// assert_eq!(serde_json::from_str::<Stamp>(&json)?.0, at);
+///
+/// // Older files stored whole seconds, which are all below 1e10.
+/// let old: Stamp = serde_json::from_str("1714555800")?;
+/// assert_eq!(old.0, Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap());
+/// let last_second: Stamp = serde_json::from_str("9999999999")?;
+/// assert_eq!(last_second.0.timestamp(), 9_999_999_999);
+/// let first_milli: Stamp = serde_json::from_str("10000000000")?;
+/// assert_eq!(first_milli.0.timestamp_millis(), 10_000_000_000);
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+pub mod ts_milliseconds {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(dt.timestamp_millis())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let value = i64::deserialize(deserializer)?;
+        super::from_stored(value)
+            .ok_or_else(|| D::Error::custom(format!("timestamp {} is out of range", value)))
+    }
+}
+
+/// Optional variant of [`ts_milliseconds`].
+pub mod ts_milliseconds_option {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(
+        dt: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match dt {
+            Some(dt) => serializer.serialize_some(&dt.timestamp_millis()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Option::<i64>::deserialize(deserializer)? {
+            Some(value) => super::from_stored(value)
+                .map(Some)
+                .ok_or_else(|| D::Error::custom(format!("timestamp {} is out of range", value))),
+            None => Ok(None),
+        }
+    }
+}
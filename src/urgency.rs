@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+
+use crate::cli::TodoData;
+
+/// Weights for the urgency score, set with the `urgency_*` config keys.
+///
+/// The score is `priority * P + due * D` where
+///
+/// - `P` runs from 1 for priority 0 down to 0 for the default of 255, and
+/// - `D` is 1 once the todo is due, falls linearly to 0 at `horizon_days`
+///   before the due date, and is 0 for todos without a due date, so they
+///   neither gain nor lose against one due far in the future.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    pub priority: f64,
+    pub due: f64,
+    pub horizon_days: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            priority: 1.0,
+            due: 1.0,
+            horizon_days: 14.0,
+        }
+    }
+}
+
+/// The urgency of `todo` at `now`. Higher is more urgent.
+///
+/// ```
+/// use chrono::{DateTime, TimeDelta};
+/// use toto::cli::TodoData;
+/// use toto::urgency::{self, Weights};
+///
+/// let now = DateTime::from_timestamp(1_714_555_800, 0).unwrap();
+/// let todo = |priority: u8, due_in: Option<i64>| -> TodoData {
+///     let due_at = due_in.map(|days| (now + TimeDelta::days(days)).timestamp_millis());
+///     serde_json::from_value(serde_json::json!({
+///         "title": "Chore",
+///         "description": null,
+///         "priority": priority,
+///         "status": "Pending",
+///         "created_at": now.timestamp_millis(),
+///         "in_progress_at": null,
+///         "completed_at": null,
+///         "deleted_at": null,
+///         "due_at": due_at,
+///     }))
+///     .unwrap()
+/// };
+/// let todos = [
+///     todo(0, Some(-1)),
+///     todo(0, None),
+///     todo(255, Some(7)),
+///     todo(255, Some(30)),
+///     todo(255, None),
+/// ];
+/// let scores = |weights: &Weights| -> Vec<f64> {
+///     todos.iter().map(|todo| urgency::score(todo, weights, now)).collect()
+/// };
+/// assert_eq!(scores(&Weights::default()), [2.0, 1.0, 0.5, 0.0, 0.0]);
+/// let deadlines_first = Weights { due: 3.0, ..Weights::default() };
+/// assert_eq!(scores(&deadlines_first), [4.0, 1.0, 1.5, 0.0, 0.0]);
+/// ```
+pub fn score(todo: &TodoData, weights: &Weights, now: DateTime<Utc>) -> f64 {
+    weights.priority * priority_factor(todo.priority)
+        + weights.due * due_factor(todo.due_at, weights.horizon_days, now)
+}
+
+fn priority_factor(priority: u8) -> f64 {
+    f64::from(u8::MAX - priority) / f64::from(u8::MAX)
+}
+
+fn due_factor(due_at: Option<DateTime<Utc>>, horizon_days: f64, now: DateTime<Utc>) -> f64 {
+    let Some(due_at) = due_at else {
+        return 0.0;
+    };
+    let days_left = (due_at - now).num_seconds() as f64 / 86_400.0;
+    if days_left <= 0.0 {
+        1.0
+    } else if horizon_days <= 0.0 {
+        0.0
+    } else {
+        (1.0 - days_left / horizon_days).max(0.0)
+    }
+}